@@ -1,6 +1,8 @@
-use criterion::{black_box, criterion_group, criterion_main, BenchmarkId, Criterion};
+use criterion::{black_box, criterion_group, criterion_main, BatchSize, BenchmarkId, Criterion};
+use swhid::directory::{sort_entries, Entry};
 use swhid::{
-    ByteRange, Content, DiskDirectoryBuilder, LineRange, QualifiedSwhid, Swhid, WalkOptions,
+    ByteRange, Content, DirectoryBuildOptions, DirectoryCache, DiskDirectoryBuilder, LineRange,
+    QualifiedSwhid, RangeEnd, Swhid, WalkOptions,
 };
 use tempfile::TempDir;
 
@@ -52,6 +54,65 @@ fn bench_hash_functions(c: &mut Criterion) {
     group.finish();
 }
 
+fn bench_bulk_hashing(c: &mut Criterion) {
+    let mut group = c.benchmark_group("bulk_hashing");
+
+    let blobs: Vec<Vec<u8>> = (0..10_000)
+        .map(|i| format!("small blob {i}").into())
+        .collect();
+    let inputs: Vec<&[u8]> = blobs.iter().map(Vec::as_slice).collect();
+
+    group.bench_function("sequential_10k", |b| {
+        b.iter(|| {
+            black_box(&inputs)
+                .iter()
+                .map(|data| swhid::hash::hash_content(data))
+                .collect::<Vec<_>>()
+        })
+    });
+
+    #[cfg(feature = "parallel")]
+    group.bench_function("parallel_10k", |b| {
+        b.iter(|| swhid::hash::hash_contents_par(black_box(&inputs)))
+    });
+
+    group.finish();
+}
+
+fn bench_large_directory_sort(c: &mut Criterion) {
+    let mut group = c.benchmark_group("large_directory_sort");
+    group.sample_size(10);
+
+    // Synthetic directory with 1M entries, a third of them subdirectories
+    let entries: Vec<Entry> = (0..1_000_000u32)
+        .map(|i| {
+            let mode = if i % 3 == 0 { 0o040000 } else { 0o100644 };
+            // 7919 is coprime with 1M, so names are unique but not in order
+            let name = format!("entry{}", u64::from(i) * 7919 % 1_000_000);
+            Entry::new(name.into_bytes().into(), mode, [(i % 256) as u8; 20])
+        })
+        .collect();
+
+    let small_entries = entries[..10_000].to_vec();
+    group.bench_function("sort_entries_10k", |b| {
+        b.iter_batched(
+            || small_entries.clone(),
+            |mut entries| sort_entries(&mut entries).unwrap(),
+            BatchSize::SmallInput,
+        )
+    });
+
+    group.bench_function("sort_entries_1m", |b| {
+        b.iter_batched(
+            || entries.clone(),
+            |mut entries| sort_entries(&mut entries).unwrap(),
+            BatchSize::LargeInput,
+        )
+    });
+
+    group.finish();
+}
+
 fn bench_swhid_parsing(c: &mut Criterion) {
     let mut group = c.benchmark_group("swhid_parsing");
 
@@ -123,6 +184,51 @@ fn bench_directory_processing(c: &mut Criterion) {
     group.finish();
 }
 
+/// Creates a tree `depth` levels deep, where every directory has the same names
+fn create_repetitive_tree(path: &std::path::Path, depth: usize) {
+    for name in ["mod.rs", "lib.rs", "README.md"] {
+        std::fs::write(path.join(name), name).unwrap();
+    }
+    if depth == 0 {
+        return;
+    }
+    for name in ["src", "tests", "utils", "impl"] {
+        let subdir = path.join(name);
+        std::fs::create_dir(&subdir).unwrap();
+        create_repetitive_tree(&subdir, depth - 1);
+    }
+}
+
+fn bench_intern_names(c: &mut Criterion) {
+    let mut group = c.benchmark_group("intern_names");
+    group.sample_size(20);
+
+    let temp_dir = TempDir::new().unwrap();
+    create_repetitive_tree(temp_dir.path(), 5);
+
+    for intern_names in [false, true] {
+        let opts = DirectoryBuildOptions {
+            walk_options: WalkOptions {
+                intern_names,
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        group.bench_with_input(
+            BenchmarkId::new("directory_cache", intern_names),
+            &opts,
+            |b, opts| b.iter(|| DirectoryCache::new(temp_dir.path(), opts.clone()).unwrap()),
+        );
+        group.bench_with_input(BenchmarkId::new("swhid", intern_names), &opts, |b, opts| {
+            let dir = DiskDirectoryBuilder::new(temp_dir.path()).with_build_options(opts.clone());
+            b.iter(|| dir.swhid().unwrap())
+        });
+    }
+
+    group.finish();
+}
+
+#[allow(clippy::field_reassign_with_default)]
 fn bench_symlink_handling(c: &mut Criterion) {
     let mut group = c.benchmark_group("symlink_handling");
 
@@ -195,11 +301,11 @@ fn bench_qualified_swhid(c: &mut Criterion) {
                 .with_path("/src/lib.rs")
                 .with_lines(LineRange {
                     start: 10,
-                    end: Some(20),
+                    end: Some(RangeEnd::Inclusive(20)),
                 })
                 .with_bytes(ByteRange {
                     start: 100,
-                    end: Some(200),
+                    end: Some(RangeEnd::Inclusive(200)),
                 })
         })
     });
@@ -209,7 +315,7 @@ fn bench_qualified_swhid(c: &mut Criterion) {
         .with_path("/src/lib.rs")
         .with_lines(LineRange {
             start: 10,
-            end: Some(20),
+            end: Some(RangeEnd::Inclusive(20)),
         });
 
     group.bench_function("to_string", |b| {
@@ -263,9 +369,12 @@ criterion_group!(
     bench_content_creation,
     bench_content_swhid,
     bench_hash_functions,
+    bench_bulk_hashing,
+    bench_large_directory_sort,
     bench_swhid_parsing,
     bench_swhid_computation,
     bench_directory_processing,
+    bench_intern_names,
     bench_symlink_handling,
     bench_verification,
     bench_qualified_swhid,
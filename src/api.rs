@@ -0,0 +1,158 @@
+//! Queries to the Software Heritage archive API, when the `api` feature is enabled.
+
+use std::time::Duration;
+
+use crate::core::Swhid;
+use crate::error::SwhidError;
+
+/// Root of the public API of the Software Heritage archive
+pub const DEFAULT_API_URL: &str = "https://archive.softwareheritage.org/api/1";
+
+/// Maximum number of SWHIDs the `/known/` endpoint accepts per request
+const KNOWN_BATCH_LEN: usize = 1000;
+
+/// How [`ApiClient`] retries requests rejected because of the rate limit (HTTP 429)
+/// or an unavailable server (HTTP 503).
+#[derive(Debug, Clone)]
+pub struct RetryPolicy {
+    /// Number of retries of a request before failing, 0 to fail immediately
+    pub max_retries: u32,
+    /// Wait before the first retry, doubled before each next one, when the response
+    /// has no `Retry-After` header
+    pub initial_backoff: Duration,
+    /// Maximum wait between retries, also capping the wait asked by `Retry-After`
+    /// headers
+    pub max_backoff: Duration,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            max_retries: 5,
+            initial_backoff: Duration::from_secs(1),
+            max_backoff: Duration::from_secs(60),
+        }
+    }
+}
+
+/// Options for queries to the archive API.
+#[derive(Debug, Clone)]
+pub struct ApiOptions {
+    /// Root of the API, eg. of a mirror of the archive
+    pub base_url: String,
+    /// Authentication token, sent as a bearer token to raise the rate limit
+    pub token: Option<String>,
+    /// Retries of requests rejected because of the rate limit or an unavailable
+    /// server, by default up to 5 times
+    pub retry: RetryPolicy,
+}
+
+impl Default for ApiOptions {
+    fn default() -> Self {
+        Self {
+            base_url: DEFAULT_API_URL.to_owned(),
+            token: None,
+            retry: RetryPolicy::default(),
+        }
+    }
+}
+
+/// Client of the archive API, reusing connections between requests.
+///
+/// Requests are blocking, and retried according to [`ApiOptions::retry`], waiting
+/// as long as the `Retry-After` header of rejected requests asks to, up to
+/// [`RetryPolicy::max_backoff`].
+#[derive(Debug, Clone)]
+pub struct ApiClient {
+    client: reqwest::blocking::Client,
+    opts: ApiOptions,
+}
+
+impl ApiClient {
+    pub fn new(opts: ApiOptions) -> Self {
+        Self {
+            client: reqwest::blocking::Client::new(),
+            opts,
+        }
+    }
+
+    /// Returns whether each SWHID is already archived by Software Heritage, in the
+    /// same order, with the bulk `/known/` endpoint.
+    ///
+    /// SWHIDs are sent in batches of 1000, the most the API accepts per request.
+    pub fn known(&self, swhids: &[Swhid]) -> Result<Vec<bool>, SwhidError> {
+        let url = format!("{}/known/", self.opts.base_url.trim_end_matches('/'));
+        let mut known = Vec::with_capacity(swhids.len());
+        for batch in swhids.chunks(KNOWN_BATCH_LEN) {
+            let batch: Vec<String> = batch.iter().map(ToString::to_string).collect();
+            let body = serde_json::to_vec(&batch).map_err(api_error)?;
+            let response = self.post(&url, body)?;
+            // `{"<swhid>": {"known": <bool>}, ...}`
+            let statuses: serde_json::Value =
+                serde_json::from_slice(&response).map_err(api_error)?;
+            for swhid in &batch {
+                let status = statuses
+                    .get(swhid)
+                    .and_then(|status| status.get("known"))
+                    .and_then(serde_json::Value::as_bool)
+                    .ok_or_else(|| {
+                        api_error(format!("No status for {swhid} in the API response"))
+                    })?;
+                known.push(status);
+            }
+        }
+        Ok(known)
+    }
+
+    /// Posts a JSON `body` to `url`, retrying while the API asks to, and returns the
+    /// body of the response.
+    fn post(&self, url: &str, body: Vec<u8>) -> Result<Vec<u8>, SwhidError> {
+        let retry = &self.opts.retry;
+        let mut backoff = retry.initial_backoff;
+        let mut retries = 0;
+        loop {
+            let mut request = self
+                .client
+                .post(url)
+                .header(reqwest::header::CONTENT_TYPE, "application/json")
+                .body(body.clone());
+            if let Some(token) = &self.opts.token {
+                request = request.bearer_auth(token);
+            }
+            let response = request.send().map_err(api_error)?;
+            let status = response.status();
+            let retryable = status == reqwest::StatusCode::TOO_MANY_REQUESTS
+                || status == reqwest::StatusCode::SERVICE_UNAVAILABLE;
+            if !retryable || retries == retry.max_retries {
+                let response = response.error_for_status().map_err(api_error)?;
+                return Ok(response.bytes().map_err(api_error)?.to_vec());
+            }
+            let wait = retry_after(&response).unwrap_or(backoff);
+            std::thread::sleep(wait.min(retry.max_backoff));
+            backoff = backoff.saturating_mul(2).min(retry.max_backoff);
+            retries += 1;
+        }
+    }
+}
+
+/// Wait asked by the `Retry-After` header of a response, if given in seconds
+fn retry_after(response: &reqwest::blocking::Response) -> Option<Duration> {
+    let value = response.headers().get(reqwest::header::RETRY_AFTER)?;
+    let seconds = value.to_str().ok()?.trim().parse().ok()?;
+    Some(Duration::from_secs(seconds))
+}
+
+/// Returns whether each SWHID is already archived by Software Heritage, in the same
+/// order, with the bulk `/known/` endpoint of the public API.
+pub fn known(swhids: &[Swhid]) -> Result<Vec<bool>, SwhidError> {
+    known_with_options(swhids, &ApiOptions::default())
+}
+
+/// Same as [`known`], with the API, token and retry policy of `opts`.
+pub fn known_with_options(swhids: &[Swhid], opts: &ApiOptions) -> Result<Vec<bool>, SwhidError> {
+    ApiClient::new(opts.clone()).known(swhids)
+}
+
+fn api_error(e: impl Into<Box<dyn std::error::Error + Send + Sync>>) -> SwhidError {
+    SwhidError::Io(std::io::Error::other(e))
+}
@@ -0,0 +1,126 @@
+//! SWHIDs of directories read from archives, without extracting them to disk, when
+//! the `archive` feature is enabled.
+
+use std::collections::BTreeMap;
+use std::io::Read;
+
+use crate::core::{ObjectType, Swhid};
+use crate::directory::DirectorySink;
+use crate::error::SwhidError;
+use crate::hash::{hash_content, ContentHasher};
+use crate::permissions::EntryPerms;
+use crate::utils::normalize_relative_path;
+
+/// Limits on the contents read from an archive, to fail early on archives which
+/// would take too long to hash, eg. with entries claiming to be terabytes long.
+///
+/// Contents are hashed as they are read, so their size does not change memory use.
+#[derive(Debug, Clone, Default)]
+pub struct ArchiveOptions {
+    /// Fail on files larger than this many bytes, before reading them
+    pub max_entry_size: Option<u64>,
+    /// Fail when the files of the archive add up to more than this many bytes,
+    /// before reading the file going over the limit
+    pub max_total_size: Option<u64>,
+}
+
+/// Computes the SWHID of the directory stored in a tar stream, the same as the
+/// SWHID of the directory it would be extracted to.
+///
+/// Files are executable if any executable bit is set in their mode. Directory
+/// entries are not needed, and empty directories are omitted like in Git trees.
+/// Other special files (devices, fifos...) are ignored. When a path appears several
+/// times, the last entry wins, as when extracting.
+pub fn tar_swhid(reader: impl Read) -> Result<Swhid, SwhidError> {
+    tar_swhid_with_options(reader, &ArchiveOptions::default())
+}
+
+/// Same as [`tar_swhid`], failing on files over the limits of `opts`.
+pub fn tar_swhid_with_options(
+    reader: impl Read,
+    opts: &ArchiveOptions,
+) -> Result<Swhid, SwhidError> {
+    let mut archive = tar::Archive::new(reader);
+    let mut total_size: u64 = 0;
+    let mut files: BTreeMap<String, (Swhid, EntryPerms)> = BTreeMap::new();
+    for entry in archive.entries().map_err(SwhidError::Io)? {
+        let mut entry = entry.map_err(SwhidError::Io)?;
+        let entry_type = entry.header().entry_type();
+        let is_file = matches!(
+            entry_type,
+            tar::EntryType::Regular | tar::EntryType::Continuous | tar::EntryType::GNUSparse
+        );
+        if !(is_file || entry_type.is_symlink() || entry_type.is_hard_link()) {
+            continue;
+        }
+        let path = entry_path(&entry.path_bytes())?;
+        let file = if entry_type.is_symlink() {
+            let target = entry.link_name_bytes().unwrap_or_default();
+            (
+                Swhid::new(ObjectType::Content, hash_content(&target)),
+                EntryPerms::Symlink,
+            )
+        } else if entry_type.is_hard_link() {
+            let target = entry_path(&entry.link_name_bytes().unwrap_or_default())?;
+            match files.get(&target) {
+                Some(file) => file.clone(),
+                None => {
+                    return Err(SwhidError::Io(std::io::Error::other(format!(
+                        "Hard link {path} to {target}, which is not a file before it"
+                    ))))
+                }
+            }
+        } else {
+            let size = entry.size();
+            let too_large = |limit: &str| {
+                SwhidError::Io(std::io::Error::other(format!(
+                    "{path} is {size} bytes long, over the {limit}"
+                )))
+            };
+            if opts.max_entry_size.is_some_and(|max| size > max) {
+                return Err(too_large("maximum entry size"));
+            }
+            total_size = total_size.saturating_add(size);
+            if opts.max_total_size.is_some_and(|max| total_size > max) {
+                return Err(too_large("maximum total size of the archive"));
+            }
+            let executable = entry.header().mode().map_err(SwhidError::Io)? & 0o111 != 0;
+            (
+                Swhid::new(ObjectType::Content, hash_entry(&mut entry, size)?),
+                EntryPerms::File { executable },
+            )
+        };
+        files.insert(path, file);
+    }
+
+    let mut sink = DirectorySink::new();
+    for (path, (swhid, perms)) in files {
+        sink.add_entry(&path, &swhid, perms)?;
+    }
+    sink.finalize()
+}
+
+/// Hashes the `size` bytes of contents of `entry` as they are read
+fn hash_entry(entry: &mut impl Read, size: u64) -> Result<[u8; 20], SwhidError> {
+    let mut hasher = ContentHasher::new(size);
+    let mut buf = [0; 64 * 1024];
+    loop {
+        match entry.read(&mut buf) {
+            Ok(0) => break,
+            Ok(n) => hasher.update(&buf[..n]),
+            Err(e) if e.kind() == std::io::ErrorKind::Interrupted => (),
+            Err(e) => return Err(SwhidError::Io(e)),
+        }
+    }
+    hasher.finalize()
+}
+
+fn entry_path(bytes: &[u8]) -> Result<String, SwhidError> {
+    let path = std::str::from_utf8(bytes).map_err(|_| {
+        SwhidError::InvalidFormat(format!(
+            "Non UTF-8 path in archive: {}",
+            String::from_utf8_lossy(bytes)
+        ))
+    })?;
+    normalize_relative_path(path)
+}
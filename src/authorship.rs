@@ -0,0 +1,121 @@
+//! Timestamps of the `author`, `committer` and `tagger` lines of revision and
+//! release manifests.
+
+use std::fmt;
+
+use crate::Bytestring;
+
+/// A point in time and the timezone offset it was recorded in, as written after the
+/// name in authorship lines, eg. `1700000000 +0100`.
+///
+/// The offset is kept as raw bytes rather than a number of minutes: `-0000` (a UTC
+/// offset marked as unknown) must hash differently from `+0000`, and malformed
+/// offsets found in the wild must be preserved for identifiers to match.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Timestamp {
+    /// Seconds since the Unix epoch
+    pub seconds: i64,
+    /// Timezone offset, as `[+-]HHMM`
+    pub offset: Bytestring,
+}
+
+impl Timestamp {
+    pub fn new(seconds: i64, offset: impl Into<Bytestring>) -> Self {
+        Timestamp {
+            seconds,
+            offset: offset.into(),
+        }
+    }
+
+    /// Builds a timestamp from an offset in minutes east of UTC.
+    ///
+    /// `negative_utc` only matters when `offset_minutes` is zero, and selects `-0000`
+    /// (unknown offset) over `+0000`.
+    pub fn from_offset_minutes(seconds: i64, offset_minutes: i32, negative_utc: bool) -> Self {
+        let sign = if offset_minutes < 0 || (offset_minutes == 0 && negative_utc) {
+            '-'
+        } else {
+            '+'
+        };
+        let offset_minutes = offset_minutes.unsigned_abs();
+        let offset = format!("{sign}{:02}{:02}", offset_minutes / 60, offset_minutes % 60);
+        Timestamp::new(seconds, offset.into_bytes())
+    }
+
+    /// Builds a timestamp from the time of a Git signature, keeping `-0000` offsets.
+    #[cfg(feature = "git")]
+    pub fn from_git_time(time: git2::Time) -> Self {
+        Timestamp::from_offset_minutes(time.seconds(), time.offset_minutes(), time.sign() == '-')
+    }
+
+    /// Bytes written after the name in an authorship line
+    pub(crate) fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = self.seconds.to_string().into_bytes();
+        bytes.push(b' ');
+        bytes.extend_from_slice(&self.offset);
+        bytes
+    }
+}
+
+impl fmt::Display for Timestamp {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", String::from_utf8_lossy(&self.to_bytes()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn positive_offset() {
+        let timestamp = Timestamp::from_offset_minutes(1700000000, 60, false);
+        assert_eq!(timestamp.to_string(), "1700000000 +0100");
+        assert_eq!(
+            Timestamp::from_offset_minutes(0, 14 * 60, false).to_string(),
+            "0 +1400"
+        );
+    }
+
+    #[test]
+    fn negative_offset() {
+        let timestamp = Timestamp::from_offset_minutes(1700000000, -330, false);
+        assert_eq!(timestamp.to_string(), "1700000000 -0530");
+        // `negative_utc` is irrelevant for non-zero offsets
+        assert_eq!(
+            Timestamp::from_offset_minutes(-1, -720, true).to_string(),
+            "-1 -1200"
+        );
+    }
+
+    #[test]
+    fn unknown_offset() {
+        assert_eq!(
+            Timestamp::from_offset_minutes(1700000000, 0, true).to_string(),
+            "1700000000 -0000"
+        );
+        assert_eq!(
+            Timestamp::from_offset_minutes(1700000000, 0, false).to_string(),
+            "1700000000 +0000"
+        );
+    }
+
+    #[test]
+    fn raw_offset_is_kept() {
+        let timestamp = Timestamp::new(1700000000, b"+05".to_vec());
+        assert_eq!(timestamp.to_bytes(), b"1700000000 +05");
+    }
+
+    #[cfg(feature = "git")]
+    #[test]
+    fn from_git_time() {
+        assert_eq!(
+            Timestamp::from_git_time(git2::Time::new(1700000000, -330)).to_string(),
+            "1700000000 -0530"
+        );
+        assert_eq!(
+            Timestamp::from_git_time(git2::Time::new(1700000000, 90)).to_string(),
+            "1700000000 +0130"
+        );
+    }
+}
@@ -1,5 +1,6 @@
-use crate::core::{ObjectType, Swhid};
-use crate::hash::hash_content;
+use crate::core::{ObjectType, Swhid, SwhidObject};
+use crate::hash::{hash_content, hash_swhid_object};
+use crate::utils::constant_time_eq;
 
 /// SWHID v1.2 content object for computing content SWHIDs.
 ///
@@ -14,6 +15,7 @@ impl<B: AsRef<[u8]>> Content<B> {
     /// Create a new Content object from byte data.
     ///
     /// This implements SWHID v1.2 content object creation for any byte data.
+    #[allow(clippy::useless_conversion)]
     pub fn from_bytes(bytes: B) -> Self {
         Self {
             bytes: bytes.into(),
@@ -40,4 +42,77 @@ impl<B: AsRef<[u8]>> Content<B> {
         let digest = hash_content(self.bytes.as_ref());
         Swhid::new(ObjectType::Content, digest)
     }
+
+    /// Returns whether `expected` is the content SWHID of this content.
+    ///
+    /// Digests are compared in constant time, so verifying untrusted content does not
+    /// leak how much of the expected digest it matches.
+    pub fn verify(&self, expected: &Swhid) -> bool {
+        expected.object_type() == ObjectType::Content
+            && constant_time_eq(self.swhid().digest_bytes(), expected.digest_bytes())
+    }
+
+    /// Compute a content identifier using `typ` instead of `blob` in the object
+    /// header.
+    ///
+    /// `swhid_with_header("blob")` is the same as [`Content::swhid`]. Any other header
+    /// type produces a non-canonical SWHID, only meant for comparing alternative
+    /// identifier schemes.
+    pub fn swhid_with_header(&self, typ: &str) -> Swhid {
+        let digest = hash_swhid_object(typ, self.bytes.as_ref());
+        Swhid::new(ObjectType::Content, digest)
+    }
+
+    /// Compute the hex digest of the content identifier, without the `swh:1:cnt:` prefix.
+    pub fn swhid_hex(&self) -> String {
+        self.swhid().digest_hex()
+    }
+}
+
+impl Content {
+    /// SWHID of the empty content, eg. of an empty file.
+    ///
+    /// Equal to `Content::from_bytes(&[]).swhid()`, without hashing anything.
+    pub const fn empty() -> Swhid {
+        Swhid::new(
+            ObjectType::Content,
+            [
+                0xe6, 0x9d, 0xe2, 0x9b, 0xb2, 0xd1, 0xd6, 0x43, 0x4b, 0x8b, 0x29, 0xae, 0x77, 0x5a,
+                0xd8, 0xc2, 0xe4, 0x8c, 0x53, 0x91,
+            ],
+        )
+    }
+}
+
+#[cfg(feature = "tokio")]
+impl Content {
+    /// Read all of `reader` (eg. a `tokio::fs::File`) into a new Content object,
+    /// without blocking the async runtime.
+    pub async fn from_reader_async(
+        mut reader: impl tokio::io::AsyncRead + Unpin,
+    ) -> std::io::Result<Self> {
+        use tokio::io::AsyncReadExt;
+
+        let mut bytes = Vec::new();
+        reader.read_to_end(&mut bytes).await?;
+        Ok(Content::from_bytes(bytes.into_boxed_slice()))
+    }
+
+    /// Same as [`Content::swhid`], hashing on tokio's blocking thread pool so large
+    /// contents do not stall other tasks.
+    pub async fn swhid_async(self) -> Swhid {
+        tokio::task::spawn_blocking(move || self.swhid())
+            .await
+            .expect("hashing does not panic")
+    }
+}
+
+impl<B: AsRef<[u8]>> SwhidObject for Content<B> {
+    fn swhid(&self) -> Swhid {
+        Content::swhid(self)
+    }
+
+    fn object_type(&self) -> ObjectType {
+        ObjectType::Content
+    }
 }
@@ -30,20 +30,65 @@ impl ObjectType {
         }
     }
     pub fn from_tag(tag: &str) -> Result<Self, SwhidError> {
+        Self::parse_tag(tag).ok_or_else(|| SwhidError::InvalidObjectType(tag.to_owned()))
+    }
+    fn parse_tag(tag: &str) -> Option<Self> {
         match tag {
-            "cnt" => Ok(Self::Content),
-            "dir" => Ok(Self::Directory),
-            "rev" => Ok(Self::Revision),
-            "rel" => Ok(Self::Release),
-            "snp" => Ok(Self::Snapshot),
-            other => Err(SwhidError::InvalidObjectType(other.to_owned())),
+            "cnt" => Some(Self::Content),
+            "dir" => Some(Self::Directory),
+            "rev" => Some(Self::Revision),
+            "rel" => Some(Self::Release),
+            "snp" => Some(Self::Snapshot),
+            _ => None,
+        }
+    }
+    /// Type name of the object in Git (`blob`, `tree`, `commit` or `tag`), as hashed
+    /// in the object header. Snapshots have no Git equivalent and use `snapshot`.
+    pub fn git_object_type(self) -> &'static str {
+        match self {
+            ObjectType::Content => "blob",
+            ObjectType::Directory => "tree",
+            ObjectType::Revision => "commit",
+            ObjectType::Release => "tag",
+            ObjectType::Snapshot => "snapshot",
+        }
+    }
+    /// Parse a Git object type name (`blob`, `tree`, `commit` or `tag`).
+    pub fn from_git_name(name: &str) -> Result<Self, SwhidError> {
+        Self::parse_git_name(name).ok_or_else(|| SwhidError::InvalidObjectType(name.to_owned()))
+    }
+    fn parse_git_name(name: &str) -> Option<Self> {
+        match name {
+            "blob" => Some(Self::Content),
+            "tree" => Some(Self::Directory),
+            "commit" => Some(Self::Revision),
+            "tag" => Some(Self::Release),
+            _ => None,
         }
     }
 }
 
+impl FromStr for ObjectType {
+    type Err = SwhidError;
+
+    /// Parses either a SWHID tag (eg. `cnt`) or a Git object type name (eg. `blob`).
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::parse_tag(s)
+            .or_else(|| Self::parse_git_name(s))
+            .ok_or_else(|| SwhidError::InvalidObjectType(s.to_owned()))
+    }
+}
+
+/// Whether `s` is a valid digest: 40 lowercase hex digits
+fn is_digest_hex(s: &str) -> bool {
+    s.len() == 40 && s.bytes().all(|b| matches!(b, b'0'..=b'9'|b'a'..=b'f'))
+}
+
 /// A core SWHID: `swh:1:<tag>:<hex-digest>`
 #[derive(Debug, Clone, PartialEq, Eq, Hash)]
 pub struct Swhid {
+    /// Always one of [`Swhid::SUPPORTED_VERSIONS`]
+    version: u32,
     object_type: ObjectType,
     /// Lowercase hex sha1 digest (20 bytes -> 40 hex chars)
     digest: [u8; 20],
@@ -51,16 +96,52 @@ pub struct Swhid {
 
 impl Swhid {
     pub const VERSION: &'static str = "1";
-
-    pub fn new(object_type: ObjectType, digest: [u8; 20]) -> Self {
+    /// Versions accepted by [`Swhid::parse_any_version`]
+    pub const SUPPORTED_VERSIONS: &'static [u32] = &[1];
+    /// Default number of hex digits for [`Swhid::short`], the same as Git's
+    /// abbreviated object ids so that short SWHIDs line up with `git log --oneline`
+    pub const DEFAULT_SHORT_LEN: usize = 7;
+    /// Number of hex digits kept by [`Swhid::abbreviated`], one more than
+    /// [`Swhid::DEFAULT_SHORT_LEN`]: the archive holds many more objects than a Git
+    /// repository, so abbreviated SWHIDs are less likely to be ambiguous
+    pub const ABBREVIATED_LEN: usize = 8;
+
+    /// Create a SWHID with version [`Swhid::VERSION`]
+    pub const fn new(object_type: ObjectType, digest: [u8; 20]) -> Self {
         Self {
+            version: 1,
             object_type,
             digest,
         }
     }
+    /// Same as [`Swhid::new`], from the raw bytes of a Git object id, eg. read from a
+    /// packfile, failing if they are not 20 bytes long (SHA-1).
+    pub fn from_git_oid_bytes(oid: &[u8], object_type: ObjectType) -> Result<Self, SwhidError> {
+        let digest = oid
+            .try_into()
+            .map_err(|_| SwhidError::InvalidDigestLength(oid.len()))?;
+        Ok(Self::new(object_type, digest))
+    }
+    /// Scheme version, eg. `1` for `swh:1:...`
+    pub fn version(&self) -> u32 {
+        self.version
+    }
     pub fn object_type(&self) -> ObjectType {
         self.object_type
     }
+    /// Git name of the identified object's type, see [`ObjectType::git_object_type`]
+    pub fn object_git_type(&self) -> &'static str {
+        self.object_type.git_object_type()
+    }
+    /// The same digest with another object type, eg. to make a revision SWHID from
+    /// the hash of a Git commit that was parsed as a content SWHID.
+    ///
+    /// The digest is not recomputed, so the result only identifies an object if the
+    /// digest was computed for that type.
+    pub fn with_object_type(mut self, object_type: ObjectType) -> Self {
+        self.object_type = object_type;
+        self
+    }
     pub fn digest_bytes(&self) -> &[u8; 20] {
         &self.digest
     }
@@ -68,25 +149,162 @@ impl Swhid {
     pub fn digest_hex(&self) -> String {
         hex::encode(self.digest)
     }
-}
 
-impl Display for Swhid {
-    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        write!(
-            f,
+    /// Abbreviated form: `swh:1:<tag>:` followed by the first `n` hex digits of the
+    /// digest (or all of them if `n` is larger than the digest).
+    ///
+    /// [`Swhid::DEFAULT_SHORT_LEN`] is a sensible value for display. Never panics:
+    /// the digest is always ASCII hex, so truncating it cannot split a character.
+    pub fn short(&self, n: usize) -> String {
+        let mut digest_hex = self.digest_hex();
+        digest_hex.truncate(n);
+        format!(
             "swh:{}:{}:{}",
-            Self::VERSION,
+            self.version,
             self.object_type.as_tag(),
-            self.digest_hex()
+            digest_hex
         )
     }
+
+    /// Abbreviated form with [`Swhid::ABBREVIATED_LEN`] hex digits, eg.
+    /// `swh:1:cnt:e69de29b`.
+    pub fn abbreviated(&self) -> String {
+        self.short(Self::ABBREVIATED_LEN)
+    }
+
+    /// Returns whether `s` is a valid core SWHID, ie. whether parsing it would
+    /// succeed.
+    ///
+    /// Unlike parsing, this does not allocate.
+    pub fn is_valid(s: &str) -> bool {
+        let mut it = s.split(':');
+        match (it.next(), it.next(), it.next(), it.next(), it.next()) {
+            (Some("swh"), Some(Self::VERSION), Some(tag), Some(digest_hex), None) => {
+                ObjectType::parse_tag(tag).is_some() && is_digest_hex(digest_hex)
+            }
+            _ => false,
+        }
+    }
+}
+
+/// An object which has a SWHID.
+///
+/// This allows computing identifiers of objects of different types uniformly, eg.
+/// from a `Vec<Box<dyn SwhidObject>>`.
+pub trait SwhidObject {
+    /// Compute the SWHID v1.2 identifier of this object.
+    fn swhid(&self) -> Swhid;
+
+    /// Type of the object, as in its SWHID.
+    fn object_type(&self) -> ObjectType;
+}
+
+/// Formats the full `swh:1:<tag>:<digest>` form. Width, fill and precision flags
+/// are ignored; the output is always ASCII, so it can also be sliced at any byte
+/// offset, though [`Swhid::short`] is clearer for abbreviations.
+impl Display for Swhid {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "swh:{}:{}:", self.version, self.object_type.as_tag())?;
+        for byte in self.digest {
+            write!(f, "{byte:02x}")?;
+        }
+        Ok(())
+    }
+}
+
+/// Compares with the string form of the SWHID (as formatted by `Display`), without
+/// allocating.
+///
+/// Strings which are not valid SWHIDs are never equal, and neither are strings
+/// which parse to this SWHID but are not in canonical form, eg. with an uppercase
+/// digest.
+impl PartialEq<str> for Swhid {
+    fn eq(&self, other: &str) -> bool {
+        let mut matcher = PrefixMatcher(other);
+        fmt::write(&mut matcher, format_args!("{self}")).is_ok() && matcher.0.is_empty()
+    }
+}
+
+impl PartialEq<&str> for Swhid {
+    fn eq(&self, other: &&str) -> bool {
+        self == *other
+    }
+}
+
+impl PartialEq<Swhid> for str {
+    fn eq(&self, other: &Swhid) -> bool {
+        other == self
+    }
+}
+
+impl PartialEq<Swhid> for &str {
+    fn eq(&self, other: &Swhid) -> bool {
+        other == *self
+    }
+}
+
+/// Sink checking that everything written to it is a prefix of the remaining string
+struct PrefixMatcher<'a>(&'a str);
+
+impl fmt::Write for PrefixMatcher<'_> {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        self.0 = self.0.strip_prefix(s).ok_or(fmt::Error)?;
+        Ok(())
+    }
 }
 
 impl FromStr for Swhid {
     type Err = SwhidError;
 
     fn from_str(s: &str) -> Result<Self, Self::Err> {
-        // Expect: swh:1:<tag>:<digest-hex>
+        Self::parse_with_version(s, |ver| {
+            if ver == Self::VERSION {
+                Ok(1)
+            } else {
+                Err(SwhidError::InvalidVersion(ver.to_owned()))
+            }
+        })
+    }
+}
+
+impl Swhid {
+    /// Parse a core SWHID of any version in [`Swhid::SUPPORTED_VERSIONS`].
+    ///
+    /// Unlike [`FromStr`], which only accepts `swh:1:`, this returns
+    /// [`SwhidError::UnsupportedVersion`] for well-formed versions which are not
+    /// supported yet, so callers can tell them apart from malformed SWHIDs.
+    pub fn parse_any_version(s: &str) -> Result<Self, SwhidError> {
+        Self::parse_with_version(s, |ver| {
+            let invalid = || SwhidError::InvalidVersion(ver.to_owned());
+            if !ver.bytes().all(|b| b.is_ascii_digit()) || ver.starts_with('0') {
+                return Err(invalid());
+            }
+            let version: u32 = ver.parse().map_err(|_| invalid())?;
+            if Self::SUPPORTED_VERSIONS.contains(&version) {
+                Ok(version)
+            } else {
+                Err(SwhidError::UnsupportedVersion(version))
+            }
+        })
+    }
+
+    /// Parse a core SWHID, assuming `swh:1:` if it is missing, eg. `cnt:<digest>`.
+    ///
+    /// This is meant for user input such as pasted identifiers; the object type and
+    /// digest are still validated, and [`FromStr`] stays strict.
+    pub fn parse_lenient(s: &str) -> Result<Self, SwhidError> {
+        if s.starts_with("swh:") {
+            s.parse()
+        } else {
+            format!("swh:{}:{s}", Self::VERSION).parse()
+        }
+    }
+
+    fn parse_with_version(
+        s: &str,
+        parse_version: impl FnOnce(&str) -> Result<u32, SwhidError>,
+    ) -> Result<Self, SwhidError> {
+        // Expect: swh:<version>:<tag>:<digest-hex>
         let mut it = s.split(':');
         let scheme = it
             .next()
@@ -97,9 +315,7 @@ impl FromStr for Swhid {
         let ver = it
             .next()
             .ok_or_else(|| SwhidError::InvalidFormat(s.to_owned()))?;
-        if ver != Self::VERSION {
-            return Err(SwhidError::InvalidVersion(ver.to_owned()));
-        }
+        let version = parse_version(ver)?;
         let tag = it
             .next()
             .ok_or_else(|| SwhidError::InvalidFormat(s.to_owned()))?;
@@ -112,17 +328,17 @@ impl FromStr for Swhid {
             // too many parts
             return Err(SwhidError::InvalidFormat(s.to_owned()));
         }
-        if digest_hex.len() != 40
-            || !digest_hex
-                .bytes()
-                .all(|b| matches!(b, b'0'..=b'9'|b'a'..=b'f'))
-        {
+        if !is_digest_hex(digest_hex) {
             return Err(SwhidError::InvalidDigest(digest_hex.to_owned()));
         }
         let mut raw = [0u8; 20];
         hex::decode_to_slice(digest_hex, &mut raw)
             .map_err(|_| SwhidError::InvalidDigest(digest_hex.to_owned()))?;
-        Ok(Swhid::new(object_type, raw))
+        Ok(Swhid {
+            version,
+            object_type,
+            digest: raw,
+        })
     }
 }
 
@@ -168,6 +384,52 @@ impl<'de> serde::Deserialize<'de> for Swhid {
 mod tests {
     use super::*;
 
+    #[test]
+    fn parse_lenient() {
+        let digest = "e69de29bb2d1d6434b8b29ae775ad8c2e48c5391";
+        let expected: Swhid = format!("swh:1:cnt:{digest}").parse().unwrap();
+        assert_eq!(
+            Swhid::parse_lenient(&format!("cnt:{digest}")).unwrap(),
+            expected
+        );
+        assert_eq!(
+            Swhid::parse_lenient(&format!("swh:1:cnt:{digest}")).unwrap(),
+            expected
+        );
+        assert!(format!("cnt:{digest}").parse::<Swhid>().is_err());
+
+        // the object type cannot be guessed
+        assert!(matches!(
+            Swhid::parse_lenient(digest),
+            Err(SwhidError::InvalidObjectType(_))
+        ));
+        // the rest is still validated
+        assert!(Swhid::parse_lenient("cnt:e69de29b").is_err());
+        assert!(Swhid::parse_lenient(&format!("foo:{digest}")).is_err());
+        assert!(Swhid::parse_lenient(&format!("swh:2:cnt:{digest}")).is_err());
+    }
+
+    #[test]
+    fn eq_str() {
+        let swhid = Swhid::new(ObjectType::Content, [0xab; 20]);
+        let s = "swh:1:cnt:abababababababababababababababababababab";
+        assert!(swhid == s);
+        assert!(swhid == *s);
+        assert!(s == swhid);
+        assert_eq!(swhid, s);
+
+        assert!(swhid != "swh:1:dir:abababababababababababababababababababab");
+        assert!(swhid != "swh:1:cnt:abababababababababababababababababababac");
+        // prefixes and extensions of the canonical form
+        assert!(swhid != "swh:1:cnt:abababab");
+        assert!(swhid != "swh:1:cnt:abababababababababababababababababababab;origin=x");
+        // parses to the same SWHID, but not canonical
+        assert!(swhid != "swh:1:cnt:ABABABABABABABABABABABABABABABABABABABAB");
+        // malformed
+        assert!(swhid != "");
+        assert!(swhid != "not a swhid");
+    }
+
     #[test]
     fn roundtrip_core() {
         let id: Swhid = "swh:1:cnt:e69de29bb2d1d6434b8b29ae775ad8c2e48c5391"
@@ -205,6 +467,59 @@ mod tests {
         assert!(ObjectType::from_tag("CNT").is_err());
     }
 
+    #[test]
+    fn object_type_from_git_name() {
+        assert_eq!(
+            ObjectType::from_git_name("blob").unwrap(),
+            ObjectType::Content
+        );
+        assert_eq!(
+            ObjectType::from_git_name("tree").unwrap(),
+            ObjectType::Directory
+        );
+        assert_eq!(
+            ObjectType::from_git_name("commit").unwrap(),
+            ObjectType::Revision
+        );
+        assert_eq!(
+            ObjectType::from_git_name("tag").unwrap(),
+            ObjectType::Release
+        );
+        assert!(ObjectType::from_git_name("cnt").is_err());
+        assert!(ObjectType::from_git_name("snapshot").is_err());
+    }
+
+    #[test]
+    fn object_type_git_object_type() {
+        for (typ, name) in [
+            (ObjectType::Content, "blob"),
+            (ObjectType::Directory, "tree"),
+            (ObjectType::Revision, "commit"),
+            (ObjectType::Release, "tag"),
+            (ObjectType::Snapshot, "snapshot"),
+        ] {
+            assert_eq!(typ.git_object_type(), name);
+            assert_eq!(Swhid::new(typ, [0; 20]).object_git_type(), name);
+            if typ != ObjectType::Snapshot {
+                assert_eq!(ObjectType::from_git_name(name).unwrap(), typ);
+            }
+        }
+    }
+
+    #[test]
+    fn object_type_from_str() {
+        assert_eq!("rev".parse::<ObjectType>().unwrap(), ObjectType::Revision);
+        assert_eq!(
+            "commit".parse::<ObjectType>().unwrap(),
+            ObjectType::Revision
+        );
+        assert_eq!("snp".parse::<ObjectType>().unwrap(), ObjectType::Snapshot);
+        assert!(matches!(
+            "invalid".parse::<ObjectType>(),
+            Err(SwhidError::InvalidObjectType(name)) if name == "invalid"
+        ));
+    }
+
     #[test]
     fn object_type_equality() {
         assert_eq!(ObjectType::Content, ObjectType::Content);
@@ -242,6 +557,37 @@ mod tests {
         assert_eq!(swhid.digest_bytes(), &digest);
     }
 
+    #[test]
+    fn swhid_with_object_type() {
+        let cnt: Swhid = "swh:1:cnt:4b825dc642cb6eb9a060e54bf8d69288fbee4904"
+            .parse()
+            .unwrap();
+        let dir = cnt.clone().with_object_type(ObjectType::Directory);
+        assert_eq!(
+            dir.to_string(),
+            "swh:1:dir:4b825dc642cb6eb9a060e54bf8d69288fbee4904"
+        );
+        assert_eq!(dir.digest_bytes(), cnt.digest_bytes());
+        assert_eq!(dir.version(), cnt.version());
+    }
+
+    #[test]
+    fn swhid_from_git_oid_bytes() {
+        let oid = hex::decode("4b825dc642cb6eb9a060e54bf8d69288fbee4904").unwrap();
+        let swhid = Swhid::from_git_oid_bytes(&oid, ObjectType::Directory).unwrap();
+        assert_eq!(
+            swhid.to_string(),
+            "swh:1:dir:4b825dc642cb6eb9a060e54bf8d69288fbee4904"
+        );
+
+        for len in [0, 19, 21, 32] {
+            assert!(matches!(
+                Swhid::from_git_oid_bytes(&vec![0; len], ObjectType::Content),
+                Err(SwhidError::InvalidDigestLength(l)) if l == len
+            ));
+        }
+    }
+
     #[test]
     fn swhid_version() {
         assert_eq!(Swhid::VERSION, "1");
@@ -260,6 +606,35 @@ mod tests {
         );
     }
 
+    #[test]
+    fn swhid_short() {
+        let swhid: Swhid = "swh:1:cnt:e69de29bb2d1d6434b8b29ae775ad8c2e48c5391"
+            .parse()
+            .unwrap();
+        assert_eq!(swhid.short(7), "swh:1:cnt:e69de29");
+        assert_eq!(swhid.short(Swhid::DEFAULT_SHORT_LEN), "swh:1:cnt:e69de29");
+        assert_eq!(swhid.short(0), "swh:1:cnt:");
+        assert_eq!(swhid.short(40), swhid.to_string());
+        assert_eq!(swhid.short(1000), swhid.to_string());
+    }
+
+    #[test]
+    fn swhid_abbreviated() {
+        let digest = hex::decode("e69de29bb2d1d6434b8b29ae775ad8c2e48c5391").unwrap();
+        for (object_type, tag) in [
+            (ObjectType::Content, "cnt"),
+            (ObjectType::Directory, "dir"),
+            (ObjectType::Revision, "rev"),
+            (ObjectType::Release, "rel"),
+            (ObjectType::Snapshot, "snp"),
+        ] {
+            let swhid = Swhid::from_git_oid_bytes(&digest, object_type).unwrap();
+            let abbreviated = swhid.abbreviated();
+            assert_eq!(abbreviated, format!("swh:1:{tag}:e69de29b"));
+            assert!(swhid.to_string().starts_with(&abbreviated));
+        }
+    }
+
     #[test]
     fn swhid_display() {
         let digest = [
@@ -551,4 +926,63 @@ mod tests {
             .parse::<Swhid>()
             .is_err());
     }
+
+    #[test]
+    fn is_valid() {
+        assert!(Swhid::is_valid(
+            "swh:1:cnt:e69de29bb2d1d6434b8b29ae775ad8c2e48c5391"
+        ));
+        assert!(!Swhid::is_valid(
+            "swh:1:cnt:E69DE29BB2D1D6434B8B29AE775AD8C2E48C5391"
+        ));
+        assert!(!Swhid::is_valid(
+            "swh:1:cnt:e69de29bb2d1d6434b8b29ae775ad8c2e48c5391:"
+        ));
+        assert!(!Swhid::is_valid("swh:2:cnt"));
+        assert!(!Swhid::is_valid(""));
+    }
+
+    #[test]
+    fn parse_versions() {
+        let id: Swhid = "swh:1:cnt:e69de29bb2d1d6434b8b29ae775ad8c2e48c5391"
+            .parse()
+            .unwrap();
+        assert_eq!(id.version(), 1);
+        assert_eq!(
+            Swhid::parse_any_version("swh:1:cnt:e69de29bb2d1d6434b8b29ae775ad8c2e48c5391").unwrap(),
+            id
+        );
+
+        let v2 = "swh:2:cnt:e69de29bb2d1d6434b8b29ae775ad8c2e48c5391";
+        assert!(matches!(
+            v2.parse::<Swhid>(),
+            Err(SwhidError::InvalidVersion(ver)) if ver == "2"
+        ));
+        assert!(matches!(
+            Swhid::parse_any_version(v2),
+            Err(SwhidError::UnsupportedVersion(2))
+        ));
+
+        for ver in ["", "01", "+1", "x", "99999999999"] {
+            let s = format!("swh:{ver}:cnt:e69de29bb2d1d6434b8b29ae775ad8c2e48c5391");
+            assert!(matches!(
+                Swhid::parse_any_version(&s),
+                Err(SwhidError::InvalidVersion(_))
+            ));
+        }
+    }
+
+    proptest::proptest! {
+        #[test]
+        fn is_valid_matches_parse(s in "\\PC*") {
+            proptest::prop_assert_eq!(Swhid::is_valid(&s), s.parse::<Swhid>().is_ok());
+        }
+
+        #[test]
+        fn is_valid_matches_parse_near_valid(
+            s in "(swh|SWH)?(:[12])?(:(cnt|dir|rev|rel|snp|xyz))?(:[0-9a-fA-F]{38,41})?:?"
+        ) {
+            proptest::prop_assert_eq!(Swhid::is_valid(&s), s.parse::<Swhid>().is_ok());
+        }
+    }
 }
@@ -0,0 +1,151 @@
+//! Annotated hexdumps of the exact bytes hashed to compute SWHIDs.
+//!
+//! When a computed SWHID disagrees with another implementation, comparing these
+//! dumps shows which entry, branch or header differs.
+
+use std::fmt::Write;
+
+use crate::core::ObjectType;
+use crate::directory::{dir_manifest_unchecked, Directory};
+use crate::hash::swhid_object_header;
+use crate::release::{rel_manifest, Release};
+use crate::revision::{rev_manifest, Revision};
+use crate::snapshot::{snp_manifest_unchecked, Snapshot};
+
+const BYTES_PER_ROW: usize = 16;
+
+/// Format `bytes` as rows of offset, hex bytes and printable ASCII characters.
+///
+/// `offset` is the offset of the first byte, shown at the start of the first row.
+pub fn hexdump(bytes: &[u8], offset: usize) -> String {
+    let mut out = String::new();
+    for (i, row) in bytes.chunks(BYTES_PER_ROW).enumerate() {
+        write!(out, "{:08x} ", offset + i * BYTES_PER_ROW).unwrap();
+        for byte in row {
+            write!(out, " {byte:02x}").unwrap();
+        }
+        for _ in row.len()..BYTES_PER_ROW {
+            out.push_str("   ");
+        }
+        out.push_str("  |");
+        for &byte in row {
+            out.push(if byte.is_ascii_graphic() || byte == b' ' {
+                byte as char
+            } else {
+                '.'
+            });
+        }
+        out.push_str("|\n");
+    }
+    out
+}
+
+/// Hexdump of an object with the given header type, made of the concatenation of
+/// `sections`, each preceded by a `# <annotation>` line.
+///
+/// Offsets are relative to the start of the object header.
+pub fn object_dump(typ: &str, sections: &[(String, Vec<u8>)]) -> String {
+    let len = sections.iter().map(|(_, bytes)| bytes.len()).sum();
+    let header = swhid_object_header(typ, len);
+
+    let mut out = format!("# object header: {typ} {len}\n");
+    out.push_str(&hexdump(&header, 0));
+    let mut offset = header.len();
+    for (annotation, bytes) in sections {
+        writeln!(out, "# {annotation}").unwrap();
+        out.push_str(&hexdump(bytes, offset));
+        offset += bytes.len();
+    }
+    out
+}
+
+/// Annotated hexdump of a content object
+pub fn content_dump(data: &[u8]) -> String {
+    object_dump(
+        ObjectType::Content.git_object_type(),
+        &[("content".to_owned(), data.to_vec())],
+    )
+}
+
+/// Annotated hexdump of a directory manifest, one section per entry
+pub fn directory_dump(dir: &Directory) -> String {
+    let sections: Vec<_> = dir
+        .entries()
+        .iter()
+        .map(|entry| {
+            (
+                format!(
+                    "entry: {:o} {} {}",
+                    entry.mode(),
+                    String::from_utf8_lossy(entry.name()),
+                    hex::encode(entry.id())
+                ),
+                dir_manifest_unchecked(std::slice::from_ref(entry)),
+            )
+        })
+        .collect();
+    object_dump(ObjectType::Directory.git_object_type(), &sections)
+}
+
+/// Annotated hexdump of a revision manifest, one section per header and one for
+/// the message
+pub fn revision_dump(rev: &Revision) -> String {
+    object_dump(
+        ObjectType::Revision.git_object_type(),
+        &header_sections(&rev_manifest(rev)),
+    )
+}
+
+/// Annotated hexdump of a release manifest, one section per header and one for
+/// the message
+pub fn release_dump(rel: &Release) -> String {
+    object_dump(
+        ObjectType::Release.git_object_type(),
+        &header_sections(&rel_manifest(rel)),
+    )
+}
+
+/// Annotated hexdump of a snapshot manifest, one section per branch
+pub fn snapshot_dump(snp: &Snapshot) -> String {
+    let sections: Vec<_> = snp
+        .branches()
+        .iter()
+        .map(|branch| {
+            (
+                format!("branch: {}", String::from_utf8_lossy(&branch.name)),
+                snp_manifest_unchecked(std::slice::from_ref(branch)),
+            )
+        })
+        .collect();
+    object_dump(ObjectType::Snapshot.git_object_type(), &sections)
+}
+
+/// Split a revision or release manifest into its headers (including continuation
+/// lines) and message
+fn header_sections(mut manifest: &[u8]) -> Vec<(String, Vec<u8>)> {
+    let mut sections: Vec<(String, Vec<u8>)> = Vec::new();
+    while !manifest.is_empty() {
+        if manifest[0] == b'\n' {
+            sections.push(("message".to_owned(), manifest.to_vec()));
+            break;
+        }
+        let line_len = manifest
+            .iter()
+            .position(|&byte| byte == b'\n')
+            .map_or(manifest.len(), |pos| pos + 1);
+        let (line, rest) = manifest.split_at(line_len);
+        match sections.last_mut() {
+            // continuation of a multi-line header value
+            Some((_, previous)) if line[0] == b' ' => previous.extend_from_slice(line),
+            _ => {
+                let key = line.split(|&byte| byte == b' ').next().unwrap_or(line);
+                sections.push((
+                    format!("header: {}", String::from_utf8_lossy(key)),
+                    line.to_vec(),
+                ));
+            }
+        }
+        manifest = rest;
+    }
+    sections
+}
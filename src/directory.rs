@@ -1,10 +1,17 @@
 use std::borrow::Cow;
+use std::cell::RefCell;
+use std::cmp::Ordering;
+use std::collections::{HashMap, HashSet};
 use std::fs;
+use std::hash::{Hash, Hasher};
+use std::ops::Deref;
 use std::path::Path;
+use std::sync::Arc;
+use std::time::SystemTime;
 
 use std::path::PathBuf;
 
-use crate::core::{ObjectType, Swhid};
+use crate::core::{ObjectType, Swhid, SwhidObject};
 use crate::error::DirectoryError;
 use crate::hash::{hash_content, hash_swhid_object};
 use crate::permissions::{
@@ -13,15 +20,145 @@ use crate::permissions::{
 };
 use crate::utils::check_unique;
 
+mod checkpoint;
+#[cfg(feature = "sort-external")]
+mod external_sort;
+mod sink;
+
+pub use checkpoint::WalkCheckpoint;
+#[cfg(feature = "sort-external")]
+use external_sort::{ExternalSortError, ExternalSorter};
+pub use sink::DirectorySink;
+
 const DIRECTORY_MODE: u32 = 0o040000;
+/// Bits of a mode holding the type of the entry (`S_IFMT`)
+const FILE_TYPE_MASK: u32 = 0o170000;
+
+/// Number of bytes at the start of files checked for NUL bytes with
+/// [`WalkOptions::skip_binary`], as many as Git checks to tell binary files apart
+const BINARY_SNIFF_LEN: usize = 8000;
+
+/// Names of the repositories of version control systems, skipped with
+/// [`WalkOptions::skip_vcs_dirs`]
+const VCS_DIRS: [&[u8]; 3] = [b".git", b".hg", b".svn"];
+
+/// Number of entries sorted in memory at once with
+/// [`WalkOptions::sort_large_dirs_externally`]
+#[cfg(feature = "sort-external")]
+const EXTERNAL_SORT_RUN_LEN: usize = 100_000;
 
 /// Options for SWHID v1.2 directory walking and hashing.
-#[derive(Debug, Clone, Default)]
+#[derive(Debug, Clone)]
 pub struct WalkOptions {
     /// Whether to follow symlinks (note: not recommended; SWHID v1.2 uses link targets)
+    ///
+    /// Followed symlinks take the type of what they point to: a link to a file is
+    /// hashed as a copy of that file with mode `100644` or `100755`, and a link to a
+    /// directory is walked as a copy of that directory, so nothing in the result
+    /// records that there was a link.
     pub follow_symlinks: bool,
+    /// When following symlinks, fail on links resolving outside of the walked
+    /// directory instead of hashing files elsewhere on the system, eg. `/etc/passwd`.
+    ///
+    /// Defaults to `true`. Has no effect unless `follow_symlinks` is set.
+    pub confine_symlinks_to_root: bool,
+    /// When following symlinks, fail on links to directories instead of walking
+    /// them, as a link to one of its parents would make the walk loop until paths
+    /// become too long. Links to files are still followed.
+    ///
+    /// Has no effect unless `follow_symlinks` is set.
+    pub reject_symlinked_dirs: bool,
     /// Exclude glob patterns (very minimal: literal suffix match)
     pub exclude_suffixes: Vec<String>,
+    /// When computing identifiers, sort the entries of directories with millions of
+    /// entries in chunks spilled to temporary files, instead of all in memory.
+    ///
+    /// Has no effect on [`DiskDirectoryBuilder::build`], which returns all entries.
+    /// Computing identifiers fails unless the `sort-external` feature is enabled.
+    pub sort_large_dirs_externally: bool,
+    /// Omit subdirectories without any file, like Git which cannot represent them.
+    ///
+    /// Defaults to `true`, so that the SWHID of a checkout matches the tree of its
+    /// commit. Software Heritage's own tools keep empty directories when archiving
+    /// tarballs and other non-Git sources, which requires setting this to `false`.
+    pub prune_empty_dirs: bool,
+    /// Fail on files larger than this many bytes, instead of reading them.
+    pub max_file_size: Option<u64>,
+    /// Leave files larger than `max_file_size` out of the directory instead of
+    /// failing.
+    ///
+    /// The resulting SWHID is then not the identifier of the directory on disk.
+    pub skip_oversized_files: bool,
+    /// Report files found at several paths, eg. hardlinks, from
+    /// [`DiskDirectoryBuilder::build_with_hardlinks`].
+    ///
+    /// This does not change identifiers, which hash each path separately. Only
+    /// supported on Unix.
+    pub detect_hardlinks: bool,
+    /// Share a single allocation between entries with the same name, eg. the many
+    /// `src` or `mod.rs` of a large tree, instead of allocating each name.
+    ///
+    /// Names are compared in full, so this never changes identifiers. Mostly useful
+    /// with [`DirectoryCache`], which keeps the entries of every directory.
+    pub intern_names: bool,
+    /// Leave binary files out of the directory, eg. to identify only the source
+    /// code of a tree. Files are considered binary if their first 8000 bytes
+    /// contain a NUL byte, like Git does.
+    ///
+    /// The resulting SWHID is then not the identifier of the directory on disk.
+    pub skip_binary: bool,
+    /// Leave hidden files and directories (whose name starts with `.`) out of the
+    /// directory, eg. `.git` to identify a checkout without the repository
+    /// internals, or `.DS_Store` files.
+    ///
+    /// The resulting SWHID is then not the identifier of the directory on disk.
+    pub skip_hidden: bool,
+    /// Leave the repositories of version control systems (entries named `.git`,
+    /// `.hg` or `.svn`) out of the directory, so the SWHID of a working copy is the
+    /// one of the project tree, like `git write-tree` gives.
+    ///
+    /// Defaults to `true`. `.git` is skipped whether it is a directory or a file, as
+    /// in submodules and linked worktrees.
+    pub skip_vcs_dirs: bool,
+}
+
+impl Default for WalkOptions {
+    fn default() -> Self {
+        Self {
+            follow_symlinks: false,
+            confine_symlinks_to_root: true,
+            reject_symlinked_dirs: false,
+            exclude_suffixes: Vec::new(),
+            sort_large_dirs_externally: false,
+            prune_empty_dirs: true,
+            max_file_size: None,
+            skip_oversized_files: false,
+            detect_hardlinks: false,
+            intern_names: false,
+            skip_binary: false,
+            skip_hidden: false,
+            skip_vcs_dirs: true,
+        }
+    }
+}
+
+/// Counts of the entries hashed by a walk, from [`DiskDirectoryBuilder::stats`].
+///
+/// Only entries part of the directory are counted, ie. not excluded, skipped or
+/// pruned ones. Submodules are always zero, as walks on disk hash nested
+/// repositories as directories.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct DirStats {
+    /// Regular files, including executables and followed symlinks to files
+    pub files: u64,
+    /// Total size of the files
+    pub bytes: u64,
+    /// Symlinks which were not followed
+    pub symlinks: u64,
+    /// Subdirectories, at any depth
+    pub directories: u64,
+    /// Submodules (revision entries)
+    pub submodules: u64,
 }
 
 /// Options for building directories with permission handling.
@@ -37,6 +174,18 @@ pub struct DirectoryBuildOptions {
     pub walk_options: WalkOptions,
 }
 
+impl Default for DirectoryBuildOptions {
+    /// Best-effort policy, auto permission source
+    fn default() -> Self {
+        Self {
+            permissions_source: PermissionsSourceKind::Auto,
+            permissions_policy: PermissionPolicy::BestEffort,
+            permissions_manifest_path: None,
+            walk_options: WalkOptions::default(),
+        }
+    }
+}
+
 /// Manifest entry for building directories from explicit permissions.
 ///
 /// This represents a directory entry with explicit permission information,
@@ -51,34 +200,187 @@ pub struct ManifestEntry {
     pub target: Vec<u8>,
 }
 
-#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 /// Item in a [`Directory`]
+///
+/// Entries are ordered like in directory manifests (see [`Entry::swhid_cmp`]), then
+/// by mode and id, so sorting entries with distinct names gives the manifest order.
+///
+/// Like [`Entry::new`], deserializing accepts any mode, so that entries of Git trees
+/// with non-canonical modes round-trip. Use [`EntryPerms::from_mode`] to check the
+/// modes of untrusted entries, as [`Entry::try_new`] does.
 pub struct Entry {
-    /// raw bytes (no encoding assumptions)
-    name: Box<[u8]>,
+    /// raw bytes (no encoding assumptions), shared with other entries if interned,
+    /// see [`WalkOptions::intern_names`]
+    #[cfg_attr(feature = "serde", serde(with = "crate::hex_serde"))]
+    name: EntryName,
     /// SWHID v1.2 tree mode (compatible with Git tree mode)
     mode: u32,
     /// SWHID object id
+    #[cfg_attr(feature = "serde", serde(with = "crate::hex_serde"))]
     id: [u8; 20],
 }
 
 impl Entry {
+    /// Create an entry with any mode, written verbatim in manifests.
+    ///
+    /// Old Git repositories have trees with non-canonical modes (eg. `100664`),
+    /// which must be kept for identifiers to match the tree ids; use
+    /// [`Entry::try_new`] to only accept the modes listed in [`EntryPerms`].
     pub fn new(name: Box<[u8]>, mode: u32, id: [u8; 20]) -> Entry {
-        Self { name, mode, id }
+        Self {
+            name: EntryName::Owned(name),
+            mode,
+            id,
+        }
+    }
+
+    /// Same as [`Entry::new`], failing if `mode` is not one of the modes of
+    /// [`EntryPerms`], eg. for entries from untrusted input.
+    pub fn try_new(name: Box<[u8]>, mode: u32, id: [u8; 20]) -> Result<Entry, DirectoryError> {
+        if EntryPerms::from_mode(mode).is_err() {
+            return Err(DirectoryError::InvalidMode { name, mode });
+        }
+        Ok(Self::new(name, mode, id))
+    }
+
+    /// Create an entry pointing to the object identified by `swhid`.
+    ///
+    /// Fails if the type of the object does not match `perms`: files and symlinks
+    /// must point to contents, directories to directories and submodules to revisions.
+    pub fn from_swhid(
+        name: Box<[u8]>,
+        swhid: &Swhid,
+        perms: EntryPerms,
+    ) -> Result<Entry, DirectoryError> {
+        let expected_type = match perms {
+            EntryPerms::File { .. } | EntryPerms::Symlink => ObjectType::Content,
+            EntryPerms::Directory => ObjectType::Directory,
+            EntryPerms::RevisionRef => ObjectType::Revision,
+        };
+        if swhid.object_type() != expected_type {
+            return Err(DirectoryError::MismatchedObjectType {
+                name,
+                perms,
+                object_type: swhid.object_type(),
+            });
+        }
+        Ok(Self::new(
+            name,
+            perms.to_swh_mode_u32(),
+            *swhid.digest_bytes(),
+        ))
+    }
+
+    /// Name of the entry (raw bytes)
+    pub fn name(&self) -> &[u8] {
+        &self.name
+    }
+
+    /// SWHID v1.2 tree mode, eg. `0o100644`
+    pub fn mode(&self) -> u32 {
+        self.mode
+    }
+
+    /// Digest of the object the entry points to
+    pub fn id(&self) -> &[u8; 20] {
+        &self.id
     }
 
     fn is_dir(&self) -> bool {
-        self.mode & DIRECTORY_MODE != 0
+        // not just the directory bit, which submodules (`0o160000`) have too
+        self.mode & FILE_TYPE_MASK == DIRECTORY_MODE
     }
 
-    fn name_for_sort(&self) -> Cow<'_, [u8]> {
+    /// Compares entries in the order of directory manifests: by name, directory
+    /// names being compared as if they ended with `/`.
+    ///
+    /// This differs from comparing names, eg. a directory `foo` comes after a file
+    /// `foo.txt` since `/` is after `.`.
+    pub fn swhid_cmp(&self, other: &Entry) -> Ordering {
+        self.sort_bytes().cmp(other.sort_bytes())
+    }
+
+    /// Bytes of [`Entry::sort_key`], without allocating
+    fn sort_bytes(&self) -> impl Iterator<Item = u8> + '_ {
+        let slash = self.is_dir().then_some(b'/');
+        self.name.iter().copied().chain(slash)
+    }
+
+    /// Key entries are sorted by: the name, followed by `/` for directories.
+    ///
+    /// Like in Git, only directories get a `/`: submodules sort as files, even
+    /// though their mode shares the directory bit.
+    ///
+    /// This allocates, so it should be computed once per entry rather than once per
+    /// comparison.
+    fn sort_key(&self) -> Vec<u8> {
+        let mut key = Vec::with_capacity(self.name.len() + 1);
+        key.extend_from_slice(&self.name);
         if self.is_dir() {
-            let mut name = Vec::from(self.name.clone());
-            name.push(b'/');
-            Cow::Owned(name)
-        } else {
-            Cow::Borrowed(&self.name)
+            key.push(b'/');
         }
+        key
+    }
+}
+
+/// Name of an [`Entry`], shared with the other entries of the same name if interned
+#[derive(Debug, Clone)]
+enum EntryName {
+    Owned(Box<[u8]>),
+    Shared(Arc<[u8]>),
+}
+
+impl Deref for EntryName {
+    type Target = [u8];
+
+    fn deref(&self) -> &[u8] {
+        match self {
+            EntryName::Owned(name) => name,
+            EntryName::Shared(name) => name,
+        }
+    }
+}
+
+impl AsRef<[u8]> for EntryName {
+    fn as_ref(&self) -> &[u8] {
+        self
+    }
+}
+
+impl PartialEq for EntryName {
+    fn eq(&self, other: &Self) -> bool {
+        **self == **other
+    }
+}
+
+impl Eq for EntryName {}
+
+impl Hash for EntryName {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        (**self).hash(state)
+    }
+}
+
+#[cfg(feature = "serde")]
+impl crate::hex_serde::FromBytes for EntryName {
+    fn from_bytes(bytes: Vec<u8>) -> Option<Self> {
+        Some(EntryName::Owned(bytes.into()))
+    }
+}
+
+impl PartialOrd for Entry {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Entry {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.swhid_cmp(other)
+            .then_with(|| self.mode.cmp(&other.mode))
+            .then_with(|| self.id.cmp(&other.id))
     }
 }
 
@@ -92,7 +394,7 @@ impl From<ManifestEntry> for Entry {
             arr
         });
         Entry {
-            name: manifest.name.into_boxed_slice(),
+            name: EntryName::Owned(manifest.name.into()),
             mode: manifest.perms.to_swh_mode_u32(),
             id: id_array,
         }
@@ -100,6 +402,12 @@ impl From<ManifestEntry> for Entry {
 }
 
 fn is_excluded(name: &[u8], opts: &WalkOptions) -> bool {
+    if opts.skip_hidden && name.starts_with(b".") {
+        return true;
+    }
+    if opts.skip_vcs_dirs && VCS_DIRS.contains(&name) {
+        return true;
+    }
     if opts.exclude_suffixes.is_empty() {
         return false;
     }
@@ -112,14 +420,14 @@ fn is_excluded(name: &[u8], opts: &WalkOptions) -> bool {
 /// This implements the SWHID v1.2 directory tree format, which is compatible
 /// with Git's tree format for directory objects.
 pub fn dir_manifest(mut children: Vec<Entry>) -> Result<Vec<u8>, DirectoryError> {
-    sort_and_check_children(&mut children)?;
+    sort_entries(&mut children)?;
 
     Ok(dir_manifest_unchecked(&children))
 }
 
 /// Same as [`dir_manifest`] but assumes children are already sorted and validated with
-/// [`sort_and_check_children`]
-fn dir_manifest_unchecked(children: &[Entry]) -> Vec<u8> {
+/// [`sort_entries`]
+pub(crate) fn dir_manifest_unchecked(children: &[Entry]) -> Vec<u8> {
     let mut out = Vec::new();
     for e in children {
         // "<mode> <name>\0<id-bytes>"
@@ -133,23 +441,86 @@ fn dir_manifest_unchecked(children: &[Entry]) -> Vec<u8> {
     out
 }
 
-fn sort_and_check_children(children: &mut [Entry]) -> Result<(), DirectoryError> {
-    children.sort_unstable_by(|a, b| a.name_for_sort().cmp(&b.name_for_sort()));
+/// Sort directory entries in the SWHID v1.2 order, and check they are valid.
+///
+/// Entries are sorted by name, with directory names (but not submodule names)
+/// compared as if they had a trailing `/`. Returns an error if two entries have the
+/// same name, including a file and a directory, if a name contains a `\0` or `/`
+/// byte, or if it is empty, `.` or `..`.
+pub fn sort_entries(children: &mut [Entry]) -> Result<(), DirectoryError> {
+    #[cfg(feature = "tracing")]
+    tracing::trace!(entries = children.len(), "sorting entries");
+    sort_entries_unchecked(children);
 
-    check_unique(children.iter().map(|child| &child.name))
-        .map_err(|name| DirectoryError::DuplicateEntryName(name.clone()))?;
+    let duplicate = check_unique(children.iter().map(|child| &child.name))
+        .err()
+        .or_else(|| {
+            let mut collisions = FileDirCollisions::default();
+            children
+                .iter()
+                .find(|child| collisions.collides(child))
+                .map(|child| &child.name)
+        });
+    if let Some(name) = duplicate {
+        #[cfg(feature = "tracing")]
+        tracing::debug!(name = %String::from_utf8_lossy(name), "duplicate entry name");
+        return Err(DirectoryError::DuplicateEntryName(name[..].into()));
+    }
 
     for entry in children {
-        for byte in [b'\0', b'/'] {
-            if entry.name.contains(&byte) {
-                return Err(DirectoryError::InvalidByteInName {
-                    byte,
-                    name: entry.name.clone(),
-                });
-            }
+        check_name(entry)?;
+    }
+
+    Ok(())
+}
+
+/// Finds files and directories with the same name in entries fed in the SWHID
+/// order.
+///
+/// They are not necessarily adjacent: `foo.c` sorts between the file `foo` and the
+/// directory `foo` (as `foo/`). All the names sorting between them start with
+/// `foo`, so only the files whose names are prefixes of the current name are kept.
+#[derive(Default)]
+struct FileDirCollisions {
+    /// each a prefix of the next one
+    files: Vec<EntryName>,
+}
+
+impl FileDirCollisions {
+    /// Whether `entry` is a directory with the same name as a previous file
+    fn collides(&mut self, entry: &Entry) -> bool {
+        while self
+            .files
+            .last()
+            .is_some_and(|file| !entry.name.starts_with(file))
+        {
+            self.files.pop();
+        }
+        if entry.is_dir() {
+            self.files.last() == Some(&entry.name)
+        } else {
+            self.files.push(entry.name.clone());
+            false
         }
     }
+}
 
+fn sort_entries_unchecked(children: &mut [Entry]) {
+    children.sort_by_cached_key(Entry::sort_key);
+}
+
+fn check_name(entry: &Entry) -> Result<(), DirectoryError> {
+    if matches!(&*entry.name, b"" | b"." | b"..") {
+        return Err(DirectoryError::InvalidEntryName(entry.name[..].into()));
+    }
+    for byte in [b'\0', b'/'] {
+        if entry.name.contains(&byte) {
+            return Err(DirectoryError::InvalidByteInName {
+                byte,
+                name: entry.name[..].into(),
+            });
+        }
+    }
     Ok(())
 }
 
@@ -157,11 +528,10 @@ fn symlink_mode() -> u32 {
     0o120000
 }
 
-fn read_dir(
-    path: &Path,
+fn permission_source(
     root: &Path,
     opts: &DirectoryBuildOptions,
-) -> Result<Vec<Entry>, crate::error::SwhidError> {
+) -> Result<Box<dyn PermissionsSource>, crate::error::SwhidError> {
     use crate::permissions::{
         AutoPermissionsSource, FilesystemPermissionsSource, ManifestPermissionsSource,
     };
@@ -169,7 +539,7 @@ fn read_dir(
     use crate::permissions::{GitIndexPermissionsSource, GitTreePermissionsSource};
 
     // Create permission source based on options
-    let permission_source: Box<dyn PermissionsSource> = match opts.permissions_source {
+    Ok(match opts.permissions_source {
         PermissionsSourceKind::Auto => Box::new(AutoPermissionsSource::new(root)?),
         PermissionsSourceKind::Filesystem => Box::new(FilesystemPermissionsSource),
         #[cfg(feature = "git")]
@@ -198,7 +568,7 @@ fn read_dir(
                     "permissions_manifest_path is required when using Manifest source".to_string(),
                 )
             })?;
-            Box::new(ManifestPermissionsSource::load(manifest_path)?)
+            Box::new(ManifestPermissionsSource::load(manifest_path)?.with_root(root.to_path_buf()))
         }
         #[cfg(not(feature = "git"))]
         PermissionsSourceKind::GitIndex | PermissionsSourceKind::GitTree => {
@@ -210,8 +580,155 @@ fn read_dir(
             // Heuristic not implemented yet, fall back to filesystem
             Box::new(FilesystemPermissionsSource)
         }
+    })
+}
+
+/// The canonical path of `root` if followed symlinks must resolve under it, see
+/// [`WalkOptions::confine_symlinks_to_root`].
+fn symlink_confinement_root(
+    root: &Path,
+    opts: &DirectoryBuildOptions,
+) -> Result<Option<PathBuf>, crate::error::SwhidError> {
+    if !(opts.walk_options.follow_symlinks && opts.walk_options.confine_symlinks_to_root) {
+        return Ok(None);
+    }
+    fs::canonicalize(root).map(Some).map_err(|e| {
+        crate::error::SwhidError::Io(std::io::Error::other(format!(
+            "Failed to resolve {}: {}",
+            root.display(),
+            e
+        )))
+    })
+}
+
+/// Settings and state shared by all the directories read during a walk
+struct Walk<'a> {
+    opts: &'a DirectoryBuildOptions,
+    permission_source: &'a dyn PermissionsSource,
+    /// if set, followed symlinks must resolve under it, see
+    /// [`symlink_confinement_root`]
+    confinement_root: Option<&'a Path>,
+    /// if set, entries it does not keep are skipped
+    path_filter: Option<&'a PathFilter>,
+    names: &'a NameInterner,
+    /// counts of the entries read so far
+    stats: RefCell<DirStats>,
+    /// if set, the root of the walk and the identifiers of the directories under it
+    /// already hashed, which are reused and completed during the walk
+    checkpoint: Option<(&'a Path, &'a RefCell<WalkCheckpoint>)>,
+    /// if set, paths of the hashed files which may share their inode with others,
+    /// see [`record_hardlink`]
+    hardlinks: Option<RefCell<PathsByInode>>,
+}
+
+/// Paths of files, by device and inode number
+type PathsByInode = HashMap<(u64, u64), Vec<PathBuf>>;
+
+/// Reads the entries of the directory at `root`, also returning the paths of the
+/// files read which share their inode if `detect_hardlinks` is set.
+fn read_dir(
+    root: &Path,
+    opts: &DirectoryBuildOptions,
+    path_filter: Option<&PathFilter>,
+    detect_hardlinks: bool,
+) -> Result<(Vec<Entry>, PathsByInode), crate::error::SwhidError> {
+    let permission_source = permission_source(root, opts)?;
+    let confinement_root = symlink_confinement_root(root, opts)?;
+    let names = NameInterner::new(opts.walk_options.intern_names);
+    let walk = Walk {
+        opts,
+        permission_source: permission_source.as_ref(),
+        confinement_root: confinement_root.as_deref(),
+        path_filter,
+        names: &names,
+        stats: RefCell::default(),
+        checkpoint: None,
+        hardlinks: detect_hardlinks.then(RefCell::default),
     };
+    let entries = read_dir_recursive(root, &walk)?;
+    Ok((
+        entries,
+        walk.hardlinks.map(RefCell::into_inner).unwrap_or_default(),
+    ))
+}
+
+fn read_dir_recursive(path: &Path, walk: &Walk) -> Result<Vec<Entry>, crate::error::SwhidError> {
+    read_dir_shallow(path, walk, &mut |subdir_path| dir_id(subdir_path, walk))
+}
+
+/// Computes the identifier of the directory at `path`, or takes it from the
+/// checkpoint of the walk
+fn dir_id(path: &Path, walk: &Walk) -> Result<[u8; 20], crate::error::SwhidError> {
+    let Some((root, checkpoint)) = walk.checkpoint else {
+        return hash_dir(path, walk);
+    };
+    let rel_path = path.strip_prefix(root).unwrap_or(path);
+    if let Some(id) = checkpoint.borrow().get(rel_path) {
+        return Ok(*id);
+    }
+    let id = hash_dir(path, walk)?;
+    checkpoint.borrow_mut().insert(rel_path.to_path_buf(), id);
+    Ok(id)
+}
+
+/// Hashes the directory at `path`
+fn hash_dir(path: &Path, walk: &Walk) -> Result<[u8; 20], crate::error::SwhidError> {
+    let manifest_error = |e: DirectoryError| {
+        crate::error::SwhidError::Io(std::io::Error::other(format!(
+            "Failed to build directory manifest: {}",
+            e
+        )))
+    };
+    #[cfg(not(feature = "sort-external"))]
+    if walk.opts.walk_options.sort_large_dirs_externally {
+        return Err(crate::error::SwhidError::InvalidFormat(
+            "Sorting large directories externally requires the 'sort-external' feature".to_string(),
+        ));
+    }
+    #[cfg(feature = "sort-external")]
+    if walk.opts.walk_options.sort_large_dirs_externally {
+        let mut sorter = ExternalSorter::new(EXTERNAL_SORT_RUN_LEN);
+        for_each_dir_entry(
+            path,
+            walk,
+            &mut |subdir_path| dir_id(subdir_path, walk),
+            &mut |entry| sorter.push(entry),
+        )?;
+        return sorter.finish().map_err(|e| match e {
+            ExternalSortError::Directory(e) => manifest_error(e),
+            ExternalSortError::Io(e) => crate::error::SwhidError::Io(e),
+        });
+    }
+    let nested_entries = read_dir_recursive(path, walk)?;
+    let manifest = dir_manifest(nested_entries).map_err(manifest_error)?;
+    Ok(hash_swhid_object(
+        ObjectType::Directory.git_object_type(),
+        &manifest,
+    ))
+}
+
+/// Reads the entries of the directory at `path`, calling `subdir_id` to get the
+/// identifier of each subdirectory.
+fn read_dir_shallow(
+    path: &Path,
+    walk: &Walk,
+    subdir_id: &mut dyn FnMut(&Path) -> Result<[u8; 20], crate::error::SwhidError>,
+) -> Result<Vec<Entry>, crate::error::SwhidError> {
     let mut children: Vec<Entry> = Vec::new();
+    for_each_dir_entry(path, walk, subdir_id, &mut |entry| {
+        children.push(entry);
+        Ok(())
+    })?;
+    Ok(children)
+}
+
+/// Same as [`read_dir_shallow`], but passes entries to `f` as they are read.
+fn for_each_dir_entry(
+    path: &Path,
+    walk: &Walk,
+    subdir_id: &mut dyn FnMut(&Path) -> Result<[u8; 20], crate::error::SwhidError>,
+    f: &mut dyn FnMut(Entry) -> Result<(), crate::error::SwhidError>,
+) -> Result<(), crate::error::SwhidError> {
     for entry in fs::read_dir(path).map_err(|e| {
         crate::error::SwhidError::Io(std::io::Error::other(format!(
             "Failed to read directory {}: {}",
@@ -226,13 +743,17 @@ fn read_dir(
             )))
         })?;
         let file_name = entry.file_name();
-        let name_bytes = Box::from(file_name.as_os_str().as_encoded_bytes());
+        let name_bytes = file_name.as_os_str().as_encoded_bytes();
+        let opts = walk.opts;
 
-        if is_excluded(&name_bytes, &opts.walk_options) {
+        if is_excluded(name_bytes, &opts.walk_options) {
             continue;
         }
 
         let md = if opts.walk_options.follow_symlinks {
+            if let Some(root) = walk.confinement_root {
+                check_symlink_confined(&entry, root)?;
+            }
             fs::metadata(entry.path()).map_err(|e| {
                 crate::error::SwhidError::Io(std::io::Error::other(format!(
                     "Failed to read metadata for {}: {}",
@@ -250,23 +771,36 @@ fn read_dir(
             })?
         };
         let ft = md.file_type();
+        if walk
+            .path_filter
+            .is_some_and(|filter| !filter.keeps(&entry.path(), ft.is_dir()))
+        {
+            continue;
+        }
 
         if ft.is_dir() {
-            let nested_entries = read_dir(&entry.path(), root, opts)?;
-            let manifest = dir_manifest(nested_entries).map_err(|e: DirectoryError| {
-                crate::error::SwhidError::Io(std::io::Error::other(format!(
-                    "Failed to build directory manifest: {}",
-                    e
-                )))
-            })?;
-            let id = hash_swhid_object("tree", &manifest);
-            children.push(Entry {
-                name: name_bytes,
+            if opts.walk_options.follow_symlinks
+                && opts.walk_options.reject_symlinked_dirs
+                && entry.file_type().is_ok_and(|ft| ft.is_symlink())
+            {
+                return Err(crate::error::SwhidError::Io(std::io::Error::other(
+                    format!("Symlink {} points to a directory", entry.path().display()),
+                )));
+            }
+            let id = subdir_id(&entry.path())?;
+            if opts.walk_options.prune_empty_dirs && is_empty_dir_id(&id) {
+                continue;
+            }
+            walk.stats.borrow_mut().directories += 1;
+            f(Entry {
+                name: walk.names.intern(name_bytes),
                 mode: 0o040000,
                 id,
-            });
+            })?;
         } else if ft.is_symlink() {
-            // The content is the link target bytes
+            // The content is the link target bytes, as stored by the filesystem on
+            // Unix. On Windows they are the WTF-8 encoding of the target, which is
+            // its UTF-8 encoding for valid Unicode, keeping backslash separators
             let target = fs::read_link(entry.path()).map_err(|e| {
                 crate::error::SwhidError::Io(std::io::Error::other(format!(
                     "Failed to read symlink {}: {}",
@@ -276,12 +810,39 @@ fn read_dir(
             })?;
             let bytes = target.as_os_str().as_encoded_bytes();
             let id = hash_content(bytes);
-            children.push(Entry {
-                name: name_bytes,
+            walk.stats.borrow_mut().symlinks += 1;
+            f(Entry {
+                name: walk.names.intern(name_bytes),
                 mode: symlink_mode(),
                 id,
-            });
+            })?;
         } else if ft.is_file() {
+            if let Some(max_file_size) = opts.walk_options.max_file_size {
+                if md.len() > max_file_size {
+                    if opts.walk_options.skip_oversized_files {
+                        continue;
+                    }
+                    return Err(crate::error::SwhidError::Io(std::io::Error::other(
+                        format!(
+                            "File {} is larger than {} bytes ({} bytes)",
+                            entry.path().display(),
+                            max_file_size,
+                            md.len()
+                        ),
+                    )));
+                }
+            }
+            if opts.walk_options.skip_binary
+                && is_binary_file(&entry.path()).map_err(|e| {
+                    crate::error::SwhidError::Io(std::io::Error::other(format!(
+                        "Failed to read file {}: {}",
+                        entry.path().display(),
+                        e
+                    )))
+                })?
+            {
+                continue;
+            }
             let bytes = fs::read(entry.path()).map_err(|e| {
                 crate::error::SwhidError::Io(std::io::Error::other(format!(
                     "Failed to read file {}: {}",
@@ -290,23 +851,180 @@ fn read_dir(
                 )))
             })?;
             let id = hash_content(&bytes);
+            #[cfg(feature = "tracing")]
+            tracing::trace!(path = %entry.path().display(), bytes = bytes.len(), "hashed file");
 
             // Use permission source to determine executable bit
-            let exec = permission_source.executable_of(&entry.path())?;
+            let exec = walk.permission_source.executable_of(&entry.path())?;
             let perms = resolve_file_permissions(exec, opts.permissions_policy, &entry.path())?;
             let mode = perms.to_swh_mode_u32();
 
-            children.push(Entry {
-                name: name_bytes,
+            let mut stats = walk.stats.borrow_mut();
+            stats.files += 1;
+            stats.bytes += bytes.len() as u64;
+            drop(stats);
+            record_hardlink(walk, entry.path(), &md);
+            f(Entry {
+                name: walk.names.intern(name_bytes),
                 mode,
                 id,
-            });
+            })?;
         } else {
             // ignore special files
             continue;
         }
     }
-    Ok(children)
+    Ok(())
+}
+
+/// Whether the first [`BINARY_SNIFF_LEN`] bytes of the file contain a NUL byte
+fn is_binary_file(path: &Path) -> std::io::Result<bool> {
+    use std::io::Read;
+
+    let mut start = Vec::with_capacity(BINARY_SNIFF_LEN);
+    fs::File::open(path)?
+        .take(BINARY_SNIFF_LEN as u64)
+        .read_to_end(&mut start)?;
+    Ok(start.contains(&0))
+}
+
+/// Records the path of a hashed file in [`Walk::hardlinks`], if the file may be
+/// found at other paths
+#[cfg(unix)]
+fn record_hardlink(walk: &Walk, path: PathBuf, md: &fs::Metadata) {
+    use std::os::unix::fs::MetadataExt;
+
+    let Some(hardlinks) = &walk.hardlinks else {
+        return;
+    };
+    // followed symlinks may point to files with a single link
+    if md.nlink() > 1 || walk.opts.walk_options.follow_symlinks {
+        hardlinks
+            .borrow_mut()
+            .entry((md.dev(), md.ino()))
+            .or_default()
+            .push(path);
+    }
+}
+
+#[cfg(not(unix))]
+fn record_hardlink(_walk: &Walk, _path: PathBuf, _md: &fs::Metadata) {}
+
+/// Sorted groups of the paths in `paths_by_inode` sharing the same inode, relative
+/// to `root`, see [`DiskDirectoryBuilder::build_with_hardlinks`]
+fn hardlink_groups(paths_by_inode: PathsByInode, root: &Path) -> Vec<Vec<PathBuf>> {
+    let mut groups: Vec<_> = paths_by_inode
+        .into_values()
+        .filter(|paths| paths.len() > 1)
+        .map(|paths| {
+            let mut paths: Vec<_> = paths
+                .into_iter()
+                .map(|path| {
+                    path.strip_prefix(root)
+                        .map(Path::to_path_buf)
+                        .unwrap_or(path)
+                })
+                .collect();
+            paths.sort();
+            paths
+        })
+        .collect();
+    groups.sort();
+    groups
+}
+
+/// Paths set with [`DiskDirectoryBuilder::with_path_filter`], joined to the root so
+/// they can be compared to the paths of entries
+struct PathFilter {
+    paths: HashSet<PathBuf>,
+    /// directories containing some of `paths`
+    parents: HashSet<PathBuf>,
+}
+
+impl PathFilter {
+    fn new(root: &Path, paths: &HashSet<PathBuf>) -> Self {
+        let paths: HashSet<_> = paths.iter().map(|path| root.join(path)).collect();
+        let parents = paths
+            .iter()
+            .flat_map(|path| path.ancestors().skip(1))
+            .map(Path::to_path_buf)
+            .collect();
+        Self { paths, parents }
+    }
+
+    /// Whether the entry at `path` is in the set, is in a directory of the set, or is
+    /// a directory leading to paths of the set
+    fn keeps(&self, path: &Path, is_dir: bool) -> bool {
+        path.ancestors()
+            .any(|ancestor| self.paths.contains(ancestor))
+            || (is_dir && self.parents.contains(path))
+    }
+}
+
+/// Entry names seen during a walk, see [`WalkOptions::intern_names`]
+#[derive(Debug)]
+struct NameInterner(Option<RefCell<HashSet<Arc<[u8]>>>>);
+
+impl NameInterner {
+    fn new(enabled: bool) -> Self {
+        Self(enabled.then(RefCell::default))
+    }
+
+    /// A copy of `name`, shared with previous copies of the same name if enabled
+    fn intern(&self, name: &[u8]) -> EntryName {
+        let Some(names) = &self.0 else {
+            return EntryName::Owned(name.into());
+        };
+        let mut names = names.borrow_mut();
+        if let Some(interned) = names.get(name) {
+            return EntryName::Shared(interned.clone());
+        }
+        let interned: Arc<[u8]> = name.into();
+        names.insert(interned.clone());
+        EntryName::Shared(interned)
+    }
+
+    /// Forget the names no entry uses anymore
+    fn prune(&self) {
+        if let Some(names) = &self.0 {
+            names
+                .borrow_mut()
+                .retain(|name| Arc::strong_count(name) > 1);
+        }
+    }
+}
+
+/// Whether `id` is the identifier of a directory without entries
+fn is_empty_dir_id(id: &[u8; 20]) -> bool {
+    *id == hash_swhid_object(ObjectType::Directory.git_object_type(), b"")
+}
+
+/// Fails if `entry` is a symlink resolving outside of `root`, which must be canonical.
+fn check_symlink_confined(
+    entry: &fs::DirEntry,
+    root: &Path,
+) -> Result<(), crate::error::SwhidError> {
+    if !entry.file_type().is_ok_and(|ft| ft.is_symlink()) {
+        return Ok(());
+    }
+    let target = fs::canonicalize(entry.path()).map_err(|e| {
+        crate::error::SwhidError::Io(std::io::Error::other(format!(
+            "Failed to resolve symlink {}: {}",
+            entry.path().display(),
+            e
+        )))
+    })?;
+    if !target.starts_with(root) {
+        return Err(crate::error::SwhidError::Io(std::io::Error::other(
+            format!(
+                "Symlink {} points to {}, outside of {}",
+                entry.path().display(),
+                target.display(),
+                root.display()
+            ),
+        )));
+    }
+    Ok(())
 }
 
 /// SWHID v1.2 directory object for computing directory SWHIDs.
@@ -321,7 +1039,7 @@ pub struct Directory {
 
 impl Directory {
     pub fn new(mut entries: Vec<Entry>) -> Result<Self, DirectoryError> {
-        sort_and_check_children(&mut entries)?;
+        sort_entries(&mut entries)?;
 
         Ok(Self { entries })
     }
@@ -341,23 +1059,136 @@ impl Directory {
         &self.entries
     }
 
+    /// Directory made of the entries of `self` and `other`, as if `other` was
+    /// mounted over `self` like the layers of a container image.
+    ///
+    /// Entries of `other` replace the entries of `self` with the same name, except
+    /// when both are directories, which are merged recursively. `subdirectory`
+    /// returns the contents of the subdirectories of both sides from their ids; it is
+    /// only called for subdirectories which differ.
+    pub fn overlay(
+        &self,
+        other: &Directory,
+        mut subdirectory: impl FnMut(&[u8; 20]) -> Option<Directory>,
+    ) -> Result<Directory, DirectoryError> {
+        self.overlay_with(other, &mut subdirectory)
+    }
+
+    fn overlay_with(
+        &self,
+        other: &Directory,
+        subdirectory: &mut dyn FnMut(&[u8; 20]) -> Option<Directory>,
+    ) -> Result<Directory, DirectoryError> {
+        let mut entries: HashMap<&[u8], Entry> = self
+            .entries
+            .iter()
+            .map(|entry| (&entry.name[..], entry.clone()))
+            .collect();
+        for entry in &other.entries {
+            let merged = match entries.get(&entry.name[..]) {
+                Some(lower) if lower.is_dir() && entry.is_dir() && lower.id != entry.id => {
+                    let lower_dir = subdirectory(&lower.id)
+                        .ok_or(DirectoryError::UnknownSubdirectory(lower.id))?;
+                    let upper_dir = subdirectory(&entry.id)
+                        .ok_or(DirectoryError::UnknownSubdirectory(entry.id))?;
+                    let merged_dir = lower_dir.overlay_with(&upper_dir, subdirectory)?;
+                    Entry {
+                        id: *merged_dir.swhid().digest_bytes(),
+                        ..entry.clone()
+                    }
+                }
+                _ => entry.clone(),
+            };
+            entries.insert(&entry.name, merged);
+        }
+        Directory::new(entries.into_values().collect())
+    }
+
+    /// Returns whether two lists of entries contain the same entries, regardless of
+    /// their order.
+    ///
+    /// Unlike comparing [`Directory`] objects, this does not require the lists to be
+    /// valid directory contents.
+    pub fn same_contents(a: &[Entry], b: &[Entry]) -> bool {
+        if a.len() != b.len() {
+            return false;
+        }
+        let mut a = a.to_vec();
+        let mut b = b.to_vec();
+        a.sort_unstable();
+        b.sort_unstable();
+        a == b
+    }
+
+    /// The exact bytes hashed (after the object header) to compute the directory
+    /// identifier.
+    pub fn manifest(&self) -> Vec<u8> {
+        dir_manifest_unchecked(&self.entries)
+    }
+
+    /// The body of the Git tree object for this directory, such that
+    /// `git hash-object -t tree --stdin` prints the digest of [`Directory::swhid`].
+    ///
+    /// Directory manifests are Git trees, so these are the same bytes as
+    /// [`Directory::manifest`].
+    pub fn git_tree_object(&self) -> Vec<u8> {
+        self.manifest()
+    }
+
     /// Compute the SWHID v1.2 directory identifier for this directory.
     ///
     /// This implements the SWHID v1.2 directory hashing algorithm, which
     /// is compatible with Git's tree format for directory objects.
-    pub fn swhid(&self) -> Result<Swhid, crate::error::SwhidError> {
-        let manifest = dir_manifest_unchecked(&self.entries);
-        Ok(Swhid::new(
+    pub fn swhid(&self) -> Swhid {
+        let manifest = self.manifest();
+        Swhid::new(
             ObjectType::Directory,
-            hash_swhid_object("tree", &manifest),
-        ))
+            hash_swhid_object(ObjectType::Directory.git_object_type(), &manifest),
+        )
+    }
+}
+
+impl<'a> IntoIterator for &'a Directory {
+    type Item = &'a Entry;
+    type IntoIter = std::slice::Iter<'a, Entry>;
+
+    /// Iterates over the entries, in the order of [`Directory::entries`]
+    fn into_iter(self) -> Self::IntoIter {
+        self.entries.iter()
+    }
+}
+
+impl SwhidObject for Directory {
+    fn swhid(&self) -> Swhid {
+        Directory::swhid(self)
+    }
+
+    fn object_type(&self) -> ObjectType {
+        ObjectType::Directory
+    }
+}
+
+/// Serialized as the list of its entries
+#[cfg(feature = "serde")]
+impl serde::Serialize for Directory {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serde::Serialize::serialize(&self.entries, serializer)
+    }
+}
+
+/// Deserialized from a list of entries in any order, checked like in [`Directory::new`]
+#[cfg(feature = "serde")]
+impl<'de> serde::Deserialize<'de> for Directory {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        Directory::new(Vec::<Entry>::deserialize(deserializer)?).map_err(serde::de::Error::custom)
     }
 }
 
 #[derive(Debug, Clone)]
 pub struct DiskDirectoryBuilder<'a> {
-    root: &'a Path,
+    root: Cow<'a, Path>,
     opts: DirectoryBuildOptions,
+    path_filter: Option<HashSet<PathBuf>>,
 }
 
 impl<'a> DiskDirectoryBuilder<'a> {
@@ -367,13 +1198,19 @@ impl<'a> DiskDirectoryBuilder<'a> {
     /// Uses default options (best-effort policy, auto permission source).
     pub fn new(root: &'a Path) -> Self {
         Self {
-            root,
-            opts: DirectoryBuildOptions {
-                permissions_source: PermissionsSourceKind::Auto,
-                permissions_policy: PermissionPolicy::BestEffort,
-                permissions_manifest_path: None,
-                walk_options: WalkOptions::default(),
-            },
+            root: Cow::Borrowed(root),
+            opts: DirectoryBuildOptions::default(),
+            path_filter: None,
+        }
+    }
+
+    /// Same as [`DiskDirectoryBuilder::new`], taking ownership of the path so the
+    /// builder can outlive it, eg. to be stored in a struct.
+    pub fn new_owned(root: PathBuf) -> DiskDirectoryBuilder<'static> {
+        DiskDirectoryBuilder {
+            root: Cow::Owned(root),
+            opts: DirectoryBuildOptions::default(),
+            path_filter: None,
         }
     }
 
@@ -389,19 +1226,413 @@ impl<'a> DiskDirectoryBuilder<'a> {
         self
     }
 
+    /// Only include the files and directories at these paths, relative to the root,
+    /// as if nothing else existed, eg. to reproduce the tree of a sparse checkout.
+    ///
+    /// Paths are matched exactly, not as patterns. Directories in the set are
+    /// included with all their contents, and the directories leading to paths in the
+    /// set are walked to reach them.
+    ///
+    /// The resulting SWHID is then not the identifier of the directory on disk.
+    pub fn with_path_filter(mut self, paths: HashSet<PathBuf>) -> Self {
+        self.path_filter = Some(paths);
+        self
+    }
+
+    fn resolved_path_filter(&self) -> Option<PathFilter> {
+        self.path_filter
+            .as_ref()
+            .map(|paths| PathFilter::new(&self.root, paths))
+    }
+
     pub fn build(self) -> Result<Directory, crate::error::SwhidError> {
-        let entries = read_dir(self.root, self.root, &self.opts)?;
+        let path_filter = self.resolved_path_filter();
+        let (entries, _) = read_dir(&self.root, &self.opts, path_filter.as_ref(), false)?;
         Directory::new(entries).map_err(|e| crate::error::SwhidError::Io(std::io::Error::other(e)))
     }
 
+    /// Same as [`DiskDirectoryBuilder::build`], also returning groups of paths
+    /// (relative to the root) which are the same file on disk, if
+    /// [`WalkOptions::detect_hardlinks`] is set.
+    ///
+    /// Groups and the paths in each group are sorted. Only files part of the
+    /// directory are reported, ie. not excluded or skipped ones.
+    pub fn build_with_hardlinks(
+        self,
+    ) -> Result<(Directory, Vec<Vec<PathBuf>>), crate::error::SwhidError> {
+        let path_filter = self.resolved_path_filter();
+        let (entries, paths_by_inode) = read_dir(
+            &self.root,
+            &self.opts,
+            path_filter.as_ref(),
+            self.opts.walk_options.detect_hardlinks,
+        )?;
+        let dir = Directory::new(entries)
+            .map_err(|e| crate::error::SwhidError::Io(std::io::Error::other(e)))?;
+        Ok((dir, hardlink_groups(paths_by_inode, &self.root)))
+    }
+
     /// Compute the SWHID v1.2 directory identifier for this directory.
     ///
     /// This implements the SWHID v1.2 directory hashing algorithm, which
     /// is compatible with Git's tree format for directory objects.
     pub fn swhid(&self) -> Result<Swhid, crate::error::SwhidError> {
-        let entries = read_dir(self.root, self.root, &self.opts)?;
-        Directory::new(entries)
+        self.swhid_with_stats().map(|(swhid, _stats)| swhid)
+    }
+
+    /// Same as [`DiskDirectoryBuilder::swhid`], walking the directory twice and
+    /// failing if the two identifiers differ.
+    ///
+    /// This guards against nondeterminism, such as files changing during the walk,
+    /// at the cost of hashing everything twice.
+    pub fn swhid_checked(&self) -> Result<Swhid, crate::error::SwhidError> {
+        let first = self.swhid()?;
+        let second = self.swhid()?;
+        if first != second {
+            return Err(crate::error::SwhidError::Io(std::io::Error::other(
+                format!(
+                    "Directory {} is not reproducible: hashed to {} then {}",
+                    self.root.display(),
+                    first,
+                    second
+                ),
+            )));
+        }
+        Ok(first)
+    }
+
+    /// Counts of the files, bytes, symlinks and directories of the directory, see
+    /// [`DirStats`].
+    ///
+    /// This walks the directory like [`DiskDirectoryBuilder::swhid`]; use
+    /// [`DiskDirectoryBuilder::swhid_with_stats`] to get both in a single walk.
+    pub fn stats(&self) -> Result<DirStats, crate::error::SwhidError> {
+        self.swhid_with_stats().map(|(_swhid, stats)| stats)
+    }
+
+    /// Same as [`DiskDirectoryBuilder::swhid`], also returning counts of what was
+    /// hashed.
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(level = "debug", skip_all, fields(root = %self.root.display()))
+    )]
+    pub fn swhid_with_stats(&self) -> Result<(Swhid, DirStats), crate::error::SwhidError> {
+        self.walk_swhid(None)
+    }
+
+    /// Same as [`DiskDirectoryBuilder::swhid`], recording in `checkpoint` the
+    /// identifier of each directory as soon as it is hashed, and reusing the ones
+    /// already recorded instead of reading those directories again.
+    ///
+    /// If the walk fails, `checkpoint` keeps the directories hashed until then, so it
+    /// can be saved and passed again to resume the walk where it stopped.
+    pub fn swhid_resuming(
+        &self,
+        checkpoint: &mut WalkCheckpoint,
+    ) -> Result<Swhid, crate::error::SwhidError> {
+        let state = RefCell::new(std::mem::take(checkpoint));
+        let result = self.walk_swhid(Some(&state));
+        *checkpoint = state.into_inner();
+        Ok(result?.0)
+    }
+
+    fn walk_swhid(
+        &self,
+        checkpoint: Option<&RefCell<WalkCheckpoint>>,
+    ) -> Result<(Swhid, DirStats), crate::error::SwhidError> {
+        let permission_source = permission_source(&self.root, &self.opts)?;
+        let confinement_root = symlink_confinement_root(&self.root, &self.opts)?;
+        let path_filter = self.resolved_path_filter();
+        let names = NameInterner::new(self.opts.walk_options.intern_names);
+        let walk = Walk {
+            opts: &self.opts,
+            permission_source: permission_source.as_ref(),
+            confinement_root: confinement_root.as_deref(),
+            path_filter: path_filter.as_ref(),
+            names: &names,
+            stats: RefCell::default(),
+            checkpoint: checkpoint.map(|checkpoint| (self.root.as_ref(), checkpoint)),
+            hardlinks: None,
+        };
+        let id = dir_id(&self.root, &walk)?;
+        Ok((
+            Swhid::new(ObjectType::Directory, id),
+            walk.stats.into_inner(),
+        ))
+    }
+
+    /// Same as [`DiskDirectoryBuilder::swhid`], without blocking the async runtime.
+    ///
+    /// The whole walk runs on tokio's blocking thread pool (where `tokio::fs` runs
+    /// its operations too), so reading and hashing files does not stall other tasks.
+    #[cfg(feature = "tokio")]
+    pub async fn swhid_async(&self) -> Result<Swhid, crate::error::SwhidError> {
+        let builder = DiskDirectoryBuilder {
+            root: Cow::Owned(self.root.to_path_buf()),
+            opts: self.opts.clone(),
+            path_filter: self.path_filter.clone(),
+        };
+        tokio::task::spawn_blocking(move || builder.swhid())
+            .await
             .map_err(|e| crate::error::SwhidError::Io(std::io::Error::other(e)))?
-            .swhid()
+    }
+}
+
+/// Cached state of a directory in a [`DirectoryCache`]
+#[derive(Debug, Clone)]
+struct CachedDirectory {
+    /// modification time of the directory when it was read
+    mtime: Option<SystemTime>,
+    /// sorted according to the SWHID v1.2 order
+    entries: Vec<Entry>,
+    id: [u8; 20],
+}
+
+/// Directory SWHID of a tree on disk, which can be updated incrementally.
+///
+/// The entries and identifier of every subdirectory are cached, keyed by their path
+/// relative to the root and their modification time. When a path changes,
+/// [`DirectoryCache::update`] only re-reads the directory containing it and recomputes
+/// the identifiers of its ancestors, instead of walking the whole tree again.
+///
+/// Changing the content of a file does not change the modification time of its
+/// directory, so every changed path must be passed to [`DirectoryCache::update`].
+pub struct DirectoryCache {
+    root: PathBuf,
+    opts: DirectoryBuildOptions,
+    permission_source: Box<dyn PermissionsSource>,
+    /// see [`symlink_confinement_root`]
+    confinement_root: Option<PathBuf>,
+    /// shared by all the reads, so that cached entries share their names
+    names: NameInterner,
+    /// keyed by path relative to `root`, which is the empty path
+    dirs: HashMap<PathBuf, CachedDirectory>,
+}
+
+impl DirectoryCache {
+    /// Walk the directory at `root`, caching the identifiers of all its subdirectories.
+    pub fn new(
+        root: impl Into<PathBuf>,
+        opts: DirectoryBuildOptions,
+    ) -> Result<Self, crate::error::SwhidError> {
+        let root = root.into();
+        let permission_source = permission_source(&root, &opts)?;
+        let confinement_root = symlink_confinement_root(&root, &opts)?;
+        let names = NameInterner::new(opts.walk_options.intern_names);
+        let mut dirs = HashMap::new();
+        cache_dir(
+            &root,
+            Path::new(""),
+            &Walk {
+                opts: &opts,
+                permission_source: permission_source.as_ref(),
+                confinement_root: confinement_root.as_deref(),
+                path_filter: None,
+                names: &names,
+                stats: RefCell::default(),
+                checkpoint: None,
+                hardlinks: None,
+            },
+            &mut dirs,
+        )?;
+        Ok(Self {
+            root,
+            opts,
+            permission_source,
+            confinement_root,
+            names,
+            dirs,
+        })
+    }
+
+    /// Returns the SWHID v1.2 directory identifier of the root directory.
+    pub fn swhid(&self) -> Swhid {
+        Swhid::new(ObjectType::Directory, self.dirs[Path::new("")].id)
+    }
+
+    /// Sorted entries of the subdirectory at `rel_path`, relative to the root (the
+    /// empty path for the root itself), or `None` if there is no such directory.
+    pub fn entries(&self, rel_path: &Path) -> Option<&[Entry]> {
+        self.dirs.get(rel_path).map(|dir| &dir.entries[..])
+    }
+
+    /// Recompute identifiers after `changed_path` was modified, created or removed.
+    ///
+    /// `changed_path` is either absolute, or relative to the root. Returns the new
+    /// SWHID of the root directory.
+    pub fn update(&mut self, changed_path: &Path) -> Result<Swhid, crate::error::SwhidError> {
+        let rel_path = changed_path
+            .strip_prefix(&self.root)
+            .unwrap_or(changed_path);
+        if rel_path.is_absolute() {
+            return Err(crate::error::SwhidError::InvalidFormat(format!(
+                "Path {} is not under {}",
+                changed_path.display(),
+                self.root.display()
+            )));
+        }
+
+        // If a directory changed, nothing cached under it can be trusted
+        self.dirs.retain(|path, _| !path.starts_with(rel_path));
+
+        // Re-read the closest cached ancestor which still exists. Its subdirectories
+        // are reused from the cache if they were not modified.
+        let mut dir = rel_path.parent().unwrap_or(rel_path);
+        while !(dir.as_os_str().is_empty()
+            || self.dirs.contains_key(dir) && self.root.join(dir).is_dir())
+        {
+            dir = dir.parent().unwrap_or(Path::new(""));
+        }
+        let dir = dir.to_path_buf();
+        let walk = Walk {
+            opts: &self.opts,
+            permission_source: self.permission_source.as_ref(),
+            confinement_root: self.confinement_root.as_deref(),
+            path_filter: None,
+            names: &self.names,
+            stats: RefCell::default(),
+            checkpoint: None,
+            hardlinks: None,
+        };
+        let mut id = cache_dir(&self.root, &dir, &walk, &mut self.dirs)?;
+        let root = &self.root;
+        self.dirs
+            .retain(|path, _| !path.starts_with(&dir) || root.join(path).is_dir());
+
+        // Propagate the new identifier to the ancestors
+        let mut child = dir.as_path();
+        while let Some(parent) = child.parent() {
+            let name = child.file_name().unwrap_or_default().as_encoded_bytes();
+            let cached = self.dirs.get_mut(parent).ok_or_else(|| {
+                crate::error::SwhidError::InvalidFormat(format!(
+                    "Directory {} is missing from the cache",
+                    parent.display()
+                ))
+            })?;
+            let prune = self.opts.walk_options.prune_empty_dirs;
+            let entry = cached
+                .entries
+                .iter_mut()
+                .find(|entry| entry.is_dir() && &*entry.name == name);
+            match entry {
+                Some(entry) if !(prune && is_empty_dir_id(&id)) => {
+                    entry.id = id;
+                    cached.id = hash_swhid_object(
+                        ObjectType::Directory.git_object_type(),
+                        &dir_manifest_unchecked(&cached.entries),
+                    );
+                    id = cached.id;
+                }
+                // The directory became empty, or is no longer empty, so it must be
+                // removed from or added to its parent
+                _ if prune => {
+                    id = cache_dir(&self.root, parent, &walk, &mut self.dirs)?;
+                }
+                _ => {
+                    return Err(crate::error::SwhidError::InvalidFormat(format!(
+                        "Directory {} is missing from the cache",
+                        child.display()
+                    )))
+                }
+            }
+            child = parent;
+        }
+        self.names.prune();
+
+        Ok(self.swhid())
+    }
+}
+
+/// Reads the directory at `root.join(rel_path)` and caches it in `dirs`, reusing the
+/// cached identifiers of unmodified subdirectories. Returns its identifier.
+fn cache_dir(
+    root: &Path,
+    rel_path: &Path,
+    walk: &Walk,
+    dirs: &mut HashMap<PathBuf, CachedDirectory>,
+) -> Result<[u8; 20], crate::error::SwhidError> {
+    let path = root.join(rel_path);
+    let mtime = fs::metadata(&path).and_then(|md| md.modified()).ok();
+    let mut entries = read_dir_shallow(&path, walk, &mut |subdir_path| {
+        let subdir_rel_path = rel_path.join(subdir_path.file_name().unwrap_or_default());
+        let subdir_mtime = fs::metadata(subdir_path).and_then(|md| md.modified()).ok();
+        match dirs.get(&subdir_rel_path) {
+            Some(cached) if cached.mtime.is_some() && cached.mtime == subdir_mtime => Ok(cached.id),
+            _ => cache_dir(root, &subdir_rel_path, walk, dirs),
+        }
+    })?;
+    sort_entries(&mut entries)
+        .map_err(|e| crate::error::SwhidError::Io(std::io::Error::other(e)))?;
+    let id = hash_swhid_object(
+        ObjectType::Directory.git_object_type(),
+        &dir_manifest_unchecked(&entries),
+    );
+    dirs.insert(
+        rel_path.to_path_buf(),
+        CachedDirectory { mtime, entries, id },
+    );
+    Ok(id)
+}
+
+#[cfg(test)]
+mod tests {
+    use assert_fs::prelude::*;
+
+    use super::*;
+
+    fn shared_name(entries: &[Entry], name: &[u8]) -> Arc<[u8]> {
+        match &entries
+            .iter()
+            .find(|entry| &*entry.name == name)
+            .unwrap()
+            .name
+        {
+            EntryName::Shared(name) => name.clone(),
+            EntryName::Owned(_) => panic!("{name:?} is not interned"),
+        }
+    }
+
+    #[test]
+    fn intern_names() {
+        let tmp = assert_fs::TempDir::new().unwrap();
+        for dir in ["", "src/", "lib/", "lib/src/"] {
+            tmp.child(format!("{dir}mod.rs")).write_str(dir).unwrap();
+        }
+        tmp.child("lib/old.rs").write_str("old").unwrap();
+
+        let opts = DirectoryBuildOptions {
+            walk_options: WalkOptions {
+                intern_names: true,
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        let mut cache = DirectoryCache::new(tmp.path(), opts).unwrap();
+        let entries = |path: &str| cache.entries(Path::new(path)).unwrap();
+        assert!(Arc::ptr_eq(
+            &shared_name(entries(""), b"src"),
+            &shared_name(entries("lib"), b"src")
+        ));
+        assert!(Arc::ptr_eq(
+            &shared_name(entries("src"), b"mod.rs"),
+            &shared_name(entries("lib/src"), b"mod.rs")
+        ));
+
+        // Names of removed entries are dropped
+        let interned = |cache: &DirectoryCache, name: &[u8]| {
+            cache.names.0.as_ref().unwrap().borrow().contains(name)
+        };
+        assert!(interned(&cache, b"old.rs"));
+        fs::remove_file(tmp.path().join("lib/old.rs")).unwrap();
+        cache.update(Path::new("lib/old.rs")).unwrap();
+        assert!(!interned(&cache, b"old.rs"));
+        assert!(interned(&cache, b"mod.rs"));
+
+        let cache = DirectoryCache::new(tmp.path(), DirectoryBuildOptions::default()).unwrap();
+        assert!(cache
+            .entries(Path::new(""))
+            .unwrap()
+            .iter()
+            .all(|entry| { matches!(entry.name, EntryName::Owned(_)) }));
     }
 }
@@ -0,0 +1,96 @@
+//! Identifiers of the directories already hashed by a walk, to resume it after an
+//! interruption without reading them again.
+
+use std::collections::HashMap;
+use std::io::{BufRead, BufReader, Read, Write};
+use std::path::{Path, PathBuf};
+
+use crate::error::SwhidError;
+use crate::utils::parse_object_id;
+
+/// Identifiers of the directories completely hashed by
+/// [`DiskDirectoryBuilder::swhid_resuming`](super::DiskDirectoryBuilder::swhid_resuming),
+/// by path relative to the root of the walk (the root itself being the empty path).
+///
+/// A directory is only recorded once all its contents have been hashed, so a walk
+/// resumed from a checkpoint gives the same identifier as an uninterrupted walk, as
+/// long as the recorded directories did not change in between.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct WalkCheckpoint {
+    dirs: HashMap<PathBuf, [u8; 20]>,
+}
+
+impl WalkCheckpoint {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Number of directories recorded
+    pub fn len(&self) -> usize {
+        self.dirs.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.dirs.is_empty()
+    }
+
+    /// Identifier recorded for the directory at `rel_path`, relative to the root
+    pub fn get(&self, rel_path: &Path) -> Option<&[u8; 20]> {
+        self.dirs.get(rel_path)
+    }
+
+    pub(crate) fn insert(&mut self, rel_path: PathBuf, id: [u8; 20]) {
+        self.dirs.insert(rel_path, id);
+    }
+
+    /// Writes the checkpoint as one `<hex id> <path>\0` record per directory,
+    /// sorted by path.
+    pub fn save(&self, writer: impl Write) -> Result<(), SwhidError> {
+        self.write_records(writer).map_err(SwhidError::Io)
+    }
+
+    fn write_records(&self, mut writer: impl Write) -> std::io::Result<()> {
+        let mut dirs: Vec<_> = self.dirs.iter().collect();
+        dirs.sort();
+        for (path, id) in dirs {
+            writer.write_all(hex::encode(id).as_bytes())?;
+            writer.write_all(b" ")?;
+            writer.write_all(path.as_os_str().as_encoded_bytes())?;
+            writer.write_all(b"\0")?;
+        }
+        writer.flush()
+    }
+
+    /// Reads a checkpoint written by [`WalkCheckpoint::save`].
+    pub fn load(reader: impl Read) -> Result<Self, SwhidError> {
+        let invalid = |message: &str| {
+            SwhidError::Io(std::io::Error::new(
+                std::io::ErrorKind::InvalidData,
+                format!("Invalid walk checkpoint: {message}"),
+            ))
+        };
+        let mut checkpoint = Self::new();
+        for record in BufReader::new(reader).split(b'\0') {
+            let record = record.map_err(SwhidError::Io)?;
+            let (id, path) = match record.get(40) {
+                Some(b' ') => (&record[..40], &record[41..]),
+                _ => return Err(invalid("expected an identifier and a path")),
+            };
+            let id = parse_object_id(id).ok_or_else(|| invalid("invalid identifier"))?;
+            let path = path_from_bytes(path.to_vec()).ok_or_else(|| invalid("invalid path"))?;
+            checkpoint.insert(path, id);
+        }
+        Ok(checkpoint)
+    }
+}
+
+#[cfg(unix)]
+fn path_from_bytes(bytes: Vec<u8>) -> Option<PathBuf> {
+    use std::os::unix::ffi::OsStringExt;
+    Some(std::ffi::OsString::from_vec(bytes).into())
+}
+
+#[cfg(not(unix))]
+fn path_from_bytes(bytes: Vec<u8>) -> Option<PathBuf> {
+    String::from_utf8(bytes).ok().map(PathBuf::from)
+}
@@ -0,0 +1,249 @@
+//! External merge sort of directory entries, for directories too large to sort in
+//! memory.
+//!
+//! Entries are sorted in runs of bounded length, each spilled to a temporary file as
+//! manifest records, then the runs are merged while hashing the manifest.
+
+use std::cmp::Reverse;
+use std::collections::BinaryHeap;
+use std::fs::File;
+use std::io::{self, BufRead, BufReader, BufWriter, Seek, Write};
+
+use sha1collisiondetection::{Digest, Sha1CD};
+
+use super::{
+    check_name, dir_manifest_unchecked, sort_entries, sort_entries_unchecked, Entry, EntryName,
+    FileDirCollisions,
+};
+use crate::core::ObjectType;
+use crate::error::DirectoryError;
+use crate::hash::{hash_swhid_object, swhid_object_header};
+
+#[derive(Debug)]
+pub(super) enum ExternalSortError {
+    Directory(DirectoryError),
+    Io(io::Error),
+}
+
+impl From<DirectoryError> for ExternalSortError {
+    fn from(e: DirectoryError) -> Self {
+        ExternalSortError::Directory(e)
+    }
+}
+
+impl From<io::Error> for ExternalSortError {
+    fn from(e: io::Error) -> Self {
+        ExternalSortError::Io(e)
+    }
+}
+
+/// Computes a directory identifier from entries pushed in any order, keeping at most
+/// `run_len` entries in memory.
+pub(super) struct ExternalSorter {
+    run_len: usize,
+    entries: Vec<Entry>,
+    runs: Vec<File>,
+    manifest_len: usize,
+}
+
+impl ExternalSorter {
+    pub(super) fn new(run_len: usize) -> Self {
+        Self {
+            run_len,
+            entries: Vec::new(),
+            runs: Vec::new(),
+            manifest_len: 0,
+        }
+    }
+
+    pub(super) fn push(&mut self, entry: Entry) -> Result<(), crate::error::SwhidError> {
+        check_name(&entry).map_err(|e| crate::error::SwhidError::Io(io::Error::other(e)))?;
+        self.manifest_len += dir_manifest_unchecked(std::slice::from_ref(&entry)).len();
+        self.entries.push(entry);
+        if self.entries.len() >= self.run_len {
+            self.spill().map_err(crate::error::SwhidError::Io)?;
+        }
+        Ok(())
+    }
+
+    /// Sort the entries in memory and write them to a new run
+    fn spill(&mut self) -> io::Result<()> {
+        sort_entries_unchecked(&mut self.entries);
+        let mut writer = BufWriter::new(tempfile::tempfile()?);
+        for entry in self.entries.drain(..) {
+            writer.write_all(&dir_manifest_unchecked(std::slice::from_ref(&entry)))?;
+        }
+        let mut file = writer.into_inner().map_err(|e| e.into_error())?;
+        file.rewind()?;
+        self.runs.push(file);
+        Ok(())
+    }
+
+    pub(super) fn finish(mut self) -> Result<[u8; 20], ExternalSortError> {
+        if self.runs.is_empty() {
+            // Everything fits in memory
+            sort_entries(&mut self.entries)?;
+            return Ok(hash_swhid_object(
+                ObjectType::Directory.git_object_type(),
+                &dir_manifest_unchecked(&self.entries),
+            ));
+        }
+        if !self.entries.is_empty() {
+            self.spill()?;
+        }
+
+        let mut hasher = Sha1CD::new();
+        hasher.update(swhid_object_header(
+            ObjectType::Directory.git_object_type(),
+            self.manifest_len,
+        ));
+
+        let mut readers: Vec<_> = self.runs.into_iter().map(BufReader::new).collect();
+        let mut heap = BinaryHeap::new();
+        for (i, reader) in readers.iter_mut().enumerate() {
+            if let Some(record) = read_record(reader)? {
+                heap.push(Reverse((record, i)));
+            }
+        }
+        let mut previous_name: Option<EntryName> = None;
+        let mut collisions = FileDirCollisions::default();
+        while let Some(Reverse((record, i))) = heap.pop() {
+            if previous_name.as_ref() == Some(&record.entry.name)
+                || collisions.collides(&record.entry)
+            {
+                #[cfg(feature = "tracing")]
+                tracing::debug!(
+                    name = %String::from_utf8_lossy(&record.entry.name),
+                    "duplicate entry name"
+                );
+                return Err(
+                    DirectoryError::DuplicateEntryName(record.entry.name[..].into()).into(),
+                );
+            }
+            hasher.update(&record.bytes);
+            previous_name = Some(record.entry.name);
+            if let Some(record) = read_record(&mut readers[i])? {
+                heap.push(Reverse((record, i)));
+            }
+        }
+        Ok(hasher.finalize().into())
+    }
+}
+
+/// An entry read back from a run, ordered by its sort key
+struct Record {
+    sort_key: Vec<u8>,
+    entry: Entry,
+    /// the entry's manifest bytes
+    bytes: Vec<u8>,
+}
+
+impl PartialEq for Record {
+    fn eq(&self, other: &Self) -> bool {
+        self.sort_key == other.sort_key
+    }
+}
+
+impl Eq for Record {}
+
+impl PartialOrd for Record {
+    fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Record {
+    fn cmp(&self, other: &Self) -> std::cmp::Ordering {
+        self.sort_key.cmp(&other.sort_key)
+    }
+}
+
+/// Reads a `<mode> <name>\0<id>` manifest record, or `None` at the end of the run
+fn read_record(reader: &mut impl BufRead) -> io::Result<Option<Record>> {
+    let mut bytes = Vec::new();
+    if reader.read_until(b'\0', &mut bytes)? == 0 {
+        return Ok(None);
+    }
+    let mut id = [0u8; 20];
+    reader.read_exact(&mut id)?;
+
+    let invalid = || io::Error::new(io::ErrorKind::InvalidData, "Invalid run record");
+    let (mode, name) = bytes[..bytes.len() - 1]
+        .split_at(bytes.iter().position(|&b| b == b' ').ok_or_else(invalid)?);
+    let mode = std::str::from_utf8(mode)
+        .ok()
+        .and_then(|mode| u32::from_str_radix(mode, 8).ok())
+        .ok_or_else(invalid)?;
+    let entry = Entry::new(name[1..].into(), mode, id);
+    bytes.extend_from_slice(&id);
+    Ok(Some(Record {
+        sort_key: entry.sort_key(),
+        entry,
+        bytes,
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entries(n: usize) -> Vec<Entry> {
+        (0..n)
+            .map(|i| {
+                // mix files and directories whose names are prefixes of each other
+                let mode = if i % 3 == 0 { 0o040000 } else { 0o100644 };
+                let name = format!("{}", i * 7919 % n).into_bytes();
+                Entry::new(name.into(), mode, [(i % 256) as u8; 20])
+            })
+            .collect()
+    }
+
+    #[test]
+    fn matches_in_memory_sort() {
+        for (n, run_len) in [(0, 4), (3, 4), (100, 7), (1000, 10), (1000, 1000)] {
+            let mut sorter = ExternalSorter::new(run_len);
+            for entry in entries(n) {
+                sorter.push(entry).unwrap();
+            }
+            let mut expected = entries(n);
+            sort_entries(&mut expected).unwrap();
+            assert_eq!(
+                sorter.finish().unwrap(),
+                hash_swhid_object("tree", &dir_manifest_unchecked(&expected)),
+                "{n} entries, runs of {run_len}"
+            );
+        }
+    }
+
+    #[test]
+    fn detects_duplicates_across_runs() {
+        let mut sorter = ExternalSorter::new(2);
+        for name in ["b", "a", "c", "a"] {
+            sorter
+                .push(Entry::new(name.as_bytes().into(), 0o100644, [0; 20]))
+                .unwrap();
+        }
+        assert!(matches!(
+            sorter.finish(),
+            Err(ExternalSortError::Directory(
+                DirectoryError::DuplicateEntryName(_)
+            ))
+        ));
+    }
+
+    #[test]
+    fn detects_file_and_directory_with_same_name() {
+        let mut sorter = ExternalSorter::new(2);
+        for (name, mode) in [("foo", 0o040000), ("foo.c", 0o100644), ("foo", 0o100644)] {
+            sorter
+                .push(Entry::new(name.as_bytes().into(), mode, [0; 20]))
+                .unwrap();
+        }
+        assert!(matches!(
+            sorter.finish(),
+            Err(ExternalSortError::Directory(
+                DirectoryError::DuplicateEntryName(_)
+            ))
+        ));
+    }
+}
@@ -0,0 +1,124 @@
+//! Directory identifiers computed from files received one at a time, eg. from a
+//! stream of upload events, without keeping their contents in memory.
+
+use std::collections::HashMap;
+
+use super::{Directory, Entry};
+use crate::core::{ObjectType, Swhid};
+use crate::error::{DirectoryError, SwhidError};
+use crate::hash::hash_content;
+use crate::permissions::EntryPerms;
+use crate::utils::normalize_relative_path;
+use crate::Bytestring;
+
+/// Builds the identifier of a directory from files added by path, in any order.
+///
+/// Contents are hashed as soon as they are added, and only the resulting entries are
+/// kept until [`DirectorySink::finalize`]. Directories are created from the paths of
+/// the files they contain, so there are no empty directories.
+///
+/// ```
+/// # use swhid::directory::DirectorySink;
+/// let mut sink = DirectorySink::new();
+/// sink.add_file("src/lib.rs", b"pub fn f() {}\n")?;
+/// sink.add_file("README.md", b"# Example\n")?;
+/// let swhid = sink.finalize()?;
+/// # Ok::<(), swhid::error::SwhidError>(())
+/// ```
+#[derive(Debug, Default)]
+pub struct DirectorySink {
+    root: SinkDir,
+}
+
+#[derive(Debug, Default)]
+struct SinkDir {
+    children: HashMap<Bytestring, SinkNode>,
+}
+
+#[derive(Debug)]
+enum SinkNode {
+    Entry(Entry),
+    Dir(SinkDir),
+}
+
+impl DirectorySink {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds a regular, non-executable file at `path`, relative to the root and
+    /// normalized with [`normalize_relative_path`].
+    pub fn add_file(&mut self, path: &str, contents: &[u8]) -> Result<(), SwhidError> {
+        let swhid = Swhid::new(ObjectType::Content, hash_content(contents));
+        self.add_entry(path, &swhid, EntryPerms::File { executable: false })
+    }
+
+    /// Adds an entry pointing to an already identified object at `path`, eg. an
+    /// executable file, a symlink or a submodule.
+    ///
+    /// Fails if `swhid` does not match `perms` (see [`Entry::from_swhid`]), or if an
+    /// entry was already added at `path`, or if a parent of `path` is not a
+    /// directory.
+    pub fn add_entry(
+        &mut self,
+        path: &str,
+        swhid: &Swhid,
+        perms: EntryPerms,
+    ) -> Result<(), SwhidError> {
+        let path = normalize_relative_path(path)?;
+        let (parents, name) = match path.rsplit_once('/') {
+            Some((parents, name)) => (Some(parents), name),
+            None => (None, path.as_str()),
+        };
+        let name: Bytestring = name.as_bytes().into();
+        let entry = Entry::from_swhid(name.clone(), swhid, perms).map_err(directory_error)?;
+
+        let mut dir = &mut self.root;
+        for segment in parents.into_iter().flat_map(|parents| parents.split('/')) {
+            let node = dir
+                .children
+                .entry(segment.as_bytes().into())
+                .or_insert_with(|| SinkNode::Dir(SinkDir::default()));
+            dir = match node {
+                SinkNode::Dir(subdir) => subdir,
+                SinkNode::Entry(_) => {
+                    return Err(SwhidError::Io(std::io::Error::other(format!(
+                        "Cannot add {path}: {segment} is not a directory"
+                    ))))
+                }
+            };
+        }
+        if dir.children.contains_key(&name) {
+            return Err(directory_error(DirectoryError::DuplicateEntryName(name)));
+        }
+        dir.children.insert(name, SinkNode::Entry(entry));
+        Ok(())
+    }
+
+    /// Computes the identifier of the root directory.
+    pub fn finalize(self) -> Result<Swhid, SwhidError> {
+        Ok(self.root.into_directory()?.swhid())
+    }
+}
+
+impl SinkDir {
+    fn into_directory(self) -> Result<Directory, SwhidError> {
+        let entries = self
+            .children
+            .into_iter()
+            .map(|(name, node)| match node {
+                SinkNode::Entry(entry) => Ok(entry),
+                SinkNode::Dir(dir) => Ok(Entry::new(
+                    name,
+                    EntryPerms::Directory.to_swh_mode_u32(),
+                    *dir.into_directory()?.swhid().digest_bytes(),
+                )),
+            })
+            .collect::<Result<_, SwhidError>>()?;
+        Directory::new(entries).map_err(directory_error)
+    }
+}
+
+fn directory_error(e: DirectoryError) -> SwhidError {
+    SwhidError::Io(std::io::Error::other(e))
+}
@@ -1,6 +1,6 @@
 use thiserror::Error;
 
-use crate::Bytestring;
+use crate::{Bytestring, EntryPerms, ObjectType};
 
 /// Errors that may occur while parsing SWHIDs or computing hashes.
 #[derive(Debug, Error)]
@@ -14,18 +14,27 @@ pub enum SwhidError {
     #[error("unsupported SWHID version: {0}")]
     InvalidVersion(String),
 
+    #[error("SWHID version {0} is not supported yet")]
+    UnsupportedVersion(u32),
+
     #[error("invalid object type: {0}")]
     InvalidObjectType(String),
 
     #[error("invalid digest (expected 40 hex chars): {0}")]
     InvalidDigest(String),
 
+    #[error("invalid digest length: {0} bytes (expected 20)")]
+    InvalidDigestLength(usize),
+
     #[error("invalid qualifier key: {0}")]
     InvalidQualifierKey(String),
 
     #[error("invalid qualifier value for `{key}`: {value}")]
     InvalidQualifierValue { key: String, value: String },
 
+    #[error("duplicate qualifier: {0}")]
+    DuplicateQualifier(String),
+
     #[error("I/O error: {0}")]
     Io(#[source] std::io::Error),
 }
@@ -35,8 +44,25 @@ pub enum SwhidError {
 pub enum DirectoryError {
     #[error("Duplicate entry name: {}", String::from_utf8_lossy(.0))]
     DuplicateEntryName(Bytestring),
-    #[error("Invalid byte {byte} in name: {}", String::from_utf8_lossy(.name))]
+    #[error("Invalid byte {:?} in name: {}", char::from(*.byte), String::from_utf8_lossy(.name))]
     InvalidByteInName { byte: u8, name: Bytestring },
+    #[error("Invalid entry name: {:?}", String::from_utf8_lossy(.0))]
+    InvalidEntryName(Bytestring),
+    #[error(
+        "Entry {} with mode {} cannot point to a {} object",
+        String::from_utf8_lossy(.name),
+        .perms.to_git_mode_string(),
+        .object_type.as_tag()
+    )]
+    MismatchedObjectType {
+        name: Bytestring,
+        perms: EntryPerms,
+        object_type: ObjectType,
+    },
+    #[error("Invalid mode {mode:o} for entry {}", String::from_utf8_lossy(.name))]
+    InvalidMode { name: Bytestring, mode: u32 },
+    #[error("Unknown subdirectory {}", hex::encode(.0))]
+    UnknownSubdirectory([u8; 20]),
 }
 
 /// Errors that may occur while building a [`Snapshot`](crate::Snapshot)
@@ -44,6 +70,25 @@ pub enum DirectoryError {
 pub enum SnapshotError {
     #[error("Duplicate branch name: {}", String::from_utf8_lossy(.0))]
     DuplicateBranchName(Bytestring),
-    #[error("Invalid byte {byte} in name: {}", String::from_utf8_lossy(.name))]
+    #[error("Invalid byte {:?} in name: {}", char::from(*.byte), String::from_utf8_lossy(.name))]
     InvalidByteInName { byte: u8, name: Bytestring },
+    #[error("Invalid branch target type: {0}")]
+    InvalidTargetType(String),
+    #[error("Invalid {target_type} target length: {len} bytes (expected 20)")]
+    InvalidTargetLength {
+        target_type: &'static str,
+        len: usize,
+    },
+    #[error("Empty alias target (dangling aliases have no target)")]
+    EmptyAliasTarget,
+    #[error("Invalid snapshot manifest: {0}")]
+    InvalidManifest(String),
+}
+
+/// Errors in the headers of a [`Revision`](crate::Revision) or
+/// [`Release`](crate::Release)
+#[derive(Debug, Error)]
+pub enum HeaderError {
+    #[error("Invalid header key: {:?}", String::from_utf8_lossy(.0))]
+    InvalidKey(Bytestring),
 }
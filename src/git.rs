@@ -2,6 +2,7 @@
 //!
 //! This module provides SWHID v1.2 compliant functionality to compute SWHIDs
 //! from Git repository objects when the `git` feature is enabled:
+//! - Directory SWHIDs (trees) - `swh:1:dir:<digest>`
 //! - Revision SWHIDs (commits) - `swh:1:rev:<digest>`
 //! - Release SWHIDs (tags) - `swh:1:rel:<digest>`
 //! - Snapshot SWHIDs (repository state) - `swh:1:snp:<digest>`
@@ -10,11 +11,12 @@
 //! using Git as the reference VCS implementation.
 
 use crate::error::SwhidError;
-use crate::Swhid;
+use crate::{ObjectType, Swhid};
 use std::path::Path;
 
-use git2::{ObjectType as GitObjectType, Repository, Signature};
+use git2::{ObjectType as GitObjectType, Repository};
 
+use crate::directory::{Directory, Entry};
 use crate::release::Release;
 use crate::revision::Revision;
 use crate::snapshot::{Branch, BranchTarget, Snapshot};
@@ -24,77 +26,55 @@ fn io_error(msg: String) -> SwhidError {
     SwhidError::Io(std::io::Error::other(msg))
 }
 
+/// SWHID object type of Git objects of the given kind, `None` for `Any`
+fn object_type_from_git(kind: GitObjectType) -> Option<ObjectType> {
+    ObjectType::from_git_name(kind.str()).ok()
+}
+
+/// The type and body of a Git object, ie. the bytes hashed by Git after the object
+/// header
+fn raw_object(repo: &Repository, oid: &git2::Oid) -> Result<(GitObjectType, Vec<u8>), SwhidError> {
+    let error = |e: git2::Error| io_error(format!("Failed to read object {oid}: {e}"));
+    let odb = repo.odb().map_err(error)?;
+    let object = odb.read(*oid).map_err(error)?;
+    Ok((object.kind(), object.data().to_vec()))
+}
+
 fn oid_to_array(oid: git2::Oid) -> Result<[u8; 20], SwhidError> {
     oid.as_bytes()
         .try_into()
         .map_err(|e| io_error(format!("Unexpected tree_oid length: {e}")))
 }
 
-fn parse_signature(sig: Signature) -> (Bytestring, i64, Bytestring) {
-    let name = sig.name_bytes();
-    let email = sig.email_bytes();
-
-    let mut full_name = Vec::with_capacity(name.len() + email.len() + 3);
-    full_name.extend_from_slice(name);
-    full_name.extend_from_slice(b" <");
-    full_name.extend_from_slice(email);
-    full_name.push(b'>');
-
-    let when = sig.when();
-    let offset_minutes = when.offset_minutes();
-    let offset_hours = offset_minutes / 60;
-    let offset_minutes = offset_minutes % 60;
-    let sign = when.sign();
-    let offset = format!("{sign}{offset_hours:02}{offset_minutes:02}");
-
-    (full_name.into(), when.seconds(), offset.into_bytes().into())
-}
-
-/// Returns key-value pairs and the message
-fn parse_header(mut manifest: &[u8]) -> Result<Vec<(&[u8], Bytestring)>, SwhidError> {
-    let mut headers = Vec::new();
-    while !manifest.is_empty() {
-        // Pop first line
-        let Some(newline_position) = manifest.iter().position(|&byte| byte == b'\n') else {
-            return Err(io_error("Header line is missing a line end".to_owned()));
-        };
-        let first_line = &manifest[..newline_position];
-        manifest = &manifest[newline_position + 1..];
-
-        // The first line is a key and a value. Extract the key and the first line of the value
-        let Some(delimiter_position) = first_line.iter().position(|&byte| byte == b' ') else {
-            return Err(io_error("Header line is missing a value".to_owned()));
-        };
-        let key = &first_line[..delimiter_position];
-        if key.is_empty() {
-            return Err(io_error("Empty key".to_owned()));
-        };
-        let mut value = first_line[delimiter_position + 1..].to_vec();
-
-        // Read line by line until we find one that does not start
-        // with a space, which is the next key-value.
-        while let Some(newline_position) = manifest.iter().position(|&byte| byte == b'\n') {
-            let line = &manifest[..newline_position];
-            match line.split_first() {
-                None => {
-                    return Err(io_error("Empty line".to_owned()));
-                }
-                Some((b' ', value_line)) => {
-                    // continuation line
-                    value.push(b'\n');
-                    value.extend_from_slice(value_line);
-                }
-                Some(_) => {
-                    // new key-value pair
-                    break;
-                }
-            }
-            manifest = &manifest[newline_position + 1..];
-        }
-        headers.push((key, value.into_boxed_slice()));
+/// Build the SWHID of a Git object from its OID, when its type is already known
+///
+/// Git hashes blobs, trees, commits and tags exactly like SWHID contents,
+/// directories, revisions and releases, so the OID is the SWHID digest. Neither the
+/// object nor its type are checked against a repository. Fails for snapshots, which
+/// have no Git equivalent, and for OIDs which are not SHA-1.
+pub fn swhid_from_oid(oid: &git2::Oid, object_type: ObjectType) -> Result<Swhid, SwhidError> {
+    if object_type == ObjectType::Snapshot {
+        return Err(SwhidError::InvalidObjectType(format!(
+            "{} (no Git object is a snapshot)",
+            object_type.as_tag()
+        )));
     }
+    Ok(Swhid::new(object_type, oid_to_array(*oid)?))
+}
 
-    Ok(headers)
+/// Build the SWHID of any Git object from its OID, reading its type from `repo`
+///
+/// Blobs, trees, commits and tags are identified as contents, directories,
+/// revisions and releases. Fails if the object is not in the repository.
+pub fn swhid_for_oid(repo: &Repository, oid: &git2::Oid) -> Result<Swhid, SwhidError> {
+    let object = repo
+        .find_object(*oid, None)
+        .map_err(|e| io_error(format!("Failed to find object {oid}: {e}")))?;
+    let object_type = object
+        .kind()
+        .and_then(object_type_from_git)
+        .ok_or_else(|| io_error(format!("Object {oid} has an unknown type")))?;
+    swhid_from_oid(oid, object_type)
 }
 
 /// Compute a SWHID v1.2 revision identifier from a Git commit
@@ -105,48 +85,28 @@ pub fn revision_swhid(repo: &Repository, commit_oid: &git2::Oid) -> Result<Swhid
     revision_from_git(repo, commit_oid).map(|rev| rev.swhid())
 }
 
+/// Check that a Git commit matches an expected SWHID v1.2 revision identifier
+///
+/// Returns `Ok(false)` if the SWHID computed from the commit differs from `expected`,
+/// including when `expected` is not a revision SWHID.
+pub fn verify_revision(
+    repo: &Repository,
+    commit_oid: &git2::Oid,
+    expected: &Swhid,
+) -> Result<bool, SwhidError> {
+    revision_swhid(repo, commit_oid).map(|actual| actual == *expected)
+}
+
 #[doc(hidden)]
 pub fn revision_from_git(
     repo: &Repository,
     commit_oid: &git2::Oid,
 ) -> Result<Revision, SwhidError> {
-    let commit = repo
-        .find_commit(*commit_oid)
-        .map_err(|e| io_error(format!("Failed to find commit: {e}")))?;
-
-    let tree = commit
-        .tree()
-        .map_err(|e| io_error(format!("Failed to get commit tree: {e}")))?;
-
-    let tree_oid = tree.id();
-
-    let (author, author_timestamp, author_timestamp_offset) = parse_signature(commit.author());
-    let (committer, committer_timestamp, committer_timestamp_offset) =
-        parse_signature(commit.committer());
-
-    let headers = parse_header(commit.raw_header_bytes())?;
-
-    let extra_headers = headers
-        .into_iter()
-        .filter(|(key, _value)| !matches!(*key, b"tree" | b"parent" | b"author" | b"committer"))
-        .map(|(key, value)| (key.into(), value))
-        .collect();
-
-    Ok(Revision {
-        directory: oid_to_array(tree_oid)?,
-        parents: commit
-            .parents()
-            .map(|parent| oid_to_array(parent.id()))
-            .collect::<Result<_, _>>()?,
-        author,
-        author_timestamp,
-        author_timestamp_offset,
-        committer,
-        committer_timestamp,
-        committer_timestamp_offset,
-        extra_headers,
-        message: Some(commit.message_bytes().into()),
-    })
+    let (kind, raw_commit) = raw_object(repo, commit_oid)?;
+    if kind != GitObjectType::Commit {
+        return Err(io_error(format!("Object {commit_oid} is not a commit")));
+    }
+    Revision::from_manifest(&raw_commit)
 }
 
 /// Compute a SWHID v1.2 release identifier from a Git tag
@@ -159,45 +119,178 @@ pub fn release_swhid(repo: &Repository, tag_oid: &git2::Oid) -> Result<Swhid, Sw
 
 #[doc(hidden)]
 pub fn release_from_git(repo: &Repository, tag_oid: &git2::Oid) -> Result<Release, SwhidError> {
-    use crate::release::ReleaseTargetType;
-
-    let tag = repo
-        .find_tag(*tag_oid)
-        .map_err(|e| io_error(format!("Failed to find tag: {e}")))?;
-
-    let target = tag
-        .target()
-        .map_err(|e| io_error(format!("Failed to get tag target: {e}")))?;
-    let target_oid = target.id();
-
-    let (author, author_timestamp, author_timestamp_offset) = match tag.tagger() {
-        Some(tagger) => {
-            let (author, author_timestamp, author_timestamp_offset) = parse_signature(tagger);
-            (
-                Some(author),
-                Some(author_timestamp),
-                Some(author_timestamp_offset),
-            )
+    let (kind, raw_tag) = raw_object(repo, tag_oid)?;
+    if kind != GitObjectType::Tag {
+        return Err(io_error(format!(
+            "Object {tag_oid} is not a tag object (lightweight tag has no release object)"
+        )));
+    }
+    Release::from_manifest(&raw_tag)
+}
+
+/// Branch target type assumed for references whose target object is missing.
+///
+/// Git does not record the type of the object a reference points to, so a
+/// dangling reference (one whose target is not in the object database) has no
+/// well-defined snapshot target type. The SWHID specification does not settle
+/// this yet (see <https://github.com/swhid/specification/issues/64>), and the
+/// choice changes the resulting snapshot SWHID.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum DanglingTarget {
+    /// Assume the missing object is a commit
+    #[default]
+    Revision,
+    /// Assume the missing object is a tree
+    Directory,
+    /// Assume the missing object is a blob
+    Content,
+    /// Assume the missing object is an annotated tag
+    Release,
+}
+
+impl DanglingTarget {
+    fn object_type(self) -> ObjectType {
+        match self {
+            DanglingTarget::Revision => ObjectType::Revision,
+            DanglingTarget::Directory => ObjectType::Directory,
+            DanglingTarget::Content => ObjectType::Content,
+            DanglingTarget::Release => ObjectType::Release,
         }
-        None => (None, None, None),
-    };
+    }
+}
 
-    Ok(Release {
-        object: oid_to_array(target_oid)?,
-        object_type: match target.kind() {
-            Some(GitObjectType::Commit) => ReleaseTargetType::Revision,
-            Some(GitObjectType::Tree) => ReleaseTargetType::Directory,
-            Some(GitObjectType::Blob) => ReleaseTargetType::Content,
-            Some(GitObjectType::Tag) => ReleaseTargetType::Release,
-            _ => return Err(io_error("Unknown target type".to_string())),
-        },
-        name: tag.name_bytes().into(),
-        author,
-        author_timestamp,
-        author_timestamp_offset,
-        extra_headers: Vec::new(), // FIXME: does not seem to be exposed by git2
-        message: tag.message_bytes().map(Into::into),
-    })
+/// Options for building snapshots from Git repositories.
+#[derive(Debug, Clone)]
+pub struct SnapshotOptions {
+    /// Target type to use for references pointing to missing objects
+    pub dangling_target: DanglingTarget,
+    /// Whether to add a `HEAD` alias branch pointing to the current branch.
+    ///
+    /// This is enabled by default, which matches snapshots created by the
+    /// Software Heritage Git loader.
+    pub include_head: bool,
+}
+
+impl Default for SnapshotOptions {
+    fn default() -> Self {
+        Self {
+            dangling_target: DanglingTarget::default(),
+            include_head: true,
+        }
+    }
+}
+
+/// Compute a SWHID v1.2 directory identifier from a Git tree
+///
+/// Unlike [`DiskDirectoryBuilder`](crate::DiskDirectoryBuilder), this reads entries
+/// from the tree object, so file modes and submodules are exactly those recorded
+/// in Git.
+pub fn directory_swhid(repo: &Repository, tree_oid: &git2::Oid) -> Result<Swhid, SwhidError> {
+    Ok(directory_from_git(repo, tree_oid)?.swhid())
+}
+
+/// Compute the SWHID v1.2 directory identifiers of a Git tree and all its sub-trees
+///
+/// Returns pairs of paths (relative to the given tree, `/`-separated, empty for the
+/// tree itself) and directory SWHIDs, parents before their children.
+pub fn directory_swhids_recursive(
+    repo: &Repository,
+    tree_oid: &git2::Oid,
+) -> Result<Vec<(Bytestring, Swhid)>, SwhidError> {
+    let mut swhids = Vec::new();
+    let mut stack = vec![(Vec::new(), *tree_oid)];
+    while let Some((path, tree_oid)) = stack.pop() {
+        let tree = repo
+            .find_tree(tree_oid)
+            .map_err(|e| io_error(format!("Failed to find tree: {e}")))?;
+        for entry in tree.iter().rev() {
+            if entry.kind() == Some(GitObjectType::Tree) {
+                let mut subtree_path = path.clone();
+                if !subtree_path.is_empty() {
+                    subtree_path.push(b'/');
+                }
+                subtree_path.extend_from_slice(entry.name_bytes());
+                stack.push((subtree_path, entry.id()));
+            }
+        }
+        swhids.push((path.into(), directory_from_tree(&tree)?.swhid()));
+    }
+    Ok(swhids)
+}
+
+#[doc(hidden)]
+pub fn directory_from_git(
+    repo: &Repository,
+    tree_oid: &git2::Oid,
+) -> Result<Directory, SwhidError> {
+    let tree = repo
+        .find_tree(*tree_oid)
+        .map_err(|e| io_error(format!("Failed to find tree: {e}")))?;
+    directory_from_tree(&tree)
+}
+
+fn directory_from_tree(tree: &git2::Tree<'_>) -> Result<Directory, SwhidError> {
+    let entries = tree
+        .iter()
+        .map(|entry| {
+            Ok(Entry::new(
+                entry.name_bytes().into(),
+                // Use the mode as written in the tree, which may be a non-canonical
+                // one (eg. `100664`) in old repositories.
+                entry.filemode_raw() as u32,
+                oid_to_array(entry.id())?,
+            ))
+        })
+        .collect::<Result<_, SwhidError>>()?;
+
+    Directory::new(entries).map_err(|e| io_error(format!("Invalid tree {}: {e}", tree.id())))
+}
+
+/// How to handle lightweight tags, which are plain references without a tag object.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum LightweightTagPolicy {
+    /// Fail, as there is no release object to identify
+    #[default]
+    Error,
+    /// Identify the tagged object instead (eg. the revision of a tagged commit)
+    Target,
+}
+
+/// Compute the SWHID v1.2 identifier of a tag, given its name
+///
+/// Annotated tags are identified by their release SWHID. Lightweight tags are
+/// handled according to `policy`.
+pub fn tag_swhid(
+    repo: &Repository,
+    tag_name: &str,
+    policy: LightweightTagPolicy,
+) -> Result<Swhid, SwhidError> {
+    let tag_oid = repo
+        .refname_to_id(&format!("refs/tags/{tag_name}"))
+        .map_err(|e| io_error(format!("Tag not found: {e}")))?;
+    let object = repo
+        .find_object(tag_oid, None)
+        .map_err(|e| io_error(format!("Failed to find tag {tag_name}: {e}")))?;
+
+    match (object.kind(), policy) {
+        (Some(GitObjectType::Tag), _) => release_swhid(repo, &object.id()),
+        (_, LightweightTagPolicy::Error) => Err(io_error(format!(
+            "{tag_name} is a lightweight tag, which has no release object"
+        ))),
+        (Some(GitObjectType::Commit), LightweightTagPolicy::Target) => {
+            revision_swhid(repo, &object.id())
+        }
+        // Trees and blobs are hashed by Git exactly like SWHID directories and contents
+        (Some(GitObjectType::Tree), LightweightTagPolicy::Target) => {
+            swhid_from_oid(&object.id(), ObjectType::Directory)
+        }
+        (Some(GitObjectType::Blob), LightweightTagPolicy::Target) => {
+            swhid_from_oid(&object.id(), ObjectType::Content)
+        }
+        (_, LightweightTagPolicy::Target) => Err(io_error(format!(
+            "Tag {tag_name} points to an object of unknown type"
+        ))),
+    }
 }
 
 /// Compute a SWHID v1.2 snapshot identifier from a Git repository
@@ -205,31 +298,49 @@ pub fn release_from_git(repo: &Repository, tag_oid: &git2::Oid) -> Result<Releas
 /// This implements the SWHID v1.2 snapshot hashing algorithm for Git repositories,
 /// creating a `swh:1:snp:<digest>` identifier according to the specification.
 pub fn snapshot_swhid(repo: &Repository) -> Result<Swhid, SwhidError> {
-    snapshot_from_git(repo).map(|snp| snp.swhid())
+    snapshot_swhid_with_options(repo, &SnapshotOptions::default())
+}
+
+/// Same as [`snapshot_swhid`], with explicit [`SnapshotOptions`]
+pub fn snapshot_swhid_with_options(
+    repo: &Repository,
+    opts: &SnapshotOptions,
+) -> Result<Swhid, SwhidError> {
+    snapshot_from_git_with_options(repo, opts).map(|snp| snp.swhid())
 }
 
 #[doc(hidden)]
 pub fn snapshot_from_git(repo: &Repository) -> Result<Snapshot, SwhidError> {
+    snapshot_from_git_with_options(repo, &SnapshotOptions::default())
+}
+
+#[doc(hidden)]
+pub fn snapshot_from_git_with_options(
+    repo: &Repository,
+    opts: &SnapshotOptions,
+) -> Result<Snapshot, SwhidError> {
     let references = repo
         .references()
         .map_err(|e| io_error(format!("Failed to list references: {e}")))?;
 
     let mut branches: Vec<_> = references
         .flat_map(|reference| match reference {
-            Ok(reference) => reference_to_branch(repo, reference).transpose(),
+            Ok(reference) => reference_to_branch(repo, reference, opts).transpose(),
             Err(e) => Some(Err(io_error(format!("Failed to read reference: {e}")))),
         })
         .collect::<Result<_, _>>()?;
 
-    let head = repo
-        .head()
-        .map_err(|e| io_error(format!("Failed to get HEAD: {e}")))?;
-    if let Some(head_branch) = reference_to_branch(repo, head)? {
-        let Branch { name, target: _ } = head_branch;
-        branches.push(Branch {
-            name: (*b"HEAD").into(),
-            target: BranchTarget::Alias(Some(name)),
-        });
+    if opts.include_head {
+        let head = repo
+            .head()
+            .map_err(|e| io_error(format!("Failed to get HEAD: {e}")))?;
+        if let Some(head_branch) = reference_to_branch(repo, head, opts)? {
+            let Branch { name, target: _ } = head_branch;
+            branches.push(Branch {
+                name: (*b"HEAD").into(),
+                target: BranchTarget::Alias(Some(name)),
+            });
+        }
     }
 
     Snapshot::new(branches).map_err(|e| io_error(format!("Invalid snapshot: {e}")))
@@ -238,6 +349,7 @@ pub fn snapshot_from_git(repo: &Repository) -> Result<Snapshot, SwhidError> {
 fn reference_to_branch(
     repo: &Repository,
     reference: git2::Reference<'_>,
+    opts: &SnapshotOptions,
 ) -> Result<Option<Branch>, SwhidError> {
     if !reference.is_branch() && !reference.is_tag() {
         return Ok(None);
@@ -246,11 +358,8 @@ fn reference_to_branch(
     let name = reference.name_bytes().to_owned().into_boxed_slice();
     let target = match reference.kind() {
         None => {
-            // Dangling reference.
-            //
-            // FIXME: We need to define a type (because of
-            // https://github.com/swhid/specification/issues/64), so let's assume it's
-            // a commit.
+            // Dangling reference, with no target at all. Its type is whatever the
+            // caller configured, see [`DanglingTarget`].
             if reference.target().is_some() {
                 return Err(io_error(format!(
                     "Reference {} has None kind, but has a target",
@@ -263,7 +372,7 @@ fn reference_to_branch(
                     String::from_utf8_lossy(&name)
                 )));
             }
-            BranchTarget::Revision(None)
+            BranchTarget::from_object_type(opts.dangling_target.object_type(), None)
         }
         Some(git2::ReferenceType::Direct) => {
             let Some(target_id) = reference.target() else {
@@ -272,24 +381,31 @@ fn reference_to_branch(
                     String::from_utf8_lossy(&name)
                 )));
             };
-            let target = repo
-                .find_object(target_id, None)
-                .map_err(|e| io_error(format!("Could not find object {target_id}: {e}")))?;
+            let target = match repo.find_object(target_id, None) {
+                Ok(target) => Some(target),
+                Err(e) if e.code() == git2::ErrorCode::NotFound => None,
+                Err(e) => return Err(io_error(format!("Could not find object {target_id}: {e}"))),
+            };
             let target_id = oid_to_array(target_id)?;
-            match target.kind() {
+            match target.as_ref().and_then(git2::Object::kind) {
                 None => {
-                    // Dangling reference.
-                    //
-                    // FIXME: We need to define a type (because of
-                    // https://github.com/swhid/specification/issues/64), so let's assume it's
-                    // a commit.
-                    BranchTarget::Revision(Some(target_id))
+                    // Dangling reference: the target object is missing, so its type
+                    // is whatever the caller configured, see [`DanglingTarget`].
+                    BranchTarget::from_object_type(
+                        opts.dangling_target.object_type(),
+                        Some(target_id),
+                    )
+                }
+                Some(kind) => {
+                    // Never a snapshot, Git objects are blobs, trees, commits or tags
+                    let object_type = object_type_from_git(kind).ok_or_else(|| {
+                        io_error(format!(
+                            "Object {} has unexpected type {kind}",
+                            hex::encode(target_id)
+                        ))
+                    })?;
+                    BranchTarget::from_object_type(object_type, Some(target_id))
                 }
-                Some(git2::ObjectType::Any) => panic!("git2 returned an object with type 'Any'"),
-                Some(git2::ObjectType::Commit) => BranchTarget::Revision(Some(target_id)),
-                Some(git2::ObjectType::Tree) => BranchTarget::Directory(Some(target_id)),
-                Some(git2::ObjectType::Blob) => BranchTarget::Content(Some(target_id)),
-                Some(git2::ObjectType::Tag) => BranchTarget::Release(Some(target_id)),
             }
         }
         Some(git2::ReferenceType::Symbolic) => {
@@ -299,6 +415,8 @@ fn reference_to_branch(
                     String::from_utf8_lossy(&name)
                 )));
             };
+            // Symbolic references are aliases even when the reference they point to
+            // does not exist, so they are not affected by `opts.dangling_target`.
             BranchTarget::Alias(Some(target.into()))
         }
     };
@@ -309,6 +427,10 @@ fn reference_to_branch(
 ///
 /// This function opens a Git repository to enable SWHID v1.2 computation
 /// for revision, release, and snapshot objects.
+#[cfg_attr(
+    feature = "tracing",
+    tracing::instrument(level = "debug", skip_all, fields(path = %path.display()))
+)]
 pub fn open_repo(path: &Path) -> Result<Repository, SwhidError> {
     Repository::open(path).map_err(|e| io_error(format!("Failed to open repository: {e}")))
 }
@@ -325,6 +447,15 @@ pub fn get_head_commit(repo: &Repository) -> Result<git2::Oid, SwhidError> {
 
 /// Get all tags in a Git repository for SWHID v1.2 release computation
 pub fn get_tags(repo: &Repository) -> Result<Vec<git2::Oid>, SwhidError> {
+    get_tags_named(repo).map(|tags| tags.into_iter().map(|(_name, oid)| oid).collect())
+}
+
+/// Get all tags in a Git repository, with their names
+///
+/// Both lightweight and annotated tags are returned. The OID is the one the
+/// `refs/tags/<name>` reference points to, ie. the tag object for annotated tags
+/// and the tagged object for lightweight tags.
+pub fn get_tags_named(repo: &Repository) -> Result<Vec<(String, git2::Oid)>, SwhidError> {
     let mut tags = Vec::new();
     let tag_names = repo
         .tag_names(None)
@@ -332,9 +463,36 @@ pub fn get_tags(repo: &Repository) -> Result<Vec<git2::Oid>, SwhidError> {
 
     for tag_name in tag_names.iter().flatten() {
         if let Ok(tag_oid) = repo.refname_to_id(&format!("refs/tags/{tag_name}")) {
-            tags.push(tag_oid);
+            tags.push((tag_name.to_owned(), tag_oid));
         }
     }
 
     Ok(tags)
 }
+
+#[cfg(test)]
+mod tests {
+    use crate::utils::parse_header;
+
+    #[test]
+    fn parse_header_continuation_lines() {
+        let headers =
+            parse_header(b"tree abc\nmergetag object def\n type commit\n \n msg\n").unwrap();
+        assert_eq!(
+            headers,
+            vec![
+                (b"tree".as_slice(), b"abc".as_slice().into()),
+                (
+                    b"mergetag".as_slice(),
+                    b"object def\ntype commit\n\nmsg".as_slice().into()
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn parse_header_missing_line_end() {
+        assert!(parse_header(b"tree abc").is_err());
+        assert!(parse_header(b"tree abc\nmergetag object def\n type commit").is_err());
+    }
+}
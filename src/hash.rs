@@ -1,14 +1,27 @@
 use sha1collisiondetection::{Digest, Sha1CD};
 
+use crate::core::ObjectType;
+use crate::error::SwhidError;
+
 /// Build SWHID v1.2 object header bytes: `<type> <len>\0`
 ///
 /// This implements the object header format specified in SWHID v1.2,
 /// which is compatible with Git's object format for content and directory objects.
 pub fn swhid_object_header(typ: &str, len: usize) -> Vec<u8> {
-    let mut v = Vec::with_capacity(typ.len() + 1 + 20 + 1 + len);
+    swhid_object_header_u64(typ, len as u64)
+}
+
+/// Same as [`swhid_object_header`], with a length independent of the platform's
+/// pointer width, for objects hashed without being loaded in memory, eg. contents
+/// larger than 4 GiB on 32-bit targets.
+///
+/// The length is written in decimal, whatever its value, like Git does.
+pub fn swhid_object_header_u64(typ: &str, len: u64) -> Vec<u8> {
+    let len = len.to_string();
+    let mut v = Vec::with_capacity(typ.len() + 1 + len.len() + 1);
     v.extend_from_slice(typ.as_bytes());
     v.push(b' ');
-    v.extend_from_slice(len.to_string().as_bytes());
+    v.extend_from_slice(len.as_bytes());
     v.push(0);
     v
 }
@@ -18,7 +31,83 @@ pub fn swhid_object_header(typ: &str, len: usize) -> Vec<u8> {
 /// This computes the SHA-1 digest of content data using the SWHID v1.2
 /// object format, which is compatible with Git's blob format.
 pub fn hash_content(data: &[u8]) -> [u8; 20] {
-    hash_swhid_object("blob", data)
+    hash_swhid_object(ObjectType::Content.git_object_type(), data)
+}
+
+/// Hash content data split in several parts, without concatenating them.
+///
+/// Content identifiers cannot be updated incrementally: the object header, which is
+/// hashed first, contains the total length, so appending to a content requires
+/// hashing it again from the start. This at least avoids copying the parts into a
+/// single buffer.
+///
+/// # Panics
+///
+/// If `total_len` is not the sum of the lengths of `parts`.
+pub fn hash_content_from_parts(total_len: usize, parts: &[&[u8]]) -> [u8; 20] {
+    assert_eq!(
+        total_len,
+        parts.iter().map(|part| part.len()).sum::<usize>(),
+        "total_len does not match the length of parts"
+    );
+    let mut hasher = Sha1CD::new();
+    hasher.update(swhid_object_header(
+        ObjectType::Content.git_object_type(),
+        total_len,
+    ));
+    for part in parts {
+        hasher.update(part);
+    }
+    hasher.finalize().into()
+}
+
+/// Hashes a content of known length fed in chunks, eg. read from a stream, without
+/// keeping it in memory.
+///
+/// Like for [`hash_content_from_parts`], the length must be known upfront as it is
+/// hashed first. It is a `u64`, so contents larger than `usize::MAX` can be hashed
+/// on 32-bit targets.
+pub struct ContentHasher {
+    hasher: Sha1CD,
+    len: u64,
+    written: u64,
+}
+
+impl ContentHasher {
+    pub fn new(len: u64) -> Self {
+        let mut hasher = Sha1CD::new();
+        hasher.update(swhid_object_header_u64(
+            ObjectType::Content.git_object_type(),
+            len,
+        ));
+        Self {
+            hasher,
+            len,
+            written: 0,
+        }
+    }
+
+    pub fn update(&mut self, data: &[u8]) {
+        self.hasher.update(data);
+        self.written += data.len() as u64;
+    }
+
+    /// Number of bytes still expected
+    pub fn remaining(&self) -> u64 {
+        self.len.saturating_sub(self.written)
+    }
+
+    /// Returns the content digest, or an error if the number of bytes fed to
+    /// [`ContentHasher::update`] is not the length given to [`ContentHasher::new`].
+    pub fn finalize(self) -> Result<[u8; 20], SwhidError> {
+        if self.written != self.len {
+            return Err(SwhidError::Io(std::io::Error::other(format!(
+                "Content length mismatch: expected {} bytes, got {}",
+                self.len, self.written
+            ))));
+        }
+        Ok(self.hasher.finalize().into())
+    }
 }
 
 /// Hash arbitrary SWHID v1.2 object given its type and payload bytes.
@@ -33,6 +122,17 @@ pub fn hash_swhid_object(typ: &str, payload: &[u8]) -> [u8; 20] {
     hasher.finalize().into()
 }
 
+/// Hash many independent contents concurrently.
+///
+/// Returns the same digests as calling [`hash_content`] on each input, in the same
+/// order.
+#[cfg(feature = "parallel")]
+pub fn hash_contents_par(inputs: &[&[u8]]) -> Vec<[u8; 20]> {
+    use rayon::prelude::*;
+
+    inputs.par_iter().map(|data| hash_content(data)).collect()
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -82,6 +182,62 @@ mod tests {
         assert_ne!(empty_hash, non_empty_hash);
     }
 
+    #[test]
+    fn hash_content_from_parts_matches_concatenation() {
+        let parts: [&[u8]; 3] = [b"log line 1\n", b"", b"log line 2\n"];
+        assert_eq!(
+            hash_content_from_parts(22, &parts),
+            hash_content(&parts.concat())
+        );
+        assert_eq!(hash_content_from_parts(0, &[]), hash_content(&[]));
+    }
+
+    #[test]
+    #[should_panic]
+    fn hash_content_from_parts_wrong_len() {
+        hash_content_from_parts(3, &[b"ab"]);
+    }
+
+    #[test]
+    fn swhid_object_header_huge_len() {
+        // 5 GiB, more than a 32-bit `usize`
+        let len = 5 * 1024 * 1024 * 1024;
+        assert_eq!(swhid_object_header_u64("blob", len), b"blob 5368709120\0");
+        assert_eq!(
+            swhid_object_header_u64("blob", u64::MAX),
+            b"blob 18446744073709551615\0"
+        );
+        // does not allocate `len` bytes
+        assert_eq!(
+            swhid_object_header("blob", usize::MAX),
+            swhid_object_header_u64("blob", usize::MAX as u64)
+        );
+
+        let mut hasher = ContentHasher::new(len);
+        hasher.update(&[0; 4096]);
+        assert_eq!(hasher.remaining(), len - 4096);
+        assert!(hasher.finalize().is_err());
+    }
+
+    #[test]
+    fn content_hasher_matches_hash_content() {
+        let data = b"log line 1\nlog line 2\n";
+        for chunk_len in [1, 5, data.len()] {
+            let mut hasher = ContentHasher::new(data.len() as u64);
+            for chunk in data.chunks(chunk_len) {
+                hasher.update(chunk);
+            }
+            assert_eq!(hasher.remaining(), 0);
+            assert_eq!(hasher.finalize().unwrap(), hash_content(data));
+        }
+        assert_eq!(ContentHasher::new(0).finalize().unwrap(), hash_content(&[]));
+
+        let mut hasher = ContentHasher::new(1);
+        hasher.update(b"ab");
+        assert_eq!(hasher.remaining(), 0);
+        assert!(hasher.finalize().is_err());
+    }
+
     #[test]
     fn hash_deterministic() {
         let data = b"deterministic test";
@@ -168,4 +324,15 @@ mod tests {
             assert_eq!(hashes[0], hashes[i]);
         }
     }
+
+    #[cfg(feature = "parallel")]
+    #[test]
+    fn hash_contents_par_matches_sequential() {
+        let inputs: Vec<Vec<u8>> = (0..1000).map(|i| format!("content {i}").into()).collect();
+        let inputs: Vec<&[u8]> = inputs.iter().map(Vec::as_slice).collect();
+
+        let sequential: Vec<[u8; 20]> = inputs.iter().map(|data| hash_content(data)).collect();
+        assert_eq!(hash_contents_par(&inputs), sequential);
+        assert!(hash_contents_par(&[]).is_empty());
+    }
 }
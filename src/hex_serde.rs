@@ -0,0 +1,117 @@
+//! Serialization of raw bytes fields (ids, names, messages...) as hex strings,
+//! with `#[serde(with = "crate::hex_serde")]` and the submodules for fields holding
+//! several of them.
+
+use serde::de::Error;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+/// Bytes fields that can be deserialized from a hex string
+pub(crate) trait FromBytes: Sized {
+    /// `None` if `bytes` does not have the expected length
+    fn from_bytes(bytes: Vec<u8>) -> Option<Self>;
+}
+
+impl FromBytes for [u8; 20] {
+    fn from_bytes(bytes: Vec<u8>) -> Option<Self> {
+        bytes.try_into().ok()
+    }
+}
+
+impl FromBytes for Box<[u8]> {
+    fn from_bytes(bytes: Vec<u8>) -> Option<Self> {
+        Some(bytes.into())
+    }
+}
+
+/// Bytes serialized as a hex string
+struct Hex<T>(T);
+
+impl<T: AsRef<[u8]>> Serialize for Hex<T> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&hex::encode(&self.0))
+    }
+}
+
+impl<'de, T: FromBytes> Deserialize<'de> for Hex<T> {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let s = String::deserialize(deserializer)?;
+        let bytes = hex::decode(&s).map_err(D::Error::custom)?;
+        let len = bytes.len();
+        T::from_bytes(bytes)
+            .map(Hex)
+            .ok_or_else(|| D::Error::invalid_length(len, &"20 bytes"))
+    }
+}
+
+pub(crate) fn serialize<S: Serializer>(
+    bytes: &impl AsRef<[u8]>,
+    serializer: S,
+) -> Result<S::Ok, S::Error> {
+    Hex(bytes).serialize(serializer)
+}
+
+pub(crate) fn deserialize<'de, D: Deserializer<'de>, T: FromBytes>(
+    deserializer: D,
+) -> Result<T, D::Error> {
+    Ok(Hex::deserialize(deserializer)?.0)
+}
+
+/// `Option` of bytes, as `null` or a hex string
+pub(crate) mod option {
+    use super::*;
+
+    pub(crate) fn serialize<S: Serializer, T: AsRef<[u8]>>(
+        bytes: &Option<T>,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        bytes.as_ref().map(Hex).serialize(serializer)
+    }
+
+    pub(crate) fn deserialize<'de, D: Deserializer<'de>, T: FromBytes>(
+        deserializer: D,
+    ) -> Result<Option<T>, D::Error> {
+        Ok(Option::<Hex<T>>::deserialize(deserializer)?.map(|hex| hex.0))
+    }
+}
+
+/// `Vec` of bytes, as a list of hex strings
+pub(crate) mod vec {
+    use super::*;
+
+    pub(crate) fn serialize<S: Serializer, T: AsRef<[u8]>>(
+        items: &[T],
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        serializer.collect_seq(items.iter().map(Hex))
+    }
+
+    pub(crate) fn deserialize<'de, D: Deserializer<'de>, T: FromBytes>(
+        deserializer: D,
+    ) -> Result<Vec<T>, D::Error> {
+        Ok(Vec::<Hex<T>>::deserialize(deserializer)?
+            .into_iter()
+            .map(|hex| hex.0)
+            .collect())
+    }
+}
+
+/// `Vec` of pairs of bytes, eg. headers, as a list of pairs of hex strings
+pub(crate) mod pairs {
+    use super::*;
+
+    pub(crate) fn serialize<S: Serializer, T: AsRef<[u8]>>(
+        pairs: &[(T, T)],
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        serializer.collect_seq(pairs.iter().map(|(key, value)| (Hex(key), Hex(value))))
+    }
+
+    pub(crate) fn deserialize<'de, D: Deserializer<'de>, T: FromBytes>(
+        deserializer: D,
+    ) -> Result<Vec<(T, T)>, D::Error> {
+        Ok(Vec::<(Hex<T>, Hex<T>)>::deserialize(deserializer)?
+            .into_iter()
+            .map(|(key, value)| (key.0, value.0))
+            .collect())
+    }
+}
@@ -1,28 +1,41 @@
 #![doc = include_str!("../README.md")]
 
+#[cfg(feature = "api")]
+pub mod api;
+#[cfg(feature = "archive")]
+pub mod archive;
+pub mod authorship;
 pub mod content;
 pub mod core;
+pub mod debug;
 pub mod directory;
 pub mod error;
 #[cfg(feature = "git")]
 pub mod git;
 pub mod hash;
+#[cfg(feature = "serde")]
+mod hex_serde;
 pub mod permissions;
+pub mod prelude;
 pub mod qualifier;
 pub mod release;
 pub mod revision;
 pub mod snapshot;
-mod utils;
+pub mod utils;
 
 pub use content::Content;
-pub use core::{ObjectType, Swhid};
-pub use directory::{Directory, DiskDirectoryBuilder, Entry, WalkOptions};
-pub use directory::{DirectoryBuildOptions, ManifestEntry};
+pub use core::{ObjectType, Swhid, SwhidObject};
+pub use directory::{DirStats, DirectoryBuildOptions, DirectorySink, ManifestEntry};
+pub use directory::{
+    Directory, DirectoryCache, DiskDirectoryBuilder, Entry, WalkCheckpoint, WalkOptions,
+};
 pub use permissions::{
     resolve_file_permissions, EntryExec, EntryPerms, PermissionPolicy, PermissionsSource,
     PermissionsSourceKind,
 };
-pub use qualifier::{ByteRange, LineRange, QualifiedSwhid};
+pub use qualifier::{
+    extract_fragment, ByteRange, LineRange, ParseOptions, QualifiedSwhid, RangeEnd,
+};
 pub use release::{Release, ReleaseTargetType};
 pub use revision::Revision;
 pub use snapshot::{Branch, BranchTarget, Snapshot};
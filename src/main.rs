@@ -1,11 +1,12 @@
 use clap::{Parser, Subcommand};
-use std::path::PathBuf;
+use std::collections::{BTreeMap, BTreeSet};
+use std::path::{Path, PathBuf};
 
+use swhid::{debug, ObjectType, QualifiedSwhid, Release, Revision, Swhid};
 use swhid::{
-    Content, DirectoryBuildOptions, DiskDirectoryBuilder, PermissionPolicy, PermissionsSourceKind,
-    WalkOptions,
+    Content, DirectoryBuildOptions, DirectoryCache, DiskDirectoryBuilder, Entry, PermissionPolicy,
+    PermissionsSourceKind, WalkOptions,
 };
-use swhid::{QualifiedSwhid, Swhid};
 
 #[cfg(feature = "git")]
 use swhid::git;
@@ -30,14 +31,46 @@ enum Command {
     },
     /// Compute a directory SWHID recursively
     Dir {
-        /// Directory root
+        /// Directory root, or tar archive with --from-tar (- for stdin)
         path: PathBuf,
-        /// Follow symlinks (not recommended)
+        /// Read the directory from a tar archive instead of the disk (requires
+        /// --features archive). --max-file-size applies to the files of the archive
+        #[cfg(feature = "archive")]
+        #[arg(long, conflicts_with_all = [
+            "follow_symlinks", "exclude", "exclude_from", "sort_large_dirs_externally",
+            "keep_empty_dirs", "include_vcs_dirs", "skip_oversized_files",
+            "permissions_manifest", "stats",
+        ])]
+        from_tar: bool,
+        /// Follow symlinks (not recommended). Symlinks resolving outside of the
+        /// directory are rejected
         #[arg(long)]
         follow_symlinks: bool,
         /// Exclude files matching these suffixes (e.g., .tmp, .log)
         #[arg(long, value_name = "SUFFIX")]
         exclude: Vec<String>,
+        /// Also exclude the suffixes listed in this file, one per line. Empty lines
+        /// and lines starting with # are ignored
+        #[arg(long, value_name = "FILE")]
+        exclude_from: Option<PathBuf>,
+        /// Sort huge directories using temporary files instead of in memory
+        /// (requires --features sort-external)
+        #[arg(long)]
+        sort_large_dirs_externally: bool,
+        /// Keep empty subdirectories, which Git omits from trees
+        #[arg(long)]
+        keep_empty_dirs: bool,
+        /// Hash the .git, .hg and .svn repositories too, which are left out by
+        /// default
+        #[arg(long)]
+        include_vcs_dirs: bool,
+        /// Fail on files larger than this many bytes
+        #[arg(long, value_name = "BYTES")]
+        max_file_size: Option<u64>,
+        /// Leave files larger than --max-file-size out instead of failing. The
+        /// resulting SWHID does not identify the directory on disk
+        #[arg(long, requires = "max_file_size")]
+        skip_oversized_files: bool,
         /// Permission source (auto, fs, git-index, git-tree, manifest, heuristic)
         #[arg(long, value_name = "SOURCE", default_value = "auto")]
         permissions_source: String,
@@ -47,11 +80,27 @@ enum Command {
         /// Path to permission manifest file (required when source=manifest)
         #[arg(long, value_name = "PATH")]
         permissions_manifest: Option<PathBuf>,
+        /// Print the number of files, bytes, symlinks and directories hashed to stderr
+        #[arg(long)]
+        stats: bool,
     },
+    /// Compute a revision SWHID from a raw commit object read from stdin, eg. the
+    /// output of `git cat-file commit HEAD`
+    RevFromObject,
+    /// Compute a release SWHID from a raw tag object read from stdin, eg. the output
+    /// of `git cat-file tag v1.0`
+    RelFromObject,
     /// Parse/pretty-print a (qualified) SWHID
     Parse {
         /// The SWHID string
-        swhid: String,
+        #[arg(required_unless_present = "stdin")]
+        swhid: Option<String>,
+        /// Read one SWHID per line from stdin, printing an error line for invalid ones
+        #[arg(long, conflicts_with = "swhid")]
+        stdin: bool,
+        /// Also sort unknown qualifiers by key, to compare SWHIDs as strings
+        #[arg(long)]
+        canonical: bool,
     },
     /// Verify that a file or directory matches a given SWHID
     Verify {
@@ -59,12 +108,35 @@ enum Command {
         path: PathBuf,
         /// Expected SWHID
         swhid: String,
-        /// Follow symlinks (not recommended)
+        /// Follow symlinks (not recommended). Symlinks resolving outside of the
+        /// directory are rejected
         #[arg(long)]
         follow_symlinks: bool,
         /// Exclude files matching these suffixes (e.g., .tmp, .log)
         #[arg(long, value_name = "SUFFIX")]
         exclude: Vec<String>,
+        /// Also exclude the suffixes listed in this file, one per line. Empty lines
+        /// and lines starting with # are ignored
+        #[arg(long, value_name = "FILE")]
+        exclude_from: Option<PathBuf>,
+        /// Sort huge directories using temporary files instead of in memory
+        /// (requires --features sort-external)
+        #[arg(long)]
+        sort_large_dirs_externally: bool,
+        /// Keep empty subdirectories, which Git omits from trees
+        #[arg(long)]
+        keep_empty_dirs: bool,
+        /// Hash the .git, .hg and .svn repositories too, which are left out by
+        /// default
+        #[arg(long)]
+        include_vcs_dirs: bool,
+        /// Fail on files larger than this many bytes
+        #[arg(long, value_name = "BYTES")]
+        max_file_size: Option<u64>,
+        /// Leave files larger than --max-file-size out instead of failing. The
+        /// resulting SWHID does not identify the directory on disk
+        #[arg(long, requires = "max_file_size")]
+        skip_oversized_files: bool,
         /// Permission source (auto, fs, git-index, git-tree, manifest, heuristic)
         #[arg(long, value_name = "SOURCE", default_value = "auto")]
         permissions_source: String,
@@ -75,6 +147,19 @@ enum Command {
         #[arg(long, value_name = "PATH")]
         permissions_manifest: Option<PathBuf>,
     },
+    /// Print an annotated hexdump of the bytes hashed for a file or directory
+    DebugManifest {
+        /// Path to file or directory
+        path: PathBuf,
+    },
+    /// Compare the SWHIDs of two directories, printing the first path where they
+    /// differ
+    Compare {
+        /// First directory
+        a: PathBuf,
+        /// Second directory
+        b: PathBuf,
+    },
     /// Git repository SWHID computation (requires --features git)
     #[cfg(feature = "git")]
     Git {
@@ -93,25 +178,75 @@ enum GitCommand {
         /// Commit hash (if omitted, use HEAD)
         commit: Option<String>,
     },
+    /// Verify that a commit matches a given revision SWHID
+    VerifyRevision {
+        /// Git repository path
+        repo: PathBuf,
+        /// Commit hash
+        commit: String,
+        /// Expected SWHID
+        swhid: String,
+    },
     /// Compute release SWHID for a tag
     Release {
         /// Git repository path
         repo: PathBuf,
         /// Tag name
         tag: String,
+        /// For lightweight tags, print the SWHID of the tagged object instead of failing
+        #[arg(long)]
+        follow_lightweight: bool,
     },
     /// Compute snapshot SWHID for a repository
     Snapshot {
         /// Git repository path
         repo: PathBuf,
+        /// Do not add a HEAD alias branch to the snapshot
+        #[arg(long)]
+        no_head: bool,
+    },
+    /// Print the revision and directory SWHIDs of HEAD and the snapshot SWHID of a
+    /// repository, one labeled line each
+    IdentifyAll {
+        /// Git repository path
+        repo: PathBuf,
+    },
+    /// Print an annotated hexdump of the bytes hashed for a Git object
+    DebugManifest {
+        /// Git repository path
+        repo: PathBuf,
+        /// Commit, tag or tree (if omitted, dump the snapshot of the repository)
+        object: Option<String>,
     },
     /// List all tags in a repository
     Tags {
         /// Git repository path
         repo: PathBuf,
+        /// Also print the release SWHID of annotated tags
+        #[arg(long)]
+        swhid: bool,
     },
 }
 
+fn read_stdin() -> std::io::Result<Vec<u8>> {
+    use std::io::Read;
+    let mut buf = Vec::new();
+    std::io::stdin().read_to_end(&mut buf)?;
+    Ok(buf)
+}
+
+/// Reads the suffixes to exclude from a file given to --exclude-from
+fn read_exclude_file(path: &Path) -> Result<Vec<String>, Box<dyn std::error::Error>> {
+    let contents = std::fs::read_to_string(path)
+        .map_err(|e| format!("Failed to read {}: {e}", path.display()))?;
+    Ok(contents
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .map(str::to_owned)
+        .collect())
+}
+
 fn parse_permissions_source(s: &str) -> Result<PermissionsSourceKind, Box<dyn std::error::Error>> {
     match s {
         "auto" => Ok(PermissionsSourceKind::Auto),
@@ -139,31 +274,131 @@ fn parse_permissions_policy(s: &str) -> Result<PermissionPolicy, Box<dyn std::er
     }
 }
 
+/// Parse a qualified or core SWHID, returning its canonical form
+fn parse_swhid(s: &str, canonical: bool) -> Result<String, swhid::error::SwhidError> {
+    // Try qualified first, fallback to core
+    match s.parse::<QualifiedSwhid>() {
+        Ok(q) if canonical => Ok(q.canonical()),
+        Ok(q) => Ok(q.to_string()),
+        Err(_) => Ok(s.parse::<Swhid>()?.to_string()),
+    }
+}
+
+/// Path, relative to the roots, and description of the first difference between
+/// the subdirectories at `rel_path` of two directories, descending into differing
+/// subdirectories
+fn first_difference(
+    a: &DirectoryCache,
+    b: &DirectoryCache,
+    rel_path: PathBuf,
+) -> Option<(PathBuf, String)> {
+    let by_name = |cache: &DirectoryCache| -> BTreeMap<Vec<u8>, Entry> {
+        cache
+            .entries(&rel_path)
+            .unwrap_or_default()
+            .iter()
+            .map(|entry| (entry.name().to_vec(), entry.clone()))
+            .collect()
+    };
+    let (entries_a, entries_b) = (by_name(a), by_name(b));
+    let names: BTreeSet<&Vec<u8>> = entries_a.keys().chain(entries_b.keys()).collect();
+    for name in names {
+        let path = rel_path.join(name_component(name));
+        let difference = match (entries_a.get(name), entries_b.get(name)) {
+            (Some(entry_a), Some(entry_b)) if entry_a == entry_b => continue,
+            (Some(entry_a), Some(entry_b)) if entry_a.mode() != entry_b.mode() => {
+                format!("mode {:o} != {:o}", entry_a.mode(), entry_b.mode())
+            }
+            (Some(entry_a), Some(_)) if entry_a.mode() == 0o040000 => {
+                return first_difference(a, b, path);
+            }
+            (Some(_), Some(_)) => "contents differ".to_owned(),
+            (Some(_), None) => "only in the first directory".to_owned(),
+            (None, _) => "only in the second directory".to_owned(),
+        };
+        return Some((path, difference));
+    }
+    None
+}
+
+/// Path component for an entry name, which is exact on Unix where names are any
+/// bytes, so that the path can be looked up in a [`DirectoryCache`]
+fn name_component(name: &[u8]) -> std::borrow::Cow<'_, std::ffi::OsStr> {
+    #[cfg(unix)]
+    return std::borrow::Cow::Borrowed(std::os::unix::ffi::OsStrExt::from_bytes(name));
+    #[cfg(not(unix))]
+    return std::borrow::Cow::Owned(String::from_utf8_lossy(name).into_owned().into());
+}
+
+/// Object type as named in error messages, eg. "a directory"
+fn object_type_description(object_type: ObjectType) -> &'static str {
+    match object_type {
+        ObjectType::Content => "a content",
+        ObjectType::Directory => "a directory",
+        ObjectType::Revision => "a revision",
+        ObjectType::Release => "a release",
+        ObjectType::Snapshot => "a snapshot",
+    }
+}
+
 fn main() -> Result<(), Box<dyn std::error::Error>> {
     let cli = Cli::parse();
     match cli.cmd {
         Command::Content { file } => {
+            let from_stdin = file.is_none();
             let bytes = if let Some(p) = file {
                 std::fs::read(p)?
             } else {
-                use std::io::Read;
-                let mut buf = Vec::new();
-                std::io::stdin().read_to_end(&mut buf)?;
-                buf
+                read_stdin()?
             };
             let s = Content::from_bytes(bytes).swhid();
+            if from_stdin && s == Content::empty() {
+                eprintln!("warning: stdin is empty, use --file to hash a file");
+            }
             println!("{s}");
         }
+        Command::RevFromObject => {
+            println!("{}", Revision::from_manifest(&read_stdin()?)?.swhid());
+        }
+        Command::RelFromObject => {
+            println!("{}", Release::from_manifest(&read_stdin()?)?.swhid());
+        }
         Command::Dir {
             path,
+            #[cfg(feature = "archive")]
+            from_tar,
             follow_symlinks,
-            exclude,
+            mut exclude,
+            exclude_from,
+            sort_large_dirs_externally,
+            keep_empty_dirs,
+            include_vcs_dirs,
+            max_file_size,
+            skip_oversized_files,
             permissions_source,
             permissions_policy,
             permissions_manifest,
+            stats,
         } => {
+            #[cfg(feature = "archive")]
+            if from_tar {
+                let opts = swhid::archive::ArchiveOptions {
+                    max_entry_size: max_file_size,
+                    ..Default::default()
+                };
+                let swhid = if path == Path::new("-") {
+                    swhid::archive::tar_swhid_with_options(std::io::stdin().lock(), &opts)?
+                } else {
+                    swhid::archive::tar_swhid_with_options(std::fs::File::open(&path)?, &opts)?
+                };
+                println!("{swhid}");
+                return Ok(());
+            }
             let perm_source = parse_permissions_source(&permissions_source)?;
             let perm_policy = parse_permissions_policy(&permissions_policy)?;
+            if let Some(exclude_from) = exclude_from {
+                exclude.extend(read_exclude_file(&exclude_from)?);
+            }
 
             if perm_source == PermissionsSourceKind::Manifest && permissions_manifest.is_none() {
                 return Err(
@@ -178,34 +413,74 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
                 walk_options: WalkOptions {
                     follow_symlinks,
                     exclude_suffixes: exclude,
+                    sort_large_dirs_externally,
+                    prune_empty_dirs: !keep_empty_dirs,
+                    skip_vcs_dirs: !include_vcs_dirs,
+                    max_file_size,
+                    skip_oversized_files,
+                    ..Default::default()
                 },
             };
 
             let dir = DiskDirectoryBuilder::new(&path).with_build_options(build_opts);
-            let swhid = dir.swhid()?;
+            let (swhid, dir_stats) = dir.swhid_with_stats()?;
             println!("{swhid}");
+            if stats {
+                eprintln!(
+                    "{} files, {} bytes, {} symlinks, {} directories",
+                    dir_stats.files, dir_stats.bytes, dir_stats.symlinks, dir_stats.directories
+                );
+            }
         }
-        Command::Parse { swhid } => {
-            // Try qualified first, fallback to core
-            match swhid.parse::<QualifiedSwhid>() {
-                Ok(q) => println!("{q}"),
-                Err(_) => {
-                    let core: Swhid = swhid.parse()?;
-                    println!("{core}");
+        Command::Parse {
+            swhid,
+            stdin,
+            canonical,
+        } => {
+            if stdin {
+                use std::io::BufRead;
+                let mut failed = false;
+                for line in std::io::stdin().lock().lines() {
+                    let line = line?;
+                    let line = line.trim();
+                    if line.is_empty() {
+                        continue;
+                    }
+                    match parse_swhid(line, canonical) {
+                        Ok(s) => println!("{s}"),
+                        Err(e) => {
+                            println!("error: {line}: {e}");
+                            failed = true;
+                        }
+                    }
+                }
+                if failed {
+                    std::process::exit(1);
                 }
+            } else if let Some(swhid) = swhid {
+                println!("{}", parse_swhid(&swhid, canonical)?);
             }
         }
         Command::Verify {
             path,
             swhid,
             follow_symlinks,
-            exclude,
+            mut exclude,
+            exclude_from,
+            sort_large_dirs_externally,
+            keep_empty_dirs,
+            include_vcs_dirs,
+            max_file_size,
+            skip_oversized_files,
             permissions_source,
             permissions_policy,
             permissions_manifest,
         } => {
             let perm_source = parse_permissions_source(&permissions_source)?;
             let perm_policy = parse_permissions_policy(&permissions_policy)?;
+            if let Some(exclude_from) = exclude_from {
+                exclude.extend(read_exclude_file(&exclude_from)?);
+            }
 
             if perm_source == PermissionsSourceKind::Manifest && permissions_manifest.is_none() {
                 return Err(
@@ -214,6 +489,25 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
             }
 
             let expected: Swhid = swhid.parse()?;
+            // Hashing the path as the wrong kind of object would only report a
+            // confusing mismatch
+            let path_kind = if path.is_file() {
+                Some((ObjectType::Content, "file"))
+            } else if path.is_dir() {
+                Some((ObjectType::Directory, "directory"))
+            } else {
+                None
+            };
+            if let Some((object_type, kind)) = path_kind {
+                if expected.object_type() != object_type {
+                    eprintln!(
+                        "Error: expected {} SWHID but {} is a {kind}",
+                        object_type_description(expected.object_type()),
+                        path.display()
+                    );
+                    std::process::exit(1);
+                }
+            }
             let actual = if path.is_file() {
                 let bytes = std::fs::read(&path)?;
                 Content::from_bytes(bytes).swhid()
@@ -225,6 +519,12 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
                     walk_options: WalkOptions {
                         follow_symlinks,
                         exclude_suffixes: exclude,
+                        sort_large_dirs_externally,
+                        prune_empty_dirs: !keep_empty_dirs,
+                        skip_vcs_dirs: !include_vcs_dirs,
+                        max_file_size,
+                        skip_oversized_files,
+                        ..Default::default()
                     },
                 };
                 let dir = DiskDirectoryBuilder::new(&path).with_build_options(build_opts);
@@ -255,6 +555,31 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
                 std::process::exit(1);
             }
         }
+        Command::DebugManifest { path } => {
+            if path.is_file() {
+                print!("{}", debug::content_dump(&std::fs::read(&path)?));
+            } else if path.is_dir() {
+                let dir = DiskDirectoryBuilder::new(&path).build()?;
+                print!("{}", debug::directory_dump(&dir));
+            } else {
+                return Err(format!("{} is neither a file nor a directory", path.display()).into());
+            }
+        }
+        Command::Compare { a, b } => {
+            let cache_a = DirectoryCache::new(&a, DirectoryBuildOptions::default())?;
+            let cache_b = DirectoryCache::new(&b, DirectoryBuildOptions::default())?;
+            if cache_a.swhid() == cache_b.swhid() {
+                println!("✓ Same SWHID: {}", cache_a.swhid());
+                std::process::exit(0);
+            }
+            println!("✗ SWHIDs differ");
+            println!("  {}: {}", a.display(), cache_a.swhid());
+            println!("  {}: {}", b.display(), cache_b.swhid());
+            if let Some((path, difference)) = first_difference(&cache_a, &cache_b, PathBuf::new()) {
+                println!("  First difference: {}: {difference}", path.display());
+            }
+            std::process::exit(1);
+        }
         #[cfg(feature = "git")]
         Command::Git { cmd } => match cmd {
             GitCommand::Revision { repo, commit } => {
@@ -268,24 +593,97 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
                 let swhid = git::revision_swhid(&repo, &commit_oid)?;
                 println!("{swhid}");
             }
-            GitCommand::Release { repo, tag } => {
+            GitCommand::VerifyRevision {
+                repo,
+                commit,
+                swhid,
+            } => {
+                let repo = git::open_repo(&repo)?;
+                let commit_oid = git2::Oid::from_str(&commit)
+                    .map_err(|e| format!("Invalid commit hash: {e}"))?;
+                let expected: Swhid = swhid.parse()?;
+
+                if git::verify_revision(&repo, &commit_oid, &expected)? {
+                    println!("✓ Verification successful: {commit} matches {expected}");
+                    std::process::exit(0);
+                } else {
+                    let actual = git::revision_swhid(&repo, &commit_oid)?;
+                    println!("✗ Verification failed: {commit} does not match {expected}");
+                    println!("  Expected: {expected}");
+                    println!("  Actual:   {actual}");
+                    std::process::exit(1);
+                }
+            }
+            GitCommand::Release {
+                repo,
+                tag,
+                follow_lightweight,
+            } => {
                 let repo = git::open_repo(&repo)?;
-                let tag_oid = repo
-                    .refname_to_id(&format!("refs/tags/{tag}"))
-                    .map_err(|e| format!("Tag not found: {e}"))?;
-                let swhid = git::release_swhid(&repo, &tag_oid)?;
+                let policy = if follow_lightweight {
+                    git::LightweightTagPolicy::Target
+                } else {
+                    git::LightweightTagPolicy::Error
+                };
+                let swhid = git::tag_swhid(&repo, &tag, policy)?;
                 println!("{swhid}");
             }
-            GitCommand::Snapshot { repo } => {
+            GitCommand::Snapshot { repo, no_head } => {
                 let repo = git::open_repo(&repo)?;
-                let swhid = git::snapshot_swhid(&repo)?;
+                let opts = git::SnapshotOptions {
+                    include_head: !no_head,
+                    ..Default::default()
+                };
+                let swhid = git::snapshot_swhid_with_options(&repo, &opts)?;
                 println!("{swhid}");
             }
-            GitCommand::Tags { repo } => {
+            GitCommand::IdentifyAll { repo } => {
+                let repo = git::open_repo(&repo)?;
+                let revision = git::revision_from_git(&repo, &git::get_head_commit(&repo)?)?;
+                println!("revision: {}", revision.swhid());
+                println!(
+                    "directory: {}",
+                    Swhid::new(ObjectType::Directory, revision.directory)
+                );
+                println!("snapshot: {}", git::snapshot_swhid(&repo)?);
+            }
+            GitCommand::DebugManifest { repo, object } => {
+                let repo = git::open_repo(&repo)?;
+                let Some(object) = object else {
+                    print!("{}", debug::snapshot_dump(&git::snapshot_from_git(&repo)?));
+                    return Ok(());
+                };
+                let object = repo.revparse_single(&object)?;
+                let dump = match object.kind() {
+                    Some(git2::ObjectType::Commit) => {
+                        debug::revision_dump(&git::revision_from_git(&repo, &object.id())?)
+                    }
+                    Some(git2::ObjectType::Tag) => {
+                        debug::release_dump(&git::release_from_git(&repo, &object.id())?)
+                    }
+                    Some(git2::ObjectType::Tree) => {
+                        debug::directory_dump(&git::directory_from_git(&repo, &object.id())?)
+                    }
+                    Some(git2::ObjectType::Blob) => {
+                        debug::content_dump(object.peel_to_blob()?.content())
+                    }
+                    _ => return Err(format!("Unsupported object: {}", object.id()).into()),
+                };
+                print!("{dump}");
+            }
+            GitCommand::Tags { repo, swhid } => {
                 let repo = git::open_repo(&repo)?;
-                let tags = git::get_tags(&repo)?;
-                for tag_oid in tags {
-                    println!("{tag_oid}");
+                let tags = git::get_tags_named(&repo)?;
+                for (tag_name, tag_oid) in tags {
+                    if !swhid {
+                        println!("{tag_name}\t{tag_oid}");
+                    } else if repo.find_tag(tag_oid).is_ok() {
+                        let swhid = git::release_swhid(&repo, &tag_oid)?;
+                        println!("{tag_name}\t{tag_oid}\t{swhid}");
+                    } else {
+                        // Lightweight tags have no release object
+                        println!("{tag_name}\t{tag_oid}\t-");
+                    }
                 }
             }
         },
@@ -3,9 +3,11 @@
 //! This module provides types and traits for handling file permissions across
 //! different platforms, particularly addressing the Windows executable bit issue.
 
+use std::fmt::{self, Display};
 use std::path::Path;
 
 use crate::error::SwhidError;
+use crate::utils::normalize_relative_path;
 
 /// Entry permissions as specified in SWHID/Git tree format.
 ///
@@ -62,6 +64,13 @@ impl EntryPerms {
     }
 }
 
+impl Display for EntryPerms {
+    /// Formats as the Git mode string, see [`EntryPerms::to_git_mode_string`].
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.to_git_mode_string())
+    }
+}
+
 /// Executable bit status at the probe layer.
 ///
 /// This represents whether the executable bit is known or unknown.
@@ -308,6 +317,7 @@ impl PermissionsSource for GitTreePermissionsSource {
 /// Reads executable bit from a sidecar permission manifest file (TOML format).
 pub struct ManifestPermissionsSource {
     manifest: std::collections::HashMap<String, bool>,
+    root: Option<std::path::PathBuf>,
 }
 
 impl ManifestPermissionsSource {
@@ -376,41 +386,40 @@ impl ManifestPermissionsSource {
             }
 
             if let (Some(p), Some(exec)) = (path, executable) {
-                // Normalize path (forward slashes, reject .. and absolute)
-                let normalized = Self::normalize_path(&p)?;
+                let normalized = normalize_relative_path(&p).map_err(|e| {
+                    SwhidError::InvalidFormat(format!("Invalid path in manifest: {e}"))
+                })?;
                 manifest.insert(normalized, exec);
             }
         }
 
-        Ok(Self { manifest })
+        Ok(Self {
+            manifest,
+            root: None,
+        })
     }
 
-    fn normalize_path(path: &str) -> Result<String, SwhidError> {
-        // Reject absolute paths
-        if path.starts_with('/') || (cfg!(windows) && path.contains(':')) {
-            return Err(SwhidError::InvalidFormat(format!(
-                "Manifest contains absolute path: {}",
-                path
-            )));
-        }
-
-        // Reject .. segments
-        if path.contains("..") {
-            return Err(SwhidError::InvalidFormat(format!(
-                "Manifest contains '..' in path: {}",
-                path
-            )));
-        }
-
-        // Normalize to forward slashes
-        Ok(path.replace('\\', "/"))
+    /// Sets the directory the manifest paths are relative to, which is stripped
+    /// from the paths looked up, like the absolute paths of a directory walk.
+    ///
+    /// Without a root, looked up paths must be relative like the manifest's.
+    pub fn with_root(mut self, root: std::path::PathBuf) -> Self {
+        self.root = Some(root);
+        self
     }
 }
 
 impl PermissionsSource for ManifestPermissionsSource {
     fn executable_of(&self, path: &Path) -> Result<EntryExec, SwhidError> {
-        // Normalize path for lookup
-        let path_str = path.to_string_lossy().replace('\\', "/");
+        let rel_path = self
+            .root
+            .as_deref()
+            .and_then(|root| path.strip_prefix(root).ok())
+            .unwrap_or(path);
+        // Normalize path for lookup, like the manifest keys
+        let Ok(path_str) = normalize_relative_path(&rel_path.to_string_lossy()) else {
+            return Ok(EntryExec::Unknown);
+        };
         if let Some(&executable) = self.manifest.get(&path_str) {
             Ok(EntryExec::Known(executable))
         } else {
@@ -493,6 +502,15 @@ mod tests {
         assert_eq!(EntryPerms::RevisionRef.to_git_mode_string(), "160000");
     }
 
+    #[test]
+    fn entry_perms_display() {
+        assert_eq!(EntryPerms::File { executable: false }.to_string(), "100644");
+        assert_eq!(EntryPerms::File { executable: true }.to_string(), "100755");
+        assert_eq!(EntryPerms::Directory.to_string(), "040000");
+        assert_eq!(EntryPerms::Symlink.to_string(), "120000");
+        assert_eq!(EntryPerms::RevisionRef.to_string(), "160000");
+    }
+
     #[test]
     fn entry_perms_to_swh_mode_u32() {
         assert_eq!(
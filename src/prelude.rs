@@ -0,0 +1,10 @@
+//! The most used types, to import them all with `use swhid::prelude::*;`
+
+pub use crate::content::Content;
+pub use crate::core::{ObjectType, Swhid, SwhidObject};
+pub use crate::directory::{Directory, DiskDirectoryBuilder, Entry, WalkOptions};
+pub use crate::error::{DirectoryError, HeaderError, SnapshotError, SwhidError};
+pub use crate::qualifier::QualifiedSwhid;
+pub use crate::release::Release;
+pub use crate::revision::Revision;
+pub use crate::snapshot::{Branch, BranchTarget, Snapshot};
@@ -3,66 +3,92 @@ use std::str::FromStr;
 
 use percent_encoding::{percent_decode_str, utf8_percent_encode, AsciiSet};
 
-use crate::core::Swhid;
+use crate::core::{ObjectType, Swhid};
 use crate::error::SwhidError;
+use crate::utils::normalize_relative_path;
 
 /// Fragment sub‑selectors
 #[derive(Debug, Clone, PartialEq, Eq)]
 #[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct LineRange {
     pub start: u64,
-    pub end: Option<u64>, // inclusive range like "9-15", or single "9"
+    pub end: Option<RangeEnd>, // inclusive range like "9-15", or single "9"
 }
 #[derive(Debug, Clone, PartialEq, Eq)]
 #[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct ByteRange {
     pub start: u64,
-    pub end: Option<u64>,
+    pub end: Option<RangeEnd>,
 }
 
-fn parse_range(s: &str) -> Result<(u64, Option<u64>), SwhidError> {
+/// Upper bound of a [`LineRange`] or [`ByteRange`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum RangeEnd {
+    /// Last line or byte of the range, eg. `15` in `9-15`
+    Inclusive(u64),
+    /// The range extends to the end of the content, eg. `10-`.
+    ///
+    /// This is not part of the SWHID specification, and is only parsed with
+    /// [`ParseOptions::allow_open_ranges`]. SWHIDs with such a range are displayed
+    /// as such, so parsing them back with [`QualifiedSwhid::from_str`] fails and
+    /// needs [`QualifiedSwhid::parse_with_options`] too.
+    Open,
+}
+
+fn parse_range(s: &str, allow_open: bool) -> Result<(u64, Option<RangeEnd>), SwhidError> {
+    split_range(s, allow_open).ok_or_else(|| SwhidError::InvalidQualifierValue {
+        key: "range".into(),
+        value: s.into(),
+    })
+}
+
+fn split_range(s: &str, allow_open: bool) -> Option<(u64, Option<RangeEnd>)> {
     if let Some((a, b)) = s.split_once('-') {
-        let start: u64 = a.parse().map_err(|_| SwhidError::InvalidQualifierValue {
-            key: "range".into(),
-            value: s.into(),
-        })?;
-        let end: u64 = b.parse().map_err(|_| SwhidError::InvalidQualifierValue {
-            key: "range".into(),
-            value: s.into(),
-        })?;
+        let start: u64 = a.parse().ok()?;
+        if b.is_empty() && allow_open {
+            return Some((start, Some(RangeEnd::Open)));
+        }
+        let end: u64 = b.parse().ok()?;
         if end < start {
-            return Err(SwhidError::InvalidQualifierValue {
-                key: "range".into(),
-                value: s.into(),
-            });
+            return None;
         }
-        Ok((start, Some(end)))
+        Some((start, Some(RangeEnd::Inclusive(end))))
     } else {
-        let start: u64 = s.parse().map_err(|_| SwhidError::InvalidQualifierValue {
-            key: "range".into(),
-            value: s.into(),
-        })?;
-        Ok((start, None))
+        Some((s.parse().ok()?, None))
+    }
+}
+
+fn fmt_range(start: u64, end: Option<RangeEnd>, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    match end {
+        Some(RangeEnd::Inclusive(e)) => write!(f, "{start}-{e}"),
+        Some(RangeEnd::Open) => write!(f, "{start}-"),
+        None => write!(f, "{start}"),
     }
 }
 
 impl Display for LineRange {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        match self.end {
-            Some(e) => write!(f, "{}-{}", self.start, e),
-            None => write!(f, "{}", self.start),
-        }
+        fmt_range(self.start, self.end, f)
     }
 }
 impl Display for ByteRange {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        match self.end {
-            Some(e) => write!(f, "{}-{}", self.start, e),
-            None => write!(f, "{}", self.start),
-        }
+        fmt_range(self.start, self.end, f)
     }
 }
 
+/// Options for [`QualifiedSwhid::parse_with_options`]
+#[derive(Debug, Clone, Default)]
+pub struct ParseOptions {
+    /// Accept `lines` and `bytes` ranges without an end, like `lines=10-`, meaning
+    /// "up to the end of the content" (see [`RangeEnd::Open`]).
+    ///
+    /// Some tools produce them, but they are not valid SWHIDs, so this is off by
+    /// default.
+    pub allow_open_ranges: bool,
+}
+
 /// Known qualifier keys (order in output is canonicalized).
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub enum KnownKey {
@@ -85,6 +111,18 @@ impl KnownKey {
             KnownKey::Bytes => "bytes",
         }
     }
+
+    pub fn from_key(key: &str) -> Option<Self> {
+        match key {
+            "origin" => Some(KnownKey::Origin),
+            "visit" => Some(KnownKey::Visit),
+            "anchor" => Some(KnownKey::Anchor),
+            "path" => Some(KnownKey::Path),
+            "lines" => Some(KnownKey::Lines),
+            "bytes" => Some(KnownKey::Bytes),
+            _ => None,
+        }
+    }
 }
 
 /// A qualified SWHID with optional qualifiers.
@@ -117,9 +155,17 @@ impl QualifiedSwhid {
     pub fn core(&self) -> &Swhid {
         &self.core
     }
+    pub fn lines(&self) -> Option<&LineRange> {
+        self.lines.as_ref()
+    }
+    pub fn bytes(&self) -> Option<&ByteRange> {
+        self.bytes.as_ref()
+    }
 
+    /// Sets the `origin` qualifier, or unsets it if `url` is empty (see
+    /// [`QualifiedSwhid::with_path`]).
     pub fn with_origin(mut self, url: impl Into<String>) -> Self {
-        self.origin = Some(url.into());
+        self.origin = Some(url.into()).filter(|url| !url.is_empty());
         self
     }
     pub fn with_visit(mut self, id: Swhid) -> Self {
@@ -130,10 +176,21 @@ impl QualifiedSwhid {
         self.anchor = Some(id);
         self
     }
+    /// Sets the `path` qualifier, or unsets it if `path` is empty.
+    ///
+    /// Empty `origin` and `path` values are rejected when parsing, so they are never
+    /// stored, and every qualified SWHID displays as a string which parses back.
     pub fn with_path(mut self, path: impl Into<String>) -> Self {
-        self.path = Some(path.into());
+        self.path = Some(path.into()).filter(|path| !path.is_empty());
         self
     }
+    /// Sets the `path` qualifier from a path relative to the anchor's root, eg.
+    /// `src/lib.rs` for `path=/src/lib.rs`, normalized with
+    /// [`normalize_relative_path`].
+    pub fn with_relative_path(self, path: &str) -> Result<Self, SwhidError> {
+        let path = normalize_relative_path(path)?;
+        Ok(self.with_path(format!("/{path}")))
+    }
     pub fn with_lines(mut self, lines: LineRange) -> Self {
         self.lines = Some(lines);
         self
@@ -143,13 +200,117 @@ impl QualifiedSwhid {
         self
     }
 
+    /// Appends an unknown qualifier, even if one with the same key is already set;
+    /// see [`QualifiedSwhid::set_unknown`] to replace it instead.
     pub fn push_unknown(mut self, key: impl Into<String>, value: impl Into<String>) -> Self {
         self.others.push((key.into(), value.into()));
         self
     }
+
+    /// Sets an unknown qualifier, replacing every existing one with the same key.
+    ///
+    /// The qualifier keeps the position of the first one it replaces, so the output
+    /// order is stable.
+    pub fn set_unknown(mut self, key: impl Into<String>, value: impl Into<String>) -> Self {
+        let key = key.into();
+        let mut value = Some(value.into());
+        self.others.retain_mut(|(k, v)| {
+            if *k != key {
+                return true;
+            }
+            // overwrite the first one, remove the others
+            match value.take() {
+                Some(value) => {
+                    *v = value;
+                    true
+                }
+                None => false,
+            }
+        });
+        if let Some(value) = value {
+            self.others.push((key, value));
+        }
+        self
+    }
+
+    /// Value of the first unknown qualifier with this key
+    pub fn get_unknown(&self, key: &str) -> Option<&str> {
+        self.others
+            .iter()
+            .find(|(k, _)| k == key)
+            .map(|(_, v)| v.as_str())
+    }
+
+    /// The form to compare qualified SWHIDs as strings: like [`Display`], with the
+    /// known qualifiers in the order of the specification and `origin` and `path`
+    /// re-encoded, but also with unknown qualifiers sorted by key (keeping the order
+    /// of those with the same key), where `Display` keeps them in their given order.
+    pub fn canonical(&self) -> String {
+        let mut canonical = self.clone();
+        canonical.others.sort_by(|(a, _), (b, _)| a.cmp(b));
+        canonical.to_string()
+    }
+}
+
+/// Extract the part of `content` referenced by the `lines` or `bytes` qualifier of
+/// `swhid`, or the whole content if it has neither.
+///
+/// Line numbers start at 1 and byte offsets at 0; both ranges include their end.
+/// Extracted lines include their line terminator.
+pub fn extract_fragment(swhid: &QualifiedSwhid, content: &[u8]) -> Result<Vec<u8>, SwhidError> {
+    if swhid.core.object_type() != ObjectType::Content {
+        return Err(SwhidError::InvalidFormat(format!(
+            "fragments can only be extracted from contents, not {}",
+            swhid.core
+        )));
+    }
+    let out_of_range = |key: &str, value: String| SwhidError::InvalidQualifierValue {
+        key: key.to_owned(),
+        value,
+    };
+    match (&swhid.lines, &swhid.bytes) {
+        (None, None) => Ok(content.to_vec()),
+        (Some(lines), None) => {
+            let line_count = content.split_inclusive(|&byte| byte == b'\n').count() as u64;
+            let end = match lines.end {
+                None => lines.start,
+                Some(RangeEnd::Inclusive(end)) => end,
+                Some(RangeEnd::Open) => line_count,
+            };
+            if lines.start == 0 || end < lines.start || end > line_count {
+                return Err(out_of_range("lines", lines.to_string()));
+            }
+            Ok(content
+                .split_inclusive(|&byte| byte == b'\n')
+                .skip((lines.start - 1) as usize)
+                .take((end - lines.start + 1) as usize)
+                .collect::<Vec<_>>()
+                .concat())
+        }
+        (None, Some(bytes)) => {
+            let end = match bytes.end {
+                None => bytes.start,
+                Some(RangeEnd::Inclusive(end)) => end,
+                // an empty content has no last byte, which the range check rejects
+                Some(RangeEnd::Open) => (content.len() as u64).wrapping_sub(1),
+            };
+            if end < bytes.start {
+                return Err(out_of_range("bytes", bytes.to_string()));
+            }
+            usize::try_from(end)
+                .ok()
+                .and_then(|end| content.get(bytes.start as usize..=end))
+                .map(<[u8]>::to_vec)
+                .ok_or_else(|| out_of_range("bytes", bytes.to_string()))
+        }
+        (Some(_), Some(_)) => Err(SwhidError::InvalidFormat(
+            "lines and bytes qualifiers cannot be combined".to_owned(),
+        )),
+    }
 }
 
-const ESCAPED: &AsciiSet = &AsciiSet::EMPTY.add(b';');
+/// Characters which would be misparsed in qualifier values
+const ESCAPED: &AsciiSet = &AsciiSet::EMPTY.add(b';').add(b'%');
 
 impl Display for QualifiedSwhid {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
@@ -185,7 +346,15 @@ impl Display for QualifiedSwhid {
     }
 }
 
+/// Percent-decodes the value of an `origin` or `path` qualifier, which must not be
+/// empty.
 fn parse_string_qualifier(key: &'static str, value: &str) -> Result<String, SwhidError> {
+    if value.is_empty() {
+        return Err(SwhidError::InvalidQualifierValue {
+            key: key.to_string(),
+            value: value.to_owned(),
+        });
+    }
     Ok(percent_decode_str(value)
         .decode_utf8()
         .map_err(|_| SwhidError::InvalidQualifierValue {
@@ -195,10 +364,77 @@ fn parse_string_qualifier(key: &'static str, value: &str) -> Result<String, Swhi
         .into_owned())
 }
 
+/// Whether percent-decoding `value` gives valid UTF-8, without allocating.
+fn percent_decodes_to_utf8(value: &str) -> bool {
+    let mut buf = [0u8; 64];
+    let mut len = 0;
+    for byte in percent_decode_str(value) {
+        buf[len] = byte;
+        len += 1;
+        if len == buf.len() {
+            match std::str::from_utf8(&buf) {
+                Ok(_) => len = 0,
+                // a code point split across chunks: carry its first bytes over
+                Err(e) if e.error_len().is_none() => {
+                    let valid_up_to = e.valid_up_to();
+                    buf.copy_within(valid_up_to.., 0);
+                    len -= valid_up_to;
+                }
+                Err(_) => return false,
+            }
+        }
+    }
+    std::str::from_utf8(&buf[..len]).is_ok()
+}
+
+impl QualifiedSwhid {
+    /// Returns whether `s` is a valid qualified SWHID, ie. whether parsing it would
+    /// succeed.
+    ///
+    /// Unlike parsing, this does not allocate.
+    pub fn is_valid(s: &str) -> bool {
+        let (core_str, qstr) = match s.split_once(';') {
+            Some((c, rest)) => (c, rest),
+            None => (s, ""),
+        };
+        // bit set of the known qualifiers already seen
+        let mut seen = 0u8;
+        Swhid::is_valid(core_str)
+            && qstr.split(';').filter(|item| !item.is_empty()).all(|item| {
+                let Some((k, v)) = item.split_once('=') else {
+                    return false;
+                };
+                if let Some(key) = KnownKey::from_key(k) {
+                    let bit = 1 << key as u8;
+                    if seen & bit != 0 {
+                        return false;
+                    }
+                    seen |= bit;
+                }
+                match k {
+                    "" => false,
+                    "origin" | "path" => !v.is_empty() && percent_decodes_to_utf8(v),
+                    "visit" | "anchor" => Swhid::is_valid(v),
+                    "lines" | "bytes" => split_range(v, false).is_some(),
+                    _ => true,
+                }
+            })
+    }
+}
+
 impl FromStr for QualifiedSwhid {
     type Err = SwhidError;
 
     fn from_str(s: &str) -> Result<Self, Self::Err> {
+        QualifiedSwhid::parse_with_options(s, &ParseOptions::default())
+    }
+}
+
+impl QualifiedSwhid {
+    /// Parses a qualified SWHID, accepting the extensions enabled in `opts`.
+    ///
+    /// With the default options, this is the same as [`str::parse`].
+    pub fn parse_with_options(s: &str, opts: &ParseOptions) -> Result<Self, SwhidError> {
         let (core_str, qstr) = match s.split_once(';') {
             Some((c, rest)) => (c, Some(rest)),
             None => (s, None),
@@ -216,25 +452,96 @@ impl FromStr for QualifiedSwhid {
                 if k.is_empty() {
                     return Err(SwhidError::InvalidFormat(item.into()));
                 }
-                match k {
-                    "origin" => q.origin = Some(parse_string_qualifier("origin", v)?),
-                    "visit" => q.visit = Some(v.parse()?),
-                    "anchor" => q.anchor = Some(v.parse()?),
-                    "path" => q.path = Some(parse_string_qualifier("path", v)?),
-                    "lines" => {
-                        let (s, e) = super::qualifier::parse_range(v)?;
-                        q.lines = Some(LineRange { start: s, end: e });
-                    }
-                    "bytes" => {
-                        let (s, e) = super::qualifier::parse_range(v)?;
-                        q.bytes = Some(ByteRange { start: s, end: e });
-                    }
-                    other => q.others.push((other.to_owned(), v.to_owned())),
-                }
+                q.set_qualifier(k, v, opts)?;
             }
         }
         Ok(q)
     }
+
+    /// Sets the qualifier `key` from its value as written in a SWHID, ie.
+    /// percent-encoded for `origin` and `path`.
+    fn set_qualifier(&mut self, k: &str, v: &str, opts: &ParseOptions) -> Result<(), SwhidError> {
+        match k {
+            "origin" => set_once(&mut self.origin, k, parse_string_qualifier("origin", v)?)?,
+            "visit" => set_once(&mut self.visit, k, v.parse()?)?,
+            "anchor" => set_once(&mut self.anchor, k, v.parse()?)?,
+            "path" => set_once(&mut self.path, k, parse_string_qualifier("path", v)?)?,
+            "lines" => {
+                let (s, e) = parse_range(v, opts.allow_open_ranges)?;
+                set_once(&mut self.lines, k, LineRange { start: s, end: e })?;
+            }
+            "bytes" => {
+                let (s, e) = parse_range(v, opts.allow_open_ranges)?;
+                set_once(&mut self.bytes, k, ByteRange { start: s, end: e })?;
+            }
+            other => self.others.push((other.to_owned(), v.to_owned())),
+        }
+        Ok(())
+    }
+
+    /// Splits into the core SWHID and all its qualifiers as `(key, value)` pairs, in
+    /// the order of [`QualifiedSwhid::canonical`], with values as written in the
+    /// SWHID (so `origin` and `path` are percent-encoded).
+    pub fn into_parts(mut self) -> (Swhid, Vec<(String, String)>) {
+        let mut parts = Vec::new();
+        if let Some(o) = self.origin {
+            parts.push((
+                "origin".to_owned(),
+                utf8_percent_encode(&o, ESCAPED).to_string(),
+            ));
+        }
+        if let Some(v) = self.visit {
+            parts.push(("visit".to_owned(), v.to_string()));
+        }
+        if let Some(a) = self.anchor {
+            parts.push(("anchor".to_owned(), a.to_string()));
+        }
+        if let Some(p) = self.path {
+            parts.push((
+                "path".to_owned(),
+                utf8_percent_encode(&p, ESCAPED).to_string(),
+            ));
+        }
+        if let Some(l) = self.lines {
+            parts.push(("lines".to_owned(), l.to_string()));
+        }
+        if let Some(b) = self.bytes {
+            parts.push(("bytes".to_owned(), b.to_string()));
+        }
+        self.others.sort_by(|(a, _), (b, _)| a.cmp(b));
+        parts.extend(self.others);
+        (self.core, parts)
+    }
+
+    /// Inverse of [`QualifiedSwhid::into_parts`]: builds a qualified SWHID from its
+    /// core and qualifiers, validated like when parsing.
+    pub fn from_parts<K, V>(
+        core: Swhid,
+        qualifiers: impl IntoIterator<Item = (K, V)>,
+    ) -> Result<Self, SwhidError>
+    where
+        K: AsRef<str>,
+        V: AsRef<str>,
+    {
+        let mut q = QualifiedSwhid::new(core);
+        for (k, v) in qualifiers {
+            let (k, v) = (k.as_ref(), v.as_ref());
+            if k.is_empty() || k.contains(['=', ';']) || v.contains(';') {
+                return Err(SwhidError::InvalidFormat(format!("{k}={v}")));
+            }
+            q.set_qualifier(k, v, &ParseOptions::default())?;
+        }
+        Ok(q)
+    }
+}
+
+/// Sets a known qualifier, which may appear at most once
+fn set_once<T>(slot: &mut Option<T>, key: &str, value: T) -> Result<(), SwhidError> {
+    if slot.is_some() {
+        return Err(SwhidError::DuplicateQualifier(key.to_owned()));
+    }
+    *slot = Some(value);
+    Ok(())
 }
 
 #[cfg(feature = "serde")]
@@ -289,7 +596,7 @@ mod tests {
             .with_path("/src/lib.rs")
             .with_lines(LineRange {
                 start: 9,
-                end: Some(15),
+                end: Some(RangeEnd::Inclusive(15)),
             });
         let s = q.to_string();
         assert_eq!(s, "swh:1:cnt:b45ef6fec89518d314f546fd6c3025367b721684;origin=https://example.org/repo.git;path=/src/lib.rs;lines=9-15");
@@ -308,7 +615,7 @@ mod tests {
 
         let range = LineRange {
             start: 10,
-            end: Some(20),
+            end: Some(RangeEnd::Inclusive(20)),
         };
         assert_eq!(range.to_string(), "10-20");
     }
@@ -323,7 +630,7 @@ mod tests {
 
         let range = ByteRange {
             start: 100,
-            end: Some(200),
+            end: Some(RangeEnd::Inclusive(200)),
         };
         assert_eq!(range.to_string(), "100-200");
     }
@@ -332,11 +639,11 @@ mod tests {
     fn line_range_equality() {
         let range1 = LineRange {
             start: 10,
-            end: Some(20),
+            end: Some(RangeEnd::Inclusive(20)),
         };
         let range2 = LineRange {
             start: 10,
-            end: Some(20),
+            end: Some(RangeEnd::Inclusive(20)),
         };
         let range3 = LineRange {
             start: 10,
@@ -344,7 +651,7 @@ mod tests {
         };
         let range4 = LineRange {
             start: 11,
-            end: Some(20),
+            end: Some(RangeEnd::Inclusive(20)),
         };
 
         assert_eq!(range1, range2);
@@ -356,11 +663,11 @@ mod tests {
     fn byte_range_equality() {
         let range1 = ByteRange {
             start: 100,
-            end: Some(200),
+            end: Some(RangeEnd::Inclusive(200)),
         };
         let range2 = ByteRange {
             start: 100,
-            end: Some(200),
+            end: Some(RangeEnd::Inclusive(200)),
         };
         let range3 = ByteRange {
             start: 100,
@@ -368,7 +675,7 @@ mod tests {
         };
         let range4 = ByteRange {
             start: 101,
-            end: Some(200),
+            end: Some(RangeEnd::Inclusive(200)),
         };
 
         assert_eq!(range1, range2);
@@ -380,7 +687,7 @@ mod tests {
     fn line_range_debug() {
         let range = LineRange {
             start: 10,
-            end: Some(20),
+            end: Some(RangeEnd::Inclusive(20)),
         };
         let debug_str = format!("{range:?}");
         assert!(debug_str.contains("LineRange"));
@@ -392,7 +699,7 @@ mod tests {
     fn byte_range_debug() {
         let range = ByteRange {
             start: 100,
-            end: Some(200),
+            end: Some(RangeEnd::Inclusive(200)),
         };
         let debug_str = format!("{range:?}");
         assert!(debug_str.contains("ByteRange"));
@@ -404,7 +711,7 @@ mod tests {
     fn line_range_clone() {
         let range1 = LineRange {
             start: 10,
-            end: Some(20),
+            end: Some(RangeEnd::Inclusive(20)),
         };
         let range2 = range1.clone();
         assert_eq!(range1, range2);
@@ -414,7 +721,7 @@ mod tests {
     fn byte_range_clone() {
         let range1 = ByteRange {
             start: 100,
-            end: Some(200),
+            end: Some(RangeEnd::Inclusive(200)),
         };
         let range2 = range1.clone();
         assert_eq!(range1, range2);
@@ -537,7 +844,7 @@ mod tests {
             .unwrap();
         let lines = LineRange {
             start: 10,
-            end: Some(20),
+            end: Some(RangeEnd::Inclusive(20)),
         };
         let q = QualifiedSwhid::new(core).with_lines(lines.clone());
         assert_eq!(q.lines, Some(lines));
@@ -550,7 +857,7 @@ mod tests {
             .unwrap();
         let bytes = ByteRange {
             start: 100,
-            end: Some(200),
+            end: Some(RangeEnd::Inclusive(200)),
         };
         let q = QualifiedSwhid::new(core).with_bytes(bytes.clone());
         assert_eq!(q.bytes, Some(bytes));
@@ -576,7 +883,7 @@ mod tests {
             .with_path("/src/lib.rs")
             .with_lines(LineRange {
                 start: 10,
-                end: Some(20),
+                end: Some(RangeEnd::Inclusive(20)),
             })
             .push_unknown("custom", "value");
 
@@ -586,7 +893,7 @@ mod tests {
             q.lines,
             Some(LineRange {
                 start: 10,
-                end: Some(20)
+                end: Some(RangeEnd::Inclusive(20))
             })
         );
         assert_eq!(q.others.len(), 1);
@@ -685,7 +992,7 @@ mod tests {
             q.lines,
             Some(LineRange {
                 start: 10,
-                end: Some(20)
+                end: Some(RangeEnd::Inclusive(20))
             })
         );
     }
@@ -711,7 +1018,7 @@ mod tests {
             q.bytes,
             Some(ByteRange {
                 start: 100,
-                end: Some(200)
+                end: Some(RangeEnd::Inclusive(200))
             })
         );
     }
@@ -876,11 +1183,11 @@ mod tests {
             .with_path("/src/lib.rs")
             .with_lines(LineRange {
                 start: 10,
-                end: Some(20),
+                end: Some(RangeEnd::Inclusive(20)),
             })
             .with_bytes(ByteRange {
                 start: 100,
-                end: Some(200),
+                end: Some(RangeEnd::Inclusive(200)),
             })
             .push_unknown("custom1", "value1")
             .push_unknown("custom2", "value2");
@@ -890,26 +1197,402 @@ mod tests {
         assert_eq!(q, parsed);
     }
 
+    #[test]
+    fn with_relative_path() {
+        let core: Swhid = "swh:1:dir:4b825dc642cb6eb9a060e54bf8d69288fbee4904"
+            .parse()
+            .unwrap();
+        let q = QualifiedSwhid::new(core.clone())
+            .with_relative_path("src\\nested/./lib.rs")
+            .unwrap();
+        assert_eq!(
+            q.to_string(),
+            "swh:1:dir:4b825dc642cb6eb9a060e54bf8d69288fbee4904;path=/src/nested/lib.rs"
+        );
+        assert!(QualifiedSwhid::new(core.clone())
+            .with_relative_path("../lib.rs")
+            .is_err());
+        assert!(QualifiedSwhid::new(core)
+            .with_relative_path("/lib.rs")
+            .is_err());
+    }
+
+    #[test]
+    fn set_unknown() {
+        let core: Swhid = "swh:1:cnt:b45ef6fec89518d314f546fd6c3025367b721684"
+            .parse()
+            .unwrap();
+        let q = QualifiedSwhid::new(core)
+            .push_unknown("foo", "1")
+            .push_unknown("bar", "2")
+            .push_unknown("foo", "3");
+        assert_eq!(q.get_unknown("foo"), Some("1"));
+        assert!(q.to_string().ends_with(";foo=1;bar=2;foo=3"));
+
+        let q = q.set_unknown("foo", "4");
+        assert_eq!(q.get_unknown("foo"), Some("4"));
+        assert_eq!(q.get_unknown("bar"), Some("2"));
+        assert!(q.to_string().ends_with(";foo=4;bar=2"));
+
+        let q = q.set_unknown("baz", "5");
+        assert_eq!(q.get_unknown("baz"), Some("5"));
+        assert!(q.to_string().ends_with(";foo=4;bar=2;baz=5"));
+        assert_eq!(q.get_unknown("missing"), None);
+    }
+
+    #[test]
+    fn empty_values() {
+        let core = "swh:1:cnt:b45ef6fec89518d314f546fd6c3025367b721684";
+        for qualifier in ["origin=", "path="] {
+            let s = format!("{core};{qualifier}");
+            assert!(
+                matches!(
+                    s.parse::<QualifiedSwhid>(),
+                    Err(SwhidError::InvalidQualifierValue { .. })
+                ),
+                "{s}"
+            );
+            assert!(!QualifiedSwhid::is_valid(&s), "{s}");
+        }
+
+        // Empty values of unknown qualifiers are preserved
+        let s = format!("{core};foo=");
+        assert!(QualifiedSwhid::is_valid(&s));
+        assert_eq!(s.parse::<QualifiedSwhid>().unwrap().to_string(), s);
+
+        // Bare flags are not qualifiers
+        let s = format!("{core};foo");
+        assert!(s.parse::<QualifiedSwhid>().is_err());
+        assert!(!QualifiedSwhid::is_valid(&s));
+
+        // Builders ignore empty values, so the output always parses back
+        let q = QualifiedSwhid::new(core.parse().unwrap())
+            .with_origin("")
+            .with_path("");
+        assert_eq!(q.to_string(), core);
+        assert_eq!(q, core.parse().unwrap());
+        let q = q.with_path("/a").with_path("");
+        assert_eq!(q.to_string(), core);
+    }
+
+    #[test]
+    fn repeated_qualifiers() {
+        let core = "swh:1:cnt:b45ef6fec89518d314f546fd6c3025367b721684";
+        for qualifier in [
+            "origin=https://example.org/a",
+            "visit=swh:1:snp:0000000000000000000000000000000000000000",
+            "anchor=swh:1:rev:0000000000000000000000000000000000000000",
+            "path=/a",
+            "lines=1-2",
+            "bytes=3",
+        ] {
+            let key = qualifier.split_once('=').unwrap().0;
+            let s = format!("{core};{qualifier};lines=5;{qualifier}");
+            assert!(
+                matches!(
+                    s.parse::<QualifiedSwhid>(),
+                    Err(SwhidError::DuplicateQualifier(k)) if k == key
+                ),
+                "{s}"
+            );
+            assert!(!QualifiedSwhid::is_valid(&s), "{s}");
+        }
+
+        // Even with the same value
+        let s = format!("{core};path=/a;path=/a");
+        assert_eq!(
+            s.parse::<QualifiedSwhid>().unwrap_err().to_string(),
+            "duplicate qualifier: path"
+        );
+
+        // Unknown qualifiers are all kept
+        let s = format!("{core};foo=1;path=/a;foo=2");
+        assert!(QualifiedSwhid::is_valid(&s));
+        let q = s.parse::<QualifiedSwhid>().unwrap();
+        assert_eq!(q.get_unknown("foo"), Some("1"));
+        assert_eq!(q.to_string(), format!("{core};path=/a;foo=1;foo=2"));
+    }
+
     #[test]
     fn parse_range_valid() {
-        assert_eq!(parse_range("10").unwrap(), (10, None));
-        assert_eq!(parse_range("10-20").unwrap(), (10, Some(20)));
-        assert_eq!(parse_range("0").unwrap(), (0, None));
-        assert_eq!(parse_range("0-0").unwrap(), (0, Some(0)));
+        assert_eq!(parse_range("10", false).unwrap(), (10, None));
+        assert_eq!(
+            parse_range("10-20", false).unwrap(),
+            (10, Some(RangeEnd::Inclusive(20)))
+        );
+        assert_eq!(parse_range("0", false).unwrap(), (0, None));
+        assert_eq!(
+            parse_range("0-0", false).unwrap(),
+            (0, Some(RangeEnd::Inclusive(0)))
+        );
     }
 
     #[test]
     fn parse_range_invalid() {
-        assert!(parse_range("invalid").is_err());
-        assert!(parse_range("10-5").is_err()); // end < start
-        assert!(parse_range("-10").is_err());
-        assert!(parse_range("10-").is_err());
+        assert!(parse_range("invalid", false).is_err());
+        assert!(parse_range("10-5", false).is_err()); // end < start
+        assert!(parse_range("-10", false).is_err());
+        assert!(parse_range("10-", false).is_err());
+        assert!(parse_range("-10", true).is_err());
+        assert!(parse_range("-", true).is_err());
+    }
+
+    #[test]
+    fn parse_range_open() {
+        assert_eq!(
+            parse_range("10-", true).unwrap(),
+            (10, Some(RangeEnd::Open))
+        );
+        assert_eq!(
+            parse_range("10-20", true).unwrap(),
+            (10, Some(RangeEnd::Inclusive(20)))
+        );
+    }
+
+    #[test]
+    fn open_ranges() {
+        let s = "swh:1:cnt:b45ef6fec89518d314f546fd6c3025367b721684;lines=2-";
+        assert!(s.parse::<QualifiedSwhid>().is_err());
+        assert!(!QualifiedSwhid::is_valid(s));
+
+        let opts = ParseOptions {
+            allow_open_ranges: true,
+        };
+        let q = QualifiedSwhid::parse_with_options(s, &opts).unwrap();
+        assert_eq!(
+            q.lines(),
+            Some(&LineRange {
+                start: 2,
+                end: Some(RangeEnd::Open)
+            })
+        );
+        assert_eq!(q.to_string(), s);
+        assert_eq!(
+            extract_fragment(&q, b"line 1\nline 2\nline 3").unwrap(),
+            b"line 2\nline 3"
+        );
+
+        let s = "swh:1:cnt:b45ef6fec89518d314f546fd6c3025367b721684;bytes=5-";
+        let q = QualifiedSwhid::parse_with_options(s, &opts).unwrap();
+        assert_eq!(q.to_string(), s);
+        assert_eq!(extract_fragment(&q, b"line 1\n").unwrap(), b"1\n");
     }
 
     #[test]
     fn parse_range_edge_cases() {
-        assert_eq!(parse_range("0").unwrap(), (0, None));
-        assert_eq!(parse_range("0-0").unwrap(), (0, Some(0)));
-        assert_eq!(parse_range("1-1").unwrap(), (1, Some(1)));
+        assert_eq!(parse_range("0", false).unwrap(), (0, None));
+        assert_eq!(
+            parse_range("0-0", false).unwrap(),
+            (0, Some(RangeEnd::Inclusive(0)))
+        );
+        assert_eq!(
+            parse_range("1-1", false).unwrap(),
+            (1, Some(RangeEnd::Inclusive(1)))
+        );
+    }
+
+    #[test]
+    fn is_valid() {
+        assert!(QualifiedSwhid::is_valid(
+            "swh:1:cnt:b45ef6fec89518d314f546fd6c3025367b721684;origin=https://example.org/repo.git;path=/src/lib.rs;lines=9-15"
+        ));
+        assert!(QualifiedSwhid::is_valid(
+            "swh:1:cnt:b45ef6fec89518d314f546fd6c3025367b721684;path=/%C3%A9t%C3%A9"
+        ));
+        assert!(!QualifiedSwhid::is_valid(
+            "swh:1:cnt:b45ef6fec89518d314f546fd6c3025367b721684;path=/%C3"
+        ));
+        assert!(!QualifiedSwhid::is_valid(
+            "swh:1:cnt:b45ef6fec89518d314f546fd6c3025367b721684;lines=15-9"
+        ));
+        assert!(!QualifiedSwhid::is_valid(
+            "swh:1:cnt:b45ef6fec89518d314f546fd6c3025367b721684;=foo"
+        ));
+    }
+
+    #[test]
+    fn is_valid_long_percent_encoded_path() {
+        // Multi-byte code points straddle the chunks used for UTF-8 validation
+        let path: String = (0..100)
+            .map(|i| if i % 3 == 0 { 'é' } else { '€' })
+            .collect();
+        let s = format!(
+            "swh:1:cnt:b45ef6fec89518d314f546fd6c3025367b721684;path={}",
+            utf8_percent_encode(&path, percent_encoding::NON_ALPHANUMERIC)
+        );
+        assert!(s.parse::<QualifiedSwhid>().is_ok());
+        assert!(QualifiedSwhid::is_valid(&s));
+        let truncated = &s[..s.len() - 3];
+        assert!(truncated.parse::<QualifiedSwhid>().is_err());
+        assert!(!QualifiedSwhid::is_valid(truncated));
+    }
+
+    proptest::proptest! {
+        #[test]
+        fn is_valid_matches_parse(s in "\\PC*") {
+            proptest::prop_assert_eq!(QualifiedSwhid::is_valid(&s), s.parse::<QualifiedSwhid>().is_ok());
+        }
+
+        #[test]
+        fn is_valid_matches_parse_near_valid(
+            s in "swh:1:(cnt|xyz):[0-9a-f]{39,40}(;(origin|path|visit|anchor|lines|bytes|foo)?(=([0-9-]{0,5}|swh:1:rev:[0-9a-f]{40}|(%[0-9A-Fa-f]{2}|[a-z/])*))?)*"
+        ) {
+            proptest::prop_assert_eq!(QualifiedSwhid::is_valid(&s), s.parse::<QualifiedSwhid>().is_ok());
+        }
+    }
+
+    #[test]
+    fn roundtrip_reserved_characters() {
+        let q = QualifiedSwhid::new(
+            "swh:1:cnt:b45ef6fec89518d314f546fd6c3025367b721684"
+                .parse()
+                .unwrap(),
+        )
+        .with_origin("https://example.org/repo;a=b?c=%3B")
+        .with_path("/100%;=.txt");
+        let s = q.to_string();
+        assert_eq!(s, "swh:1:cnt:b45ef6fec89518d314f546fd6c3025367b721684;origin=https://example.org/repo%3Ba=b?c=%253B;path=/100%25%3B=.txt");
+        assert_eq!(s.parse::<QualifiedSwhid>().unwrap(), q);
+    }
+
+    proptest::proptest! {
+        #[test]
+        fn roundtrip_origin_and_path(
+            origin in "(https?|git)://[a-z.]{1,10}(/[a-zA-Z0-9;=%?&#:@!$'()*+,~._-]*)*",
+            path in "\\PC*",
+        ) {
+            let q = QualifiedSwhid::new(
+                "swh:1:cnt:b45ef6fec89518d314f546fd6c3025367b721684"
+                    .parse()
+                    .unwrap(),
+            )
+            .with_origin(origin)
+            .with_path(path)
+            .with_lines(LineRange {
+                start: 1,
+                end: Some(RangeEnd::Inclusive(2)),
+            });
+            proptest::prop_assert_eq!(q.to_string().parse::<QualifiedSwhid>().unwrap(), q);
+        }
+    }
+
+    #[test]
+    fn extract_fragment_lines_and_bytes() {
+        let content = b"line 1\nline 2\nline 3\nline 4";
+        let core: Swhid = "swh:1:cnt:b45ef6fec89518d314f546fd6c3025367b721684"
+            .parse()
+            .unwrap();
+
+        let q = QualifiedSwhid::new(core.clone()).with_lines(LineRange {
+            start: 2,
+            end: Some(RangeEnd::Inclusive(3)),
+        });
+        assert_eq!(extract_fragment(&q, content).unwrap(), b"line 2\nline 3\n");
+
+        let q = QualifiedSwhid::new(core.clone()).with_lines(LineRange {
+            start: 4,
+            end: None,
+        });
+        assert_eq!(extract_fragment(&q, content).unwrap(), b"line 4");
+
+        let q = QualifiedSwhid::new(core.clone()).with_bytes(ByteRange {
+            start: 0,
+            end: Some(RangeEnd::Inclusive(4)),
+        });
+        assert_eq!(extract_fragment(&q, content).unwrap(), b"line ");
+
+        let q = QualifiedSwhid::new(core.clone());
+        assert_eq!(extract_fragment(&q, content).unwrap(), content);
+    }
+
+    #[test]
+    fn extract_fragment_errors() {
+        let content = b"line 1\nline 2\n";
+        let core: Swhid = "swh:1:cnt:b45ef6fec89518d314f546fd6c3025367b721684"
+            .parse()
+            .unwrap();
+
+        for (start, end) in [(0, None), (2, Some(3)), (3, None), (2, Some(1))] {
+            let end = end.map(RangeEnd::Inclusive);
+            let q = QualifiedSwhid::new(core.clone()).with_lines(LineRange { start, end });
+            assert!(
+                matches!(
+                    extract_fragment(&q, content),
+                    Err(SwhidError::InvalidQualifierValue { .. })
+                ),
+                "lines={start}-{end:?}"
+            );
+        }
+        for (start, end) in [(0, Some(14)), (14, None), (5, Some(u64::MAX)), (3, Some(2))] {
+            let end = end.map(RangeEnd::Inclusive);
+            let q = QualifiedSwhid::new(core.clone()).with_bytes(ByteRange { start, end });
+            assert!(
+                matches!(
+                    extract_fragment(&q, content),
+                    Err(SwhidError::InvalidQualifierValue { .. })
+                ),
+                "bytes={start}-{end:?}"
+            );
+        }
+
+        // open ranges starting past the end
+        let q = QualifiedSwhid::new(core.clone()).with_lines(LineRange {
+            start: 3,
+            end: Some(RangeEnd::Open),
+        });
+        assert!(extract_fragment(&q, content).is_err());
+        let q = QualifiedSwhid::new(core.clone()).with_bytes(ByteRange {
+            start: 14,
+            end: Some(RangeEnd::Open),
+        });
+        assert!(extract_fragment(&q, content).is_err());
+        assert!(extract_fragment(&q, b"").is_err());
+
+        let dir: Swhid = "swh:1:dir:4b825dc642cb6eb9a060e54bf8d69288fbee4904"
+            .parse()
+            .unwrap();
+        assert!(extract_fragment(&QualifiedSwhid::new(dir), content).is_err());
+    }
+
+    #[test]
+    fn canonical_sorts_unknown_qualifiers() {
+        let q: QualifiedSwhid =
+            "swh:1:cnt:e69de29bb2d1d6434b8b29ae775ad8c2e48c5391;z=1;lines=3;a=2;z=0"
+                .parse()
+                .unwrap();
+        assert_eq!(
+            q.to_string(),
+            "swh:1:cnt:e69de29bb2d1d6434b8b29ae775ad8c2e48c5391;lines=3;z=1;a=2;z=0"
+        );
+        assert_eq!(
+            q.canonical(),
+            "swh:1:cnt:e69de29bb2d1d6434b8b29ae775ad8c2e48c5391;lines=3;a=2;z=1;z=0"
+        );
+        assert_eq!(
+            q.canonical().parse::<QualifiedSwhid>().unwrap().canonical(),
+            q.canonical()
+        );
+    }
+
+    #[test]
+    fn into_parts_roundtrip() {
+        let s = "swh:1:cnt:e69de29bb2d1d6434b8b29ae775ad8c2e48c5391;origin=https://example.org/a%3Bb;visit=swh:1:snp:d7f1b9eb7ccb596c2622c4780febaa02549830f9;anchor=swh:1:rev:2db189928c94d62a3b4757b3eec68f0a4d4113f0;path=/src/lib.rs;lines=1-2;z=9;x=1";
+        let q: QualifiedSwhid = s.parse().unwrap();
+        let (core, parts) = q.clone().into_parts();
+        assert_eq!(core, *q.core());
+        let keys: Vec<_> = parts.iter().map(|(k, _)| k.as_str()).collect();
+        assert_eq!(
+            keys,
+            ["origin", "visit", "anchor", "path", "lines", "x", "z"]
+        );
+        assert_eq!(parts[0].1, "https://example.org/a%3Bb");
+
+        let rebuilt = QualifiedSwhid::from_parts(core.clone(), parts.clone()).unwrap();
+        assert_eq!(rebuilt.to_string(), q.canonical());
+        assert_eq!(rebuilt.into_parts(), (core.clone(), parts));
+
+        assert!(QualifiedSwhid::from_parts(core.clone(), [("lines", "2-1")]).is_err());
+        assert!(QualifiedSwhid::from_parts(core.clone(), [("a", "b;c=d")]).is_err());
+        assert!(QualifiedSwhid::from_parts(core, [("", "x")]).is_err());
     }
 }
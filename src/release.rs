@@ -1,7 +1,18 @@
-use crate::utils::HeaderWriter;
-use crate::{Bytestring, Swhid};
+use std::cmp::Ordering;
+use std::fmt::{self, Display};
+use std::hash::{Hash, Hasher};
+
+use crate::authorship::Timestamp;
+use crate::core::SwhidObject;
+use crate::error::{HeaderError, SwhidError};
+use crate::utils::{
+    check_extra_headers, parse_authorship, parse_header, parse_object_id, split_manifest,
+    HeaderWriter,
+};
+use crate::{Bytestring, ObjectType, Swhid};
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum ReleaseTargetType {
     Revision,
     Directory,
@@ -9,16 +20,78 @@ pub enum ReleaseTargetType {
     Content,
 }
 
-#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+impl ReleaseTargetType {
+    /// Type of the SWHID of the release target
+    pub fn object_type(self) -> ObjectType {
+        match self {
+            ReleaseTargetType::Revision => ObjectType::Revision,
+            ReleaseTargetType::Directory => ObjectType::Directory,
+            ReleaseTargetType::Release => ObjectType::Release,
+            ReleaseTargetType::Content => ObjectType::Content,
+        }
+    }
+
+    /// Release target type for objects of the given type, `None` for snapshots which
+    /// cannot be the target of a release
+    pub fn from_object_type(object_type: ObjectType) -> Option<Self> {
+        match object_type {
+            ObjectType::Revision => Some(ReleaseTargetType::Revision),
+            ObjectType::Directory => Some(ReleaseTargetType::Directory),
+            ObjectType::Release => Some(ReleaseTargetType::Release),
+            ObjectType::Content => Some(ReleaseTargetType::Content),
+            ObjectType::Snapshot => None,
+        }
+    }
+}
+
+impl From<ReleaseTargetType> for ObjectType {
+    fn from(target_type: ReleaseTargetType) -> Self {
+        target_type.object_type()
+    }
+}
+
+impl Display for ReleaseTargetType {
+    /// Formats as the Git object type name (`commit`, `tree`, `tag` or `blob`), see
+    /// [`ObjectType::git_object_type`].
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.object_type().git_object_type())
+    }
+}
+
+/// A release (Git tag) object
+///
+/// `author`, `author_timestamp` and `author_timestamp_offset` describe the `tagger`
+/// line, and may be partially missing: the date is only hashed when both the
+/// timestamp and its offset are set, and nothing is hashed without an author (see
+/// [`rel_manifest`]).
+///
+/// Releases are compared, ordered and hashed by all their fields but
+/// `raw_manifest`.
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Release {
+    #[cfg_attr(feature = "serde", serde(with = "crate::hex_serde"))]
     pub object: [u8; 20],
     pub object_type: ReleaseTargetType,
+    #[cfg_attr(feature = "serde", serde(with = "crate::hex_serde"))]
     pub name: Bytestring,
+    #[cfg_attr(feature = "serde", serde(with = "crate::hex_serde::option"))]
     pub author: Option<Bytestring>,
     pub author_timestamp: Option<i64>,
+    #[cfg_attr(feature = "serde", serde(with = "crate::hex_serde::option"))]
     pub author_timestamp_offset: Option<Bytestring>,
+    #[cfg_attr(feature = "serde", serde(with = "crate::hex_serde::pairs"))]
     pub extra_headers: Vec<(Bytestring, Bytestring)>,
+    /// The message of `git tag -m ""` is empty but `Some`, as Git still writes the
+    /// blank line after the headers, which `None` omits.
+    #[cfg_attr(feature = "serde", serde(with = "crate::hex_serde::option"))]
     pub message: Option<Bytestring>,
+    /// Exact manifest of the release, if the other fields cannot reproduce it (see
+    /// [`Release::from_manifest`]). Like
+    /// [`Revision::raw_manifest`](crate::Revision::raw_manifest), it is ignored once
+    /// one of the other fields is changed, and not serialized.
+    #[cfg_attr(feature = "serde", serde(skip))]
+    pub raw_manifest: Option<Bytestring>,
 }
 
 impl Release {
@@ -26,15 +99,166 @@ impl Release {
     ///
     /// This implements the SWHID v1.2 revision hashing algorithm for Git commits,
     /// creating a `swh:1:rev:<digest>` identifier according to the specification.
+    ///
+    /// Keys of `extra_headers` are written verbatim, see [`Release::check_headers`].
     pub fn swhid(&self) -> Swhid {
         let manifest = rel_manifest(self);
-        let digest = crate::hash::hash_swhid_object("tag", &manifest);
+        let digest =
+            crate::hash::hash_swhid_object(ObjectType::Release.git_object_type(), &manifest);
+
+        Swhid::new(ObjectType::Release, digest)
+    }
+
+    /// Checks the keys of `extra_headers` are non-empty and contain no space or
+    /// newline.
+    ///
+    /// Such keys would be written verbatim to the manifest, and corrupt it, so this
+    /// should be called before computing the SWHID of a release built from untrusted
+    /// data.
+    pub fn check_headers(&self) -> Result<(), HeaderError> {
+        check_extra_headers(&self.extra_headers)
+    }
+
+    /// Parses a release from its manifest, ie. the bytes of a Git tag object without
+    /// the `tag <length>\0` header, as printed by `git cat-file tag`.
+    ///
+    /// Extra headers may come anywhere after `tag`, eg. before `tagger`. If
+    /// [`rel_manifest`] would not write the fields back identically, `manifest` is
+    /// kept as [`Release::raw_manifest`], so that the SWHID of the release still
+    /// identifies these bytes. Fails if the manifest is malformed.
+    pub fn from_manifest(manifest: &[u8]) -> Result<Self, SwhidError> {
+        let invalid = |msg: &str| SwhidError::InvalidFormat(format!("release manifest: {msg}"));
+        let (headers, message) = split_manifest(manifest);
+        let mut headers = parse_header(headers)?.into_iter().peekable();
+
+        let object = match headers.next() {
+            Some((b"object", value)) => {
+                parse_object_id(&value).ok_or_else(|| invalid("bad object"))?
+            }
+            _ => return Err(invalid("missing object")),
+        };
+        let object_type = match headers.next() {
+            Some((b"type", value)) => std::str::from_utf8(&value)
+                .ok()
+                .and_then(|name| ObjectType::from_git_name(name).ok())
+                .and_then(ReleaseTargetType::from_object_type)
+                .ok_or_else(|| invalid("bad type"))?,
+            _ => return Err(invalid("missing type")),
+        };
+        let name = match headers.next() {
+            Some((b"tag", value)) => value,
+            _ => return Err(invalid("missing tag")),
+        };
+        let mut tagger = None;
+        let mut extra_headers = Vec::new();
+        for (key, value) in headers {
+            match key {
+                b"tagger" if tagger.is_none() => tagger = Some(value),
+                _ => extra_headers.push((key.into(), value)),
+            }
+        }
+        let (author, timestamp) = match tagger {
+            Some(value) => match parse_authorship(&value) {
+                Some((author, timestamp)) => (Some(author), Some(timestamp)),
+                None => (Some(value), None),
+            },
+            None => (None, None),
+        };
+
+        let mut rel = Release {
+            object,
+            object_type,
+            name,
+            author,
+            author_timestamp: timestamp.as_ref().map(|timestamp| timestamp.seconds),
+            author_timestamp_offset: timestamp.map(|timestamp| timestamp.offset),
+            extra_headers,
+            message: message.map(Into::into),
+            raw_manifest: None,
+        };
+        if rel_manifest(&rel) != manifest {
+            rel.raw_manifest = Some(manifest.into());
+        }
+        Ok(rel)
+    }
+
+    /// [`Release::raw_manifest`], if it is still the manifest of the other fields
+    fn current_raw_manifest(&self) -> Option<&[u8]> {
+        let raw_manifest = self.raw_manifest.as_deref()?;
+        Release::from_manifest(raw_manifest)
+            .is_ok_and(|parsed| parsed == *self)
+            .then_some(raw_manifest)
+    }
+
+    /// All the fields but `raw_manifest`, which releases are compared by
+    fn fields(&self) -> impl Ord + Hash + '_ {
+        let Release {
+            object,
+            object_type,
+            name,
+            author,
+            author_timestamp,
+            author_timestamp_offset,
+            extra_headers,
+            message,
+            raw_manifest: _,
+        } = self;
+        (
+            object,
+            object_type,
+            name,
+            author,
+            author_timestamp,
+            author_timestamp_offset,
+            extra_headers,
+            message,
+        )
+    }
+}
 
-        Swhid::new(crate::ObjectType::Release, digest)
+impl PartialEq for Release {
+    fn eq(&self, other: &Self) -> bool {
+        self.fields() == other.fields()
     }
 }
 
+impl Eq for Release {}
+
+impl PartialOrd for Release {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Release {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.fields().cmp(&other.fields())
+    }
+}
+
+impl Hash for Release {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.fields().hash(state)
+    }
+}
+
+impl SwhidObject for Release {
+    fn swhid(&self) -> Swhid {
+        Release::swhid(self)
+    }
+
+    fn object_type(&self) -> ObjectType {
+        ObjectType::Release
+    }
+}
+
+/// The bytes hashed (after the object header) to compute the release identifier:
+/// [`Release::raw_manifest`] if set for the other fields, or the Git tag object
+/// written from them.
 pub fn rel_manifest(rev: &Release) -> Vec<u8> {
+    if let Some(raw_manifest) = rev.current_raw_manifest() {
+        return raw_manifest.to_vec();
+    }
     let Release {
         object,
         object_type,
@@ -44,31 +268,29 @@ pub fn rel_manifest(rev: &Release) -> Vec<u8> {
         author_timestamp_offset,
         extra_headers,
         message,
+        raw_manifest: _,
     } = rev;
     let mut writer = HeaderWriter::default();
 
     writer.push(b"object", hex::encode(object));
-    writer.push(
-        b"type",
-        match object_type {
-            ReleaseTargetType::Revision => b"commit".as_ref(),
-            ReleaseTargetType::Directory => b"tree".as_ref(),
-            ReleaseTargetType::Release => b"tag".as_ref(),
-            ReleaseTargetType::Content => b"blob".as_ref(),
-        },
-    );
+    writer.push(b"type", object_type.object_type().git_object_type());
     writer.push(b"tag", name);
 
-    match (author, author_timestamp, author_timestamp_offset) {
-        (Some(author), Some(author_timestamp), Some(author_timestamp_offset)) => writer
-            .push_authorship(
+    // Same rule as Software Heritage's reference implementation (swh.model), which
+    // stores the tagger's timestamp and offset as a single optional date: the
+    // `tagger` line is written whenever there is an author, and only carries the date
+    // if both the timestamp and the offset are known. A date without an author is not
+    // represented.
+    // See https://github.com/swhid/specification/issues/62
+    if let Some(author) = author {
+        match (author_timestamp, author_timestamp_offset) {
+            (Some(author_timestamp), Some(author_timestamp_offset)) => writer.push_authorship(
                 b"tagger",
                 author,
-                *author_timestamp,
-                author_timestamp_offset,
+                &Timestamp::new(*author_timestamp, author_timestamp_offset.clone()),
             ),
-        (None, None, None) => (),
-        _ => (), // unspecified, see https://github.com/swhid/specification/issues/62
+            _ => writer.push(b"tagger", author),
+        }
     }
 
     for (key, value) in extra_headers {
@@ -1,18 +1,51 @@
-use crate::utils::HeaderWriter;
-use crate::{Bytestring, Swhid};
+use std::cmp::Ordering;
+use std::hash::{Hash, Hasher};
 
-#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+use crate::authorship::Timestamp;
+use crate::core::SwhidObject;
+use crate::error::{HeaderError, SwhidError};
+use crate::utils::{
+    check_extra_headers, parse_authorship, parse_header, parse_object_id, split_manifest,
+    HeaderWriter,
+};
+use crate::{Bytestring, ObjectType, Swhid};
+
+/// A revision (Git commit) object
+///
+/// Revisions are compared, ordered and hashed by all their fields but
+/// `raw_manifest`.
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Revision {
+    #[cfg_attr(feature = "serde", serde(with = "crate::hex_serde"))]
     pub directory: [u8; 20],
+    #[cfg_attr(feature = "serde", serde(with = "crate::hex_serde::vec"))]
     pub parents: Vec<[u8; 20]>,
+    #[cfg_attr(feature = "serde", serde(with = "crate::hex_serde"))]
     pub author: Bytestring,
     pub author_timestamp: i64,
+    #[cfg_attr(feature = "serde", serde(with = "crate::hex_serde"))]
     pub author_timestamp_offset: Bytestring,
+    #[cfg_attr(feature = "serde", serde(with = "crate::hex_serde"))]
     pub committer: Bytestring,
     pub committer_timestamp: i64,
+    #[cfg_attr(feature = "serde", serde(with = "crate::hex_serde"))]
     pub committer_timestamp_offset: Bytestring,
+    #[cfg_attr(feature = "serde", serde(with = "crate::hex_serde::pairs"))]
     pub extra_headers: Vec<(Bytestring, Bytestring)>,
+    /// Written after a blank line ending the headers. Git always writes that line,
+    /// so an empty message is `Some` of an empty string, while `None` omits the line.
+    #[cfg_attr(feature = "serde", serde(with = "crate::hex_serde::option"))]
     pub message: Option<Bytestring>,
+    /// Exact manifest of the revision, if the other fields cannot reproduce it, eg.
+    /// for a commit with an extra header between `author` and `committer` (see
+    /// [`Revision::from_manifest`]).
+    ///
+    /// It is hashed instead of the manifest written from the other fields, like in
+    /// Software Heritage's data model, as long as it parses to these fields: once one
+    /// of them is changed, it is ignored. It is not serialized.
+    #[cfg_attr(feature = "serde", serde(skip))]
+    pub raw_manifest: Option<Bytestring>,
 }
 
 impl Revision {
@@ -20,15 +53,166 @@ impl Revision {
     ///
     /// This implements the SWHID v1.2 revision hashing algorithm for Git commits,
     /// creating a `swh:1:rev:<digest>` identifier according to the specification.
+    ///
+    /// Keys of `extra_headers` are written verbatim, see [`Revision::check_headers`].
     pub fn swhid(&self) -> Swhid {
         let manifest = rev_manifest(self);
-        let digest = crate::hash::hash_swhid_object("commit", &manifest);
+        let digest =
+            crate::hash::hash_swhid_object(ObjectType::Revision.git_object_type(), &manifest);
+
+        Swhid::new(ObjectType::Revision, digest)
+    }
+
+    /// Checks the keys of `extra_headers` are non-empty and contain no space or
+    /// newline.
+    ///
+    /// Such keys would be written verbatim to the manifest, and corrupt it, so this
+    /// should be called before computing the SWHID of a revision built from untrusted
+    /// data.
+    pub fn check_headers(&self) -> Result<(), HeaderError> {
+        check_extra_headers(&self.extra_headers)
+    }
+
+    /// Parses a revision from its manifest, ie. the bytes of a Git commit object
+    /// without the `commit <length>\0` header, as printed by `git cat-file commit`.
+    ///
+    /// Extra headers may come anywhere after `tree` and `parent`, eg. between `author`
+    /// and `committer`. If [`rev_manifest`] would not write the fields back
+    /// identically, `manifest` is kept as [`Revision::raw_manifest`], so that the
+    /// SWHID of the revision still identifies these bytes. Fails if the manifest is
+    /// malformed.
+    pub fn from_manifest(manifest: &[u8]) -> Result<Self, SwhidError> {
+        let invalid = |msg: &str| SwhidError::InvalidFormat(format!("revision manifest: {msg}"));
+        let (headers, message) = split_manifest(manifest);
+        let mut headers = parse_header(headers)?.into_iter().peekable();
+
+        let directory = match headers.next() {
+            Some((b"tree", value)) => parse_object_id(&value).ok_or_else(|| invalid("bad tree"))?,
+            _ => return Err(invalid("missing tree")),
+        };
+        let mut parents = Vec::new();
+        while let Some((_, value)) = headers.next_if(|(key, _)| *key == b"parent") {
+            parents.push(parse_object_id(&value).ok_or_else(|| invalid("bad parent"))?);
+        }
+        let mut author = None;
+        let mut committer = None;
+        let mut extra_headers = Vec::new();
+        for (key, value) in headers {
+            match key {
+                b"author" if author.is_none() => {
+                    author = Some(parse_authorship(&value).ok_or_else(|| invalid("bad author"))?);
+                }
+                b"committer" if committer.is_none() => {
+                    committer =
+                        Some(parse_authorship(&value).ok_or_else(|| invalid("bad committer"))?);
+                }
+                _ => extra_headers.push((key.into(), value)),
+            }
+        }
+        let (author, author_timestamp) = author.ok_or_else(|| invalid("missing author"))?;
+        let (committer, committer_timestamp) =
+            committer.ok_or_else(|| invalid("missing committer"))?;
+
+        let mut rev = Revision {
+            directory,
+            parents,
+            author,
+            author_timestamp: author_timestamp.seconds,
+            author_timestamp_offset: author_timestamp.offset,
+            committer,
+            committer_timestamp: committer_timestamp.seconds,
+            committer_timestamp_offset: committer_timestamp.offset,
+            extra_headers,
+            message: message.map(Into::into),
+            raw_manifest: None,
+        };
+        if rev_manifest(&rev) != manifest {
+            rev.raw_manifest = Some(manifest.into());
+        }
+        Ok(rev)
+    }
+
+    /// [`Revision::raw_manifest`], if it is still the manifest of the other fields
+    fn current_raw_manifest(&self) -> Option<&[u8]> {
+        let raw_manifest = self.raw_manifest.as_deref()?;
+        Revision::from_manifest(raw_manifest)
+            .is_ok_and(|parsed| parsed == *self)
+            .then_some(raw_manifest)
+    }
+
+    /// All the fields but `raw_manifest`, which revisions are compared by
+    fn fields(&self) -> impl Ord + Hash + '_ {
+        let Revision {
+            directory,
+            parents,
+            author,
+            author_timestamp,
+            author_timestamp_offset,
+            committer,
+            committer_timestamp,
+            committer_timestamp_offset,
+            extra_headers,
+            message,
+            raw_manifest: _,
+        } = self;
+        (
+            directory,
+            parents,
+            author,
+            author_timestamp,
+            author_timestamp_offset,
+            committer,
+            committer_timestamp,
+            committer_timestamp_offset,
+            extra_headers,
+            message,
+        )
+    }
+}
 
-        Swhid::new(crate::ObjectType::Revision, digest)
+impl PartialEq for Revision {
+    fn eq(&self, other: &Self) -> bool {
+        self.fields() == other.fields()
     }
 }
 
+impl Eq for Revision {}
+
+impl PartialOrd for Revision {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Revision {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.fields().cmp(&other.fields())
+    }
+}
+
+impl Hash for Revision {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.fields().hash(state)
+    }
+}
+
+impl SwhidObject for Revision {
+    fn swhid(&self) -> Swhid {
+        Revision::swhid(self)
+    }
+
+    fn object_type(&self) -> ObjectType {
+        ObjectType::Revision
+    }
+}
+
+/// The bytes hashed (after the object header) to compute the revision identifier:
+/// [`Revision::raw_manifest`] if set for the other fields, or the Git commit object
+/// written from them.
 pub fn rev_manifest(rev: &Revision) -> Vec<u8> {
+    if let Some(raw_manifest) = rev.current_raw_manifest() {
+        return raw_manifest.to_vec();
+    }
     let Revision {
         directory,
         parents,
@@ -40,6 +224,7 @@ pub fn rev_manifest(rev: &Revision) -> Vec<u8> {
         committer_timestamp_offset,
         extra_headers,
         message,
+        raw_manifest: _,
     } = rev;
     let mut writer = HeaderWriter::default();
     writer.push(b"tree", hex::encode(directory));
@@ -51,14 +236,12 @@ pub fn rev_manifest(rev: &Revision) -> Vec<u8> {
     writer.push_authorship(
         b"author",
         author,
-        *author_timestamp,
-        author_timestamp_offset,
+        &Timestamp::new(*author_timestamp, author_timestamp_offset.clone()),
     );
     writer.push_authorship(
         b"committer",
         committer,
-        *committer_timestamp,
-        committer_timestamp_offset,
+        &Timestamp::new(*committer_timestamp, committer_timestamp_offset.clone()),
     );
 
     for (key, value) in extra_headers {
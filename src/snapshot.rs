@@ -1,20 +1,109 @@
-use crate::core::{ObjectType, Swhid};
-use crate::error::SnapshotError;
+use std::str::FromStr;
+
+use crate::core::{ObjectType, Swhid, SwhidObject};
+use crate::error::{SnapshotError, SwhidError};
 use crate::hash::hash_swhid_object;
 use crate::utils::check_unique;
 use crate::Bytestring;
 
 #[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum BranchTarget {
-    Content(Option<[u8; 20]>),
-    Directory(Option<[u8; 20]>),
-    Revision(Option<[u8; 20]>),
-    Release(Option<[u8; 20]>),
-    Snapshot(Option<[u8; 20]>),
-    Alias(Option<Bytestring>),
+    Content(
+        #[cfg_attr(feature = "serde", serde(with = "crate::hex_serde::option"))] Option<[u8; 20]>,
+    ),
+    Directory(
+        #[cfg_attr(feature = "serde", serde(with = "crate::hex_serde::option"))] Option<[u8; 20]>,
+    ),
+    Revision(
+        #[cfg_attr(feature = "serde", serde(with = "crate::hex_serde::option"))] Option<[u8; 20]>,
+    ),
+    Release(
+        #[cfg_attr(feature = "serde", serde(with = "crate::hex_serde::option"))] Option<[u8; 20]>,
+    ),
+    /// Another snapshot. Git references never point to snapshots, so this is only
+    /// produced by other sources, eg. archives of several repositories; see
+    /// [`BranchTarget::snapshot`].
+    Snapshot(
+        #[cfg_attr(feature = "serde", serde(with = "crate::hex_serde::option"))] Option<[u8; 20]>,
+    ),
+    Alias(
+        #[cfg_attr(feature = "serde", serde(with = "crate::hex_serde::option"))] Option<Bytestring>,
+    ),
 }
 
 impl BranchTarget {
+    /// Build a target from its type name, as in snapshot manifests (eg. `revision`
+    /// or `alias`), and its raw id or alias target, `None` for dangling branches.
+    ///
+    /// Object ids must be exactly 20 bytes; this is where ids from external sources
+    /// should be validated, as other targets are 20 bytes by construction. Alias
+    /// targets must not be empty, as they would be hashed like dangling aliases.
+    pub fn from_raw(target_type: &str, target: Option<&[u8]>) -> Result<Self, SnapshotError> {
+        let object_id = |target_type: &'static str| {
+            target
+                .map(|id| {
+                    id.try_into()
+                        .map_err(|_| SnapshotError::InvalidTargetLength {
+                            target_type,
+                            len: id.len(),
+                        })
+                })
+                .transpose()
+        };
+        Ok(match target_type {
+            "content" => BranchTarget::Content(object_id("content")?),
+            "directory" => BranchTarget::Directory(object_id("directory")?),
+            "revision" => BranchTarget::Revision(object_id("revision")?),
+            "release" => BranchTarget::Release(object_id("release")?),
+            "snapshot" => BranchTarget::Snapshot(object_id("snapshot")?),
+            "alias" if target == Some(b"") => return Err(SnapshotError::EmptyAliasTarget),
+            "alias" => BranchTarget::Alias(target.map(Into::into)),
+            other => return Err(SnapshotError::InvalidTargetType(other.to_owned())),
+        })
+    }
+
+    /// Build a target pointing to an object of the given type, or a dangling target
+    /// if `id` is `None`
+    pub fn from_object_type(object_type: ObjectType, id: Option<[u8; 20]>) -> Self {
+        match object_type {
+            ObjectType::Content => BranchTarget::Content(id),
+            ObjectType::Directory => BranchTarget::Directory(id),
+            ObjectType::Revision => BranchTarget::Revision(id),
+            ObjectType::Release => BranchTarget::Release(id),
+            ObjectType::Snapshot => BranchTarget::Snapshot(id),
+        }
+    }
+
+    /// Target pointing to another snapshot, by its identifier
+    pub fn snapshot(snapshot: &Snapshot) -> Self {
+        BranchTarget::Snapshot(Some(*snapshot.swhid().digest_bytes()))
+    }
+
+    /// Type of the target object, `None` for aliases which point to another branch
+    pub fn object_type(&self) -> Option<ObjectType> {
+        match self {
+            BranchTarget::Content(_) => Some(ObjectType::Content),
+            BranchTarget::Directory(_) => Some(ObjectType::Directory),
+            BranchTarget::Revision(_) => Some(ObjectType::Revision),
+            BranchTarget::Release(_) => Some(ObjectType::Release),
+            BranchTarget::Snapshot(_) => Some(ObjectType::Snapshot),
+            BranchTarget::Alias(_) => None,
+        }
+    }
+
+    /// Name of the target type, as in snapshot manifests
+    pub fn type_name(&self) -> &'static str {
+        match self {
+            BranchTarget::Content(_) => "content",
+            BranchTarget::Directory(_) => "directory",
+            BranchTarget::Revision(_) => "revision",
+            BranchTarget::Release(_) => "release",
+            BranchTarget::Snapshot(_) => "snapshot",
+            BranchTarget::Alias(_) => "alias",
+        }
+    }
+
     fn target_id(&self) -> &[u8] {
         match self {
             BranchTarget::Content(id)
@@ -27,9 +116,25 @@ impl BranchTarget {
     }
 }
 
+/// Parses the SWHID of the target object, eg. `swh:1:rev:<id>` for a
+/// [`BranchTarget::Revision`]
+impl FromStr for BranchTarget {
+    type Err = SwhidError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let swhid: Swhid = s.parse()?;
+        Ok(BranchTarget::from_object_type(
+            swhid.object_type(),
+            Some(*swhid.digest_bytes()),
+        ))
+    }
+}
+
 #[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 /// Item in a [`Snapshot`]
 pub struct Branch {
+    #[cfg_attr(feature = "serde", serde(with = "crate::hex_serde"))]
     pub name: Bytestring,
     pub target: BranchTarget,
 }
@@ -47,36 +152,148 @@ pub struct Snapshot {
 }
 
 impl Snapshot {
+    /// Sort branches and check they are valid, see [`sort_branches`].
+    ///
+    /// Object targets are 20 bytes (or dangling) by construction; ids from external
+    /// sources can be validated with [`BranchTarget::from_raw`].
     pub fn new(mut branches: Vec<Branch>) -> Result<Self, SnapshotError> {
-        sort_and_check_branches(&mut branches)?;
+        sort_branches(&mut branches)?;
 
         Ok(Self { branches })
     }
 
+    /// Same as [`Snapshot::new`], from `(name, target)` pairs, eg. the references of
+    /// a version control system other than Git.
+    pub fn from_refs(
+        refs: impl IntoIterator<Item = (Bytestring, BranchTarget)>,
+    ) -> Result<Self, SnapshotError> {
+        Self::new(
+            refs.into_iter()
+                .map(|(name, target)| Branch::new(name, target))
+                .collect(),
+        )
+    }
+
+    /// Parses a snapshot manifest, as written by [`Snapshot::manifest`], made of
+    /// `<type> <name>\0<len>:<target>` records where the target is an object id,
+    /// the name of the aliased branch, or empty for dangling branches.
+    ///
+    /// Branches must be in the canonical order.
+    pub fn from_manifest(manifest: &[u8]) -> Result<Self, SnapshotError> {
+        let invalid = |msg: &str| SnapshotError::InvalidManifest(msg.to_owned());
+        let mut branches = Vec::new();
+        let mut records = manifest;
+        while !records.is_empty() {
+            let (target_type, rest) =
+                split_at_byte(records, b' ').ok_or_else(|| invalid("missing target type"))?;
+            let (name, rest) =
+                split_at_byte(rest, b'\0').ok_or_else(|| invalid("missing branch name"))?;
+            let (len, rest) =
+                split_at_byte(rest, b':').ok_or_else(|| invalid("missing target length"))?;
+            let len: usize = std::str::from_utf8(len)
+                .ok()
+                .and_then(|len| len.parse().ok())
+                .ok_or_else(|| invalid("bad target length"))?;
+            if rest.len() < len {
+                return Err(invalid("truncated target"));
+            }
+            let (target, rest) = rest.split_at(len);
+            let target_type =
+                std::str::from_utf8(target_type).map_err(|_| invalid("bad target type"))?;
+            let target =
+                BranchTarget::from_raw(target_type, Some(target).filter(|t| !t.is_empty()))?;
+            branches.push(Branch::new(name.into(), target));
+            records = rest;
+        }
+
+        let snp = Snapshot::new(branches)?;
+        if snp.manifest() != manifest {
+            return Err(invalid("not in canonical form"));
+        }
+        Ok(snp)
+    }
+
     pub fn branches(&self) -> &[Branch] {
         &self.branches
     }
 
+    /// Target of the branch named `name`, following aliases until a branch which is
+    /// not one, eg. to know which revision `HEAD` points to.
+    ///
+    /// Returns `None` if a branch in the chain does not exist, if an alias is
+    /// dangling, or if aliases form a cycle.
+    pub fn resolve_alias(&self, name: &[u8]) -> Option<&BranchTarget> {
+        let mut name = name;
+        // a chain longer than the number of branches goes through one of them twice
+        for _ in 0..=self.branches.len() {
+            let index = self
+                .branches
+                .binary_search_by(|branch| (*branch.name).cmp(name))
+                .ok()?;
+            match &self.branches[index].target {
+                BranchTarget::Alias(target) => name = target.as_deref()?,
+                target => return Some(target),
+            }
+        }
+        None
+    }
+
+    /// The exact bytes hashed (after the object header) to compute the snapshot
+    /// identifier.
+    pub fn manifest(&self) -> Vec<u8> {
+        snp_manifest_unchecked(&self.branches)
+    }
+
     /// Compute the SWHID v1.2 snapshot identifier for this snapshot.
     pub fn swhid(&self) -> Swhid {
-        let manifest = snp_manifest_unchecked(&self.branches);
+        let manifest = self.manifest();
         Swhid::new(
             ObjectType::Snapshot,
-            hash_swhid_object("snapshot", &manifest),
+            hash_swhid_object(ObjectType::Snapshot.git_object_type(), &manifest),
         )
     }
 }
 
+impl SwhidObject for Snapshot {
+    fn swhid(&self) -> Swhid {
+        Snapshot::swhid(self)
+    }
+
+    fn object_type(&self) -> ObjectType {
+        ObjectType::Snapshot
+    }
+}
+
+/// Serialized as the list of its branches
+#[cfg(feature = "serde")]
+impl serde::Serialize for Snapshot {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serde::Serialize::serialize(&self.branches, serializer)
+    }
+}
+
+/// Deserialized from a list of branches in any order, checked like in [`Snapshot::new`]
+#[cfg(feature = "serde")]
+impl<'de> serde::Deserialize<'de> for Snapshot {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        Snapshot::new(Vec::<Branch>::deserialize(deserializer)?).map_err(serde::de::Error::custom)
+    }
+}
+
 /// Compute the SWHID v1.2 snapshot manifest (concatenation of branches).
 ///
 /// This implements the SWHID v1.2 directory tree format, which is compatible
 /// with Git's tree format for directory objects.
 pub fn snp_manifest(mut branches: Vec<Branch>) -> Result<Vec<u8>, SnapshotError> {
-    sort_and_check_branches(&mut branches)?;
+    sort_branches(&mut branches)?;
     Ok(snp_manifest_unchecked(&branches))
 }
 
-fn sort_and_check_branches(branches: &mut [Branch]) -> Result<(), SnapshotError> {
+/// Sort snapshot branches in the SWHID v1.2 order, and check they are valid.
+///
+/// Branches are sorted by name. Returns an error if two branches have the same
+/// name, or if a name contains a `\0` byte.
+pub fn sort_branches(branches: &mut [Branch]) -> Result<(), SnapshotError> {
     branches.sort_unstable_by(|a, b| a.name.cmp(&b.name));
 
     check_unique(branches.iter().map(|branch| &branch.name))
@@ -96,19 +313,18 @@ fn sort_and_check_branches(branches: &mut [Branch]) -> Result<(), SnapshotError>
     Ok(())
 }
 
+/// Splits `bytes` around the first `byte`
+fn split_at_byte(bytes: &[u8], byte: u8) -> Option<(&[u8], &[u8])> {
+    let pos = bytes.iter().position(|&b| b == byte)?;
+    Some((&bytes[..pos], &bytes[pos + 1..]))
+}
+
 /// Same as [`snp_manifest`] but assumes children are already sorted and validated with
-/// [`sort_and_check_branches`]
-fn snp_manifest_unchecked(branches: &[Branch]) -> Vec<u8> {
+/// [`sort_branches`]
+pub(crate) fn snp_manifest_unchecked(branches: &[Branch]) -> Vec<u8> {
     let mut out = Vec::new();
     for branch in branches {
-        out.extend_from_slice(match branch.target {
-            BranchTarget::Content(_) => b"content",
-            BranchTarget::Directory(_) => b"directory",
-            BranchTarget::Revision(_) => b"revision",
-            BranchTarget::Release(_) => b"release",
-            BranchTarget::Snapshot(_) => b"snapshot",
-            BranchTarget::Alias(_) => b"alias",
-        });
+        out.extend_from_slice(branch.target.type_name().as_bytes());
         out.push(b' ');
         out.extend_from_slice(&branch.name);
         out.push(b'\0');
@@ -1,3 +1,10 @@
+//! Helpers shared by the SWHID object types, and path handling for qualifiers and
+//! permission manifests.
+
+use crate::authorship::Timestamp;
+use crate::error::{HeaderError, SwhidError};
+use crate::Bytestring;
+
 #[derive(Default)]
 pub(crate) struct HeaderWriter(Vec<u8>);
 
@@ -19,15 +26,12 @@ impl HeaderWriter {
         &mut self,
         key: impl AsRef<[u8]>,
         name: impl AsRef<[u8]>,
-        timestamp: i64,
-        timestamp_offset: impl AsRef<[u8]>,
+        timestamp: &Timestamp,
     ) {
         let mut value = Vec::new();
         value.extend_from_slice(name.as_ref());
         value.push(b' ');
-        value.extend_from_slice(timestamp.to_string().as_bytes());
-        value.push(b' ');
-        value.extend_from_slice(timestamp_offset.as_ref());
+        value.extend_from_slice(&timestamp.to_bytes());
         self.push(key, value);
     }
 
@@ -40,6 +44,102 @@ impl HeaderWriter {
     }
 }
 
+/// Checks extra headers can be written by [`HeaderWriter::push`] without being
+/// mistaken for other headers or continuation lines: keys must be non-empty and
+/// contain no space or newline.
+pub(crate) fn check_extra_headers(headers: &[(Bytestring, Bytestring)]) -> Result<(), HeaderError> {
+    match headers
+        .iter()
+        .find(|(key, _)| key.is_empty() || key.iter().any(|&byte| byte == b' ' || byte == b'\n'))
+    {
+        Some((key, _)) => Err(HeaderError::InvalidKey(key.clone())),
+        None => Ok(()),
+    }
+}
+
+/// Parses the headers of a revision or release manifest, which must not include the
+/// message, into key-value pairs. Continuation lines are joined to their value.
+pub(crate) fn parse_header(mut manifest: &[u8]) -> Result<Vec<(&[u8], Bytestring)>, SwhidError> {
+    let mut headers = Vec::new();
+    while !manifest.is_empty() {
+        // Pop first line
+        let Some(newline_position) = manifest.iter().position(|&byte| byte == b'\n') else {
+            return Err(SwhidError::InvalidFormat(
+                "Header line is missing a line end".to_owned(),
+            ));
+        };
+        let first_line = &manifest[..newline_position];
+        manifest = &manifest[newline_position + 1..];
+
+        // The first line is a key and a value. Extract the key and the first line of the value
+        let Some(delimiter_position) = first_line.iter().position(|&byte| byte == b' ') else {
+            return Err(SwhidError::InvalidFormat(
+                "Header line is missing a value".to_owned(),
+            ));
+        };
+        let key = &first_line[..delimiter_position];
+        if key.is_empty() {
+            return Err(SwhidError::InvalidFormat("Empty key".to_owned()));
+        };
+        let mut value = first_line[delimiter_position + 1..].to_vec();
+
+        // Read line by line until we find one that does not start
+        // with a space, which is the next key-value.
+        while let Some(newline_position) = manifest.iter().position(|&byte| byte == b'\n') {
+            let line = &manifest[..newline_position];
+            match line.split_first() {
+                None => {
+                    return Err(SwhidError::InvalidFormat("Empty line".to_owned()));
+                }
+                Some((b' ', value_line)) => {
+                    // continuation line
+                    value.push(b'\n');
+                    value.extend_from_slice(value_line);
+                }
+                Some(_) => {
+                    // new key-value pair
+                    break;
+                }
+            }
+            manifest = &manifest[newline_position + 1..];
+        }
+        headers.push((key, value.into_boxed_slice()));
+    }
+
+    Ok(headers)
+}
+
+/// Splits a revision or release manifest into its headers and message.
+///
+/// The message follows the first empty line, which cannot appear in headers as
+/// [`HeaderWriter::push`] indents every continuation line.
+pub(crate) fn split_manifest(manifest: &[u8]) -> (&[u8], Option<&[u8]>) {
+    if let Some(message) = manifest.strip_prefix(b"\n") {
+        return (b"", Some(message));
+    }
+    match manifest.windows(2).position(|pair| pair == b"\n\n") {
+        Some(position) => (&manifest[..position + 1], Some(&manifest[position + 2..])),
+        None => (manifest, None),
+    }
+}
+
+/// Parses the hexadecimal identifier of an object referenced by a header
+pub(crate) fn parse_object_id(value: &[u8]) -> Option<[u8; 20]> {
+    let mut id = [0; 20];
+    hex::decode_to_slice(value, &mut id).ok()?;
+    Some(id)
+}
+
+/// Splits the value of an authorship header into the name and the timestamp, or
+/// returns `None` if the value does not end with `<seconds> <offset>`.
+pub(crate) fn parse_authorship(value: &[u8]) -> Option<(Bytestring, Timestamp)> {
+    let mut parts = value.rsplitn(3, |&byte| byte == b' ');
+    let offset = parts.next()?;
+    let seconds = std::str::from_utf8(parts.next()?).ok()?.parse().ok()?;
+    let name = parts.next()?;
+    Some((name.into(), Timestamp::new(seconds, offset)))
+}
+
 /// Returns `Err(item)` if the `item` is present twice in a row.
 pub(crate) fn check_unique<T: AsRef<[u8]>>(items: impl IntoIterator<Item = T>) -> Result<(), T> {
     let mut items = items.into_iter();
@@ -56,3 +156,68 @@ pub(crate) fn check_unique<T: AsRef<[u8]>>(items: impl IntoIterator<Item = T>) -
 
     Ok(())
 }
+
+/// Normalizes a relative path, eg. from a permission manifest, to `/`-separated
+/// segments: backslashes are converted to slashes, and `.` and empty segments are
+/// removed.
+///
+/// Fails on absolute paths and on `..` segments, which could point outside of the
+/// directory the path is relative to, and on paths with no segment left.
+pub fn normalize_relative_path(path: &str) -> Result<String, SwhidError> {
+    let slashed = path.replace('\\', "/");
+    if slashed.starts_with('/') || (cfg!(windows) && slashed.contains(':')) {
+        return Err(SwhidError::InvalidFormat(format!("Absolute path: {path}")));
+    }
+    let mut segments = Vec::new();
+    for segment in slashed.split('/') {
+        match segment {
+            "" | "." => (),
+            ".." => return Err(SwhidError::InvalidFormat(format!("'..' in path: {path}"))),
+            segment => segments.push(segment),
+        }
+    }
+    if segments.is_empty() {
+        return Err(SwhidError::InvalidFormat(format!("Empty path: {path:?}")));
+    }
+    Ok(segments.join("/"))
+}
+
+/// Compares digests in constant time, so the time taken does not reveal how many
+/// leading bytes match.
+pub(crate) fn constant_time_eq(a: &[u8; 20], b: &[u8; 20]) -> bool {
+    let diff = a.iter().zip(b).fold(0u8, |diff, (x, y)| diff | (x ^ y));
+    std::hint::black_box(diff) == 0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn normalize_relative_path_valid() {
+        assert_eq!(normalize_relative_path("bin/tool").unwrap(), "bin/tool");
+        assert_eq!(
+            normalize_relative_path("src\\nested\\lib.rs").unwrap(),
+            "src/nested/lib.rs"
+        );
+        assert_eq!(normalize_relative_path("./a//b/./c/").unwrap(), "a/b/c");
+        // only `..` segments are rejected
+        assert_eq!(normalize_relative_path("a..b/c.").unwrap(), "a..b/c.");
+    }
+
+    #[test]
+    fn normalize_relative_path_invalid() {
+        for path in [
+            "../etc/passwd",
+            "a/../../b",
+            "a\\..\\b",
+            "..",
+            "/etc/passwd",
+            "\\a",
+            "",
+            "./",
+        ] {
+            assert!(normalize_relative_path(path).is_err(), "{path:?}");
+        }
+    }
+}
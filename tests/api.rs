@@ -0,0 +1,136 @@
+#![cfg(feature = "api")]
+
+use std::time::Duration;
+
+use serde_json::json;
+use swhid::api::{known_with_options, ApiClient, ApiOptions, RetryPolicy};
+use swhid::Swhid;
+use wiremock::matchers::{body_json, header, method, path};
+use wiremock::{Mock, MockServer, ResponseTemplate};
+
+const ARCHIVED: &str = "swh:1:cnt:8ab686eafeb1f44702738c8b0f24f2567c36da6d";
+const UNKNOWN: &str = "swh:1:dir:0000000000000000000000000000000000000000";
+
+fn options(server: &MockServer) -> ApiOptions {
+    ApiOptions {
+        base_url: format!("{}/api/1", server.uri()),
+        token: Some("secret".to_owned()),
+        retry: RetryPolicy {
+            max_retries: 2,
+            initial_backoff: Duration::from_millis(10),
+            ..Default::default()
+        },
+    }
+}
+
+/// Runs the blocking API client outside of the runtime of the mock server
+async fn known(opts: ApiOptions, swhids: &[&str]) -> Result<Vec<bool>, String> {
+    let swhids: Vec<Swhid> = swhids.iter().map(|s| s.parse().unwrap()).collect();
+    tokio::task::spawn_blocking(move || known_with_options(&swhids, &opts))
+        .await
+        .unwrap()
+        .map_err(|e| e.to_string())
+}
+
+fn known_response() -> ResponseTemplate {
+    ResponseTemplate::new(200).set_body_json(json!({
+        ARCHIVED: {"known": true},
+        UNKNOWN: {"known": false},
+    }))
+}
+
+#[tokio::test]
+async fn known_swhids() {
+    let server = MockServer::start().await;
+    Mock::given(method("POST"))
+        .and(path("/api/1/known/"))
+        .and(header("authorization", "Bearer secret"))
+        .and(body_json(json!([UNKNOWN, ARCHIVED])))
+        .respond_with(known_response())
+        .expect(1)
+        .mount(&server)
+        .await;
+
+    assert_eq!(
+        known(options(&server), &[UNKNOWN, ARCHIVED]).await.unwrap(),
+        [false, true]
+    );
+    // no request for no SWHIDs
+    assert_eq!(
+        known(options(&server), &[]).await.unwrap(),
+        Vec::<bool>::new()
+    );
+}
+
+#[tokio::test]
+async fn known_retries_rate_limited_requests() {
+    let server = MockServer::start().await;
+    Mock::given(path("/api/1/known/"))
+        .respond_with(ResponseTemplate::new(429).insert_header("Retry-After", "0"))
+        .up_to_n_times(1)
+        .expect(1)
+        .mount(&server)
+        .await;
+    Mock::given(path("/api/1/known/"))
+        .respond_with(known_response())
+        .expect(1)
+        .mount(&server)
+        .await;
+
+    let opts = options(&server);
+    let swhids = vec![ARCHIVED.parse::<Swhid>().unwrap()];
+    let known = tokio::task::spawn_blocking(move || ApiClient::new(opts).known(&swhids))
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(known, [true]);
+}
+
+#[tokio::test]
+async fn known_caps_retry_after() {
+    let server = MockServer::start().await;
+    Mock::given(path("/api/1/known/"))
+        .respond_with(ResponseTemplate::new(503).insert_header("Retry-After", "3600"))
+        .up_to_n_times(1)
+        .expect(1)
+        .mount(&server)
+        .await;
+    Mock::given(path("/api/1/known/"))
+        .respond_with(known_response())
+        .expect(1)
+        .mount(&server)
+        .await;
+
+    let opts = ApiOptions {
+        retry: RetryPolicy {
+            max_backoff: Duration::from_millis(10),
+            ..options(&server).retry
+        },
+        ..options(&server)
+    };
+    let start = std::time::Instant::now();
+    assert_eq!(known(opts, &[ARCHIVED]).await.unwrap(), [true]);
+    assert!(start.elapsed() < Duration::from_secs(60));
+}
+
+#[tokio::test]
+async fn known_errors() {
+    let server = MockServer::start().await;
+    // with backoff, as there is no Retry-After header
+    Mock::given(path("/api/1/known/"))
+        .and(body_json(json!([ARCHIVED])))
+        .respond_with(ResponseTemplate::new(429))
+        .expect(3)
+        .mount(&server)
+        .await;
+    Mock::given(path("/api/1/known/"))
+        .and(body_json(json!([UNKNOWN])))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({})))
+        .mount(&server)
+        .await;
+
+    let err = known(options(&server), &[ARCHIVED]).await.unwrap_err();
+    assert!(err.contains("429"));
+    let err = known(options(&server), &[UNKNOWN]).await.unwrap_err();
+    assert!(err.contains("No status for"));
+}
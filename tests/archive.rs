@@ -0,0 +1,139 @@
+#![cfg(feature = "archive")]
+
+use assert_cmd::Command;
+use swhid::archive::{tar_swhid, tar_swhid_with_options, ArchiveOptions};
+use swhid::{DirectorySink, DiskDirectoryBuilder, EntryPerms, ObjectType, Swhid};
+
+#[test]
+fn dir_from_tar_stdin() {
+    let tmp = assert_fs::TempDir::new().unwrap();
+    std::fs::write(tmp.path().join("a.txt"), "a").unwrap();
+    std::fs::create_dir_all(tmp.path().join("sub/empty")).unwrap();
+    std::fs::write(tmp.path().join("sub/b.txt"), "b").unwrap();
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        std::fs::write(tmp.path().join("run.sh"), "#!/bin/sh\n").unwrap();
+        std::fs::set_permissions(
+            tmp.path().join("run.sh"),
+            std::fs::Permissions::from_mode(0o755),
+        )
+        .unwrap();
+        std::os::unix::fs::symlink("sub/b.txt", tmp.path().join("link")).unwrap();
+    }
+
+    let mut builder = tar::Builder::new(Vec::new());
+    builder.follow_symlinks(false);
+    builder.append_dir_all(".", tmp.path()).unwrap();
+    let archive = builder.into_inner().unwrap();
+
+    let on_disk = DiskDirectoryBuilder::new(tmp.path()).swhid().unwrap();
+    assert_eq!(tar_swhid(archive.as_slice()).unwrap(), on_disk);
+    Command::cargo_bin("swhid")
+        .unwrap()
+        .args(["dir", "--from-tar", "-"])
+        .write_stdin(archive)
+        .assert()
+        .success()
+        .stdout(format!("{on_disk}\n"));
+}
+
+#[test]
+fn tar_links_and_duplicates() {
+    let mut builder = tar::Builder::new(Vec::new());
+    let mut append = |path: &str, entry_type: tar::EntryType, mode: u32, data: &[u8]| {
+        let mut header = tar::Header::new_gnu();
+        header.set_entry_type(entry_type);
+        header.set_mode(mode);
+        header.set_size(data.len() as u64);
+        if entry_type == tar::EntryType::Link {
+            header
+                .set_link_name(std::str::from_utf8(data).unwrap())
+                .unwrap();
+            header.set_size(0);
+            builder.append_data(&mut header, path, &[][..]).unwrap();
+        } else {
+            builder.append_data(&mut header, path, data).unwrap();
+        }
+    };
+    append("bin/tool", tar::EntryType::Regular, 0o755, b"old");
+    // replaces the previous entry, as when extracting
+    append("bin/tool", tar::EntryType::Regular, 0o755, b"new");
+    append("tool", tar::EntryType::Link, 0o644, b"bin/tool");
+    append("fifo", tar::EntryType::Fifo, 0o644, b"");
+    let archive = builder.into_inner().unwrap();
+
+    let tool = Swhid::new(ObjectType::Content, swhid::hash::hash_content(b"new"));
+    let mut expected = DirectorySink::new();
+    let executable = EntryPerms::File { executable: true };
+    expected.add_entry("bin/tool", &tool, executable).unwrap();
+    expected.add_entry("tool", &tool, executable).unwrap();
+    assert_eq!(
+        tar_swhid(archive.as_slice()).unwrap(),
+        expected.finalize().unwrap()
+    );
+
+    let mut builder = tar::Builder::new(Vec::new());
+    let mut header = tar::Header::new_gnu();
+    header.set_entry_type(tar::EntryType::Link);
+    header.set_link_name("missing").unwrap();
+    header.set_size(0);
+    builder.append_data(&mut header, "tool", &[][..]).unwrap();
+    assert!(tar_swhid(builder.into_inner().unwrap().as_slice()).is_err());
+}
+
+#[test]
+fn tar_size_limits() {
+    // a header claiming a 1 TiB file, without its contents
+    let mut header = tar::Header::new_gnu();
+    header.set_path("huge.bin").unwrap();
+    header.set_size(1 << 40);
+    header.set_mode(0o644);
+    header.set_cksum();
+    let mut bomb = header.as_bytes().to_vec();
+    bomb.extend_from_slice(&[0; 1024]);
+
+    let opts = ArchiveOptions {
+        max_entry_size: Some(1 << 30),
+        ..Default::default()
+    };
+    let err = tar_swhid_with_options(bomb.as_slice(), &opts).unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        "I/O error: huge.bin is 1099511627776 bytes long, over the maximum entry size"
+    );
+    // without limits, the missing contents are an error too, without reading them
+    // all in memory
+    assert!(tar_swhid(bomb.as_slice()).is_err());
+    Command::cargo_bin("swhid")
+        .unwrap()
+        .args(["dir", "--from-tar", "--max-file-size", "1000", "-"])
+        .write_stdin(bomb)
+        .assert()
+        .failure();
+
+    let mut builder = tar::Builder::new(Vec::new());
+    for path in ["a", "b"] {
+        let mut header = tar::Header::new_gnu();
+        header.set_size(10);
+        header.set_mode(0o644);
+        builder
+            .append_data(&mut header, path, &[0; 10][..])
+            .unwrap();
+    }
+    let archive = builder.into_inner().unwrap();
+    let opts = |max_entry_size, max_total_size| ArchiveOptions {
+        max_entry_size,
+        max_total_size,
+    };
+    let swhid = tar_swhid(archive.as_slice()).unwrap();
+    for ok in [opts(Some(10), Some(20)), opts(None, None)] {
+        assert_eq!(
+            tar_swhid_with_options(archive.as_slice(), &ok).unwrap(),
+            swhid
+        );
+    }
+    for too_small in [opts(Some(9), None), opts(None, Some(19))] {
+        assert!(tar_swhid_with_options(archive.as_slice(), &too_small).is_err());
+    }
+}
@@ -0,0 +1,458 @@
+use assert_cmd::Command;
+
+#[test]
+fn parse_stdin() {
+    Command::cargo_bin("swhid")
+        .unwrap()
+        .args(["parse", "--stdin"])
+        .write_stdin(
+            "swh:1:cnt:e69de29bb2d1d6434b8b29ae775ad8c2e48c5391\n\
+             swh:1:foo:e69de29bb2d1d6434b8b29ae775ad8c2e48c5391\n\
+             swh:1:dir:4b825dc642cb6eb9a060e54bf8d69288fbee4904;path=/src\n",
+        )
+        .assert()
+        .code(1)
+        .stdout(
+            "swh:1:cnt:e69de29bb2d1d6434b8b29ae775ad8c2e48c5391\n\
+             error: swh:1:foo:e69de29bb2d1d6434b8b29ae775ad8c2e48c5391: invalid object type: foo\n\
+             swh:1:dir:4b825dc642cb6eb9a060e54bf8d69288fbee4904;path=/src\n",
+        );
+}
+
+#[test]
+fn parse_stdin_all_valid() {
+    Command::cargo_bin("swhid")
+        .unwrap()
+        .args(["parse", "--stdin"])
+        .write_stdin("swh:1:cnt:e69de29bb2d1d6434b8b29ae775ad8c2e48c5391\n")
+        .assert()
+        .success()
+        .stdout("swh:1:cnt:e69de29bb2d1d6434b8b29ae775ad8c2e48c5391\n");
+}
+
+#[test]
+fn parse_canonical() {
+    let scrambled = "swh:1:cnt:e69de29bb2d1d6434b8b29ae775ad8c2e48c5391;\
+        foo=1;path=/src%2Fma%69n.rs;bar=2;origin=https://example.org/x;lines=1-2";
+    Command::cargo_bin("swhid")
+        .unwrap()
+        .args(["parse", "--canonical", scrambled])
+        .assert()
+        .success()
+        .stdout(
+            "swh:1:cnt:e69de29bb2d1d6434b8b29ae775ad8c2e48c5391;\
+             origin=https://example.org/x;path=/src/main.rs;lines=1-2;bar=2;foo=1\n",
+        );
+    // without --canonical, unknown qualifiers keep their order
+    Command::cargo_bin("swhid")
+        .unwrap()
+        .args(["parse", scrambled])
+        .assert()
+        .success()
+        .stdout(
+            "swh:1:cnt:e69de29bb2d1d6434b8b29ae775ad8c2e48c5391;\
+             origin=https://example.org/x;path=/src/main.rs;lines=1-2;foo=1;bar=2\n",
+        );
+}
+
+#[test]
+fn debug_manifest_dir() {
+    let tmp = assert_fs::TempDir::new().unwrap();
+    std::fs::write(tmp.path().join("a.txt"), "a").unwrap();
+
+    Command::cargo_bin("swhid")
+        .unwrap()
+        .args(["debug-manifest"])
+        .arg(tmp.path())
+        .assert()
+        .success()
+        .stdout(
+            "# object header: tree 33\n\
+             00000000  74 72 65 65 20 33 33 00                          |tree 33.|\n\
+             # entry: 100644 a.txt 2e65efe2a145dda7ee51d1741299f848e5bf752e\n\
+             00000008  31 30 30 36 34 34 20 61 2e 74 78 74 00 2e 65 ef  |100644 a.txt..e.|\n\
+             00000018  e2 a1 45 dd a7 ee 51 d1 74 12 99 f8 48 e5 bf 75  |..E...Q.t...H..u|\n\
+             00000028  2e                                               |.|\n",
+        );
+}
+
+#[test]
+fn dir_max_file_size() {
+    let tmp = assert_fs::TempDir::new().unwrap();
+    std::fs::write(tmp.path().join("a.txt"), "a").unwrap();
+    std::fs::write(tmp.path().join("large.bin"), [0; 100]).unwrap();
+
+    Command::cargo_bin("swhid")
+        .unwrap()
+        .args(["dir", "--max-file-size", "99"])
+        .arg(tmp.path())
+        .assert()
+        .failure();
+
+    // `git write-tree` of a directory with only a.txt
+    Command::cargo_bin("swhid")
+        .unwrap()
+        .args(["dir", "--max-file-size", "99", "--skip-oversized-files"])
+        .arg(tmp.path())
+        .assert()
+        .success()
+        .stdout("swh:1:dir:1a602d9bd07ce5272ddaa64e21da12dbca2b8c9f\n");
+}
+
+#[test]
+fn dir_exclude_from() {
+    let tmp = assert_fs::TempDir::new().unwrap();
+    std::fs::write(tmp.path().join("a.txt"), "a").unwrap();
+    std::fs::write(tmp.path().join("b.log"), "b").unwrap();
+    std::fs::write(tmp.path().join("c.tmp"), "c").unwrap();
+    let excludes = assert_fs::NamedTempFile::new("excludes").unwrap();
+    std::fs::write(excludes.path(), "# build outputs\n.log\n\n").unwrap();
+
+    // Combined with --exclude, leaving only a.txt as in `dir_max_file_size`
+    Command::cargo_bin("swhid")
+        .unwrap()
+        .args(["dir", "--exclude", ".tmp", "--exclude-from"])
+        .arg(excludes.path())
+        .arg(tmp.path())
+        .assert()
+        .success()
+        .stdout("swh:1:dir:1a602d9bd07ce5272ddaa64e21da12dbca2b8c9f\n");
+
+    Command::cargo_bin("swhid")
+        .unwrap()
+        .args(["dir", "--exclude-from"])
+        .arg(tmp.path().join("missing"))
+        .arg(tmp.path())
+        .assert()
+        .failure();
+}
+
+#[test]
+fn dir_include_vcs_dirs() {
+    let tmp = assert_fs::TempDir::new().unwrap();
+    std::fs::write(tmp.path().join("a.txt"), "a").unwrap();
+    std::fs::create_dir(tmp.path().join(".git")).unwrap();
+    std::fs::write(tmp.path().join(".git/HEAD"), "ref\n").unwrap();
+
+    // .git is left out, as in `dir_max_file_size`
+    Command::cargo_bin("swhid")
+        .unwrap()
+        .arg("dir")
+        .arg(tmp.path())
+        .assert()
+        .success()
+        .stdout("swh:1:dir:1a602d9bd07ce5272ddaa64e21da12dbca2b8c9f\n");
+
+    // `git mktree` of a.txt and a .git tree with HEAD
+    let with_git = "swh:1:dir:cd3b6de7e888b67a54f20cd22952ac21a0f40dec";
+    Command::cargo_bin("swhid")
+        .unwrap()
+        .args(["dir", "--include-vcs-dirs"])
+        .arg(tmp.path())
+        .assert()
+        .success()
+        .stdout(format!("{with_git}\n"));
+
+    Command::cargo_bin("swhid")
+        .unwrap()
+        .arg("verify")
+        .arg(tmp.path())
+        .arg(with_git)
+        .assert()
+        .failure();
+    Command::cargo_bin("swhid")
+        .unwrap()
+        .args(["verify", "--include-vcs-dirs"])
+        .arg(tmp.path())
+        .arg(with_git)
+        .assert()
+        .success();
+}
+
+/// Manifest of the revision in `simple_rev_hash` (tests/revision.rs)
+const SIMPLE_REV_MANIFEST: &str = "\
+    tree 0efb37b28c53c7e4fbd253bb04a4df14008f63fe\n\
+    author Test User <test@example.com> 1763027354 +0100\n\
+    committer Test User <test@example.com> 1763027354 +0100\n\
+    \n\
+    Test commit";
+
+#[test]
+fn rev_from_object() {
+    Command::cargo_bin("swhid")
+        .unwrap()
+        .arg("rev-from-object")
+        .write_stdin(SIMPLE_REV_MANIFEST)
+        .assert()
+        .success()
+        .stdout("swh:1:rev:07cde6575fb633ef9b5ecbe730e6eb97475a2fd9\n");
+
+    // headers out of order
+    Command::cargo_bin("swhid")
+        .unwrap()
+        .arg("rev-from-object")
+        .write_stdin(
+            SIMPLE_REV_MANIFEST
+                .replace("author", "x")
+                .replace("committer", "author"),
+        )
+        .assert()
+        .failure();
+}
+
+#[test]
+fn rel_from_object() {
+    // manifest of the release in `simple_rel_hash` (tests/release.rs)
+    Command::cargo_bin("swhid")
+        .unwrap()
+        .arg("rel-from-object")
+        .write_stdin(
+            "object 0efb37b28c53c7e4fbd253bb04a4df14008f63fe\n\
+             type tree\n\
+             tag v1.0\n\
+             tagger Test User <test@example.com> 1763027354 +0100\n\
+             \n\
+             Test tag",
+        )
+        .assert()
+        .success()
+        .stdout("swh:1:rel:46d326edb8bfc49b757ccd09930365595806bfc0\n");
+
+    Command::cargo_bin("swhid")
+        .unwrap()
+        .arg("rel-from-object")
+        .write_stdin(SIMPLE_REV_MANIFEST)
+        .assert()
+        .failure();
+}
+
+#[test]
+fn content_empty_stdin() {
+    Command::cargo_bin("swhid")
+        .unwrap()
+        .arg("content")
+        .write_stdin("")
+        .assert()
+        .success()
+        .stdout("swh:1:cnt:e69de29bb2d1d6434b8b29ae775ad8c2e48c5391\n")
+        .stderr("warning: stdin is empty, use --file to hash a file\n");
+
+    // an empty file is not worth a warning
+    let tmp = assert_fs::TempDir::new().unwrap();
+    std::fs::write(tmp.path().join("empty"), "").unwrap();
+    Command::cargo_bin("swhid")
+        .unwrap()
+        .args(["content", "--file"])
+        .arg(tmp.path().join("empty"))
+        .assert()
+        .success()
+        .stdout("swh:1:cnt:e69de29bb2d1d6434b8b29ae775ad8c2e48c5391\n")
+        .stderr("");
+}
+
+#[test]
+fn dir_stats() {
+    let tmp = assert_fs::TempDir::new().unwrap();
+    std::fs::write(tmp.path().join("a.txt"), "a").unwrap();
+    std::fs::create_dir(tmp.path().join("sub")).unwrap();
+    std::fs::write(tmp.path().join("sub/b.txt"), "bb").unwrap();
+
+    Command::cargo_bin("swhid")
+        .unwrap()
+        .args(["dir", "--stats"])
+        .arg(tmp.path())
+        .assert()
+        .success()
+        .stderr("2 files, 3 bytes, 0 symlinks, 1 directories\n");
+}
+
+#[test]
+fn verify_type_mismatch() {
+    let tmp = assert_fs::TempDir::new().unwrap();
+    let file = tmp.path().join("a.txt");
+    std::fs::write(&file, "a").unwrap();
+    let dir = tmp.path().to_path_buf();
+
+    let cases = [
+        (
+            &file,
+            "swh:1:dir:4b825dc642cb6eb9a060e54bf8d69288fbee4904",
+            "a directory",
+            "file",
+        ),
+        (
+            &file,
+            "swh:1:rev:0000000000000000000000000000000000000000",
+            "a revision",
+            "file",
+        ),
+        (
+            &file,
+            "swh:1:rel:0000000000000000000000000000000000000000",
+            "a release",
+            "file",
+        ),
+        (
+            &dir,
+            "swh:1:cnt:2e65efe2a145dda7ee51d1741299f848e5bf752e",
+            "a content",
+            "directory",
+        ),
+        (
+            &dir,
+            "swh:1:snp:0000000000000000000000000000000000000000",
+            "a snapshot",
+            "directory",
+        ),
+    ];
+    for (path, swhid, expected, kind) in cases {
+        Command::cargo_bin("swhid")
+            .unwrap()
+            .arg("verify")
+            .arg(path)
+            .arg(swhid)
+            .assert()
+            .code(1)
+            .stdout("")
+            .stderr(format!(
+                "Error: expected {expected} SWHID but {} is a {kind}\n",
+                path.display()
+            ));
+    }
+
+    Command::cargo_bin("swhid")
+        .unwrap()
+        .arg("verify")
+        .arg(&file)
+        .arg("swh:1:cnt:2e65efe2a145dda7ee51d1741299f848e5bf752e")
+        .assert()
+        .success();
+}
+
+#[test]
+fn compare() {
+    let a = assert_fs::TempDir::new().unwrap();
+    let b = assert_fs::TempDir::new().unwrap();
+    for root in [&a, &b] {
+        std::fs::create_dir_all(root.path().join("src/nested")).unwrap();
+        std::fs::write(root.path().join("README"), "readme").unwrap();
+        std::fs::write(root.path().join("src/lib.rs"), "lib").unwrap();
+        std::fs::write(root.path().join("src/nested/a.rs"), "a").unwrap();
+    }
+
+    Command::cargo_bin("swhid")
+        .unwrap()
+        .arg("compare")
+        .arg(a.path())
+        .arg(b.path())
+        .assert()
+        .success();
+
+    std::fs::write(b.path().join("src/nested/a.rs"), "changed").unwrap();
+    let output = Command::cargo_bin("swhid")
+        .unwrap()
+        .arg("compare")
+        .arg(a.path())
+        .arg(b.path())
+        .assert()
+        .code(1)
+        .get_output()
+        .stdout
+        .clone();
+    let output = String::from_utf8(output).unwrap();
+    assert!(
+        output.ends_with("  First difference: src/nested/a.rs: contents differ\n"),
+        "{output}"
+    );
+
+    std::fs::remove_file(b.path().join("src/nested/a.rs")).unwrap();
+    std::fs::write(b.path().join("src/nested/b.rs"), "b").unwrap();
+    let output = Command::cargo_bin("swhid")
+        .unwrap()
+        .arg("compare")
+        .arg(a.path())
+        .arg(b.path())
+        .assert()
+        .code(1)
+        .get_output()
+        .stdout
+        .clone();
+    let output = String::from_utf8(output).unwrap();
+    assert!(
+        output.ends_with("  First difference: src/nested/a.rs: only in the first directory\n"),
+        "{output}"
+    );
+}
+
+#[cfg(unix)]
+#[test]
+fn compare_non_utf8_names() {
+    use std::ffi::OsStr;
+    use std::os::unix::ffi::OsStrExt;
+
+    let a = assert_fs::TempDir::new().unwrap();
+    let b = assert_fs::TempDir::new().unwrap();
+    let dir_name = OsStr::from_bytes(b"caf\xe9");
+    for (root, content) in [(&a, "a"), (&b, "b")] {
+        std::fs::create_dir(root.path().join(dir_name)).unwrap();
+        std::fs::write(root.path().join(dir_name).join("a.rs"), content).unwrap();
+    }
+
+    let output = Command::cargo_bin("swhid")
+        .unwrap()
+        .arg("compare")
+        .arg(a.path())
+        .arg(b.path())
+        .assert()
+        .code(1)
+        .get_output()
+        .stdout
+        .clone();
+    let output = String::from_utf8(output).unwrap();
+    assert!(
+        output.ends_with("  First difference: caf\u{fffd}/a.rs: contents differ\n"),
+        "{output}"
+    );
+}
+
+#[cfg(feature = "git")]
+#[test]
+fn git_identify_all() {
+    let tmp = assert_fs::TempDir::new().unwrap();
+    let repo = git2::Repository::init(tmp.path()).unwrap();
+    std::fs::write(tmp.path().join("test.txt"), "test content").unwrap();
+    let mut index = repo.index().unwrap();
+    index.add_path(std::path::Path::new("test.txt")).unwrap();
+    let tree = repo.find_tree(index.write_tree().unwrap()).unwrap();
+    let sig = git2::Signature::new(
+        "Test User",
+        "test@example.com",
+        &git2::Time::new(1763027354, 60),
+    )
+    .unwrap();
+    let commit_oid = repo
+        .commit(Some("HEAD"), &sig, &sig, "Test commit", &tree, &[])
+        .unwrap();
+
+    let output = Command::cargo_bin("swhid")
+        .unwrap()
+        .args(["git", "identify-all"])
+        .arg(tmp.path())
+        .assert()
+        .success()
+        .get_output()
+        .stdout
+        .clone();
+    let output = String::from_utf8(output).unwrap();
+    let lines: Vec<_> = output.lines().collect();
+    assert_eq!(lines.len(), 3);
+    assert_eq!(lines[0], format!("revision: swh:1:rev:{commit_oid}"));
+    // the tree of `simple_rev_hash` (tests/revision.rs)
+    assert_eq!(
+        lines[1],
+        "directory: swh:1:dir:0efb37b28c53c7e4fbd253bb04a4df14008f63fe"
+    );
+    let snapshot = lines[2].strip_prefix("snapshot: ").unwrap();
+    let snapshot: swhid::Swhid = snapshot.parse().unwrap();
+    assert_eq!(snapshot.object_type(), swhid::ObjectType::Snapshot);
+}
@@ -54,6 +54,8 @@ fn content_swhid_empty() {
         swhid.to_string(),
         "swh:1:cnt:e69de29bb2d1d6434b8b29ae775ad8c2e48c5391"
     );
+    assert_eq!(Content::empty(), swhid);
+    assert_eq!(Content::empty(), Content::from_bytes(Vec::new()).swhid());
 }
 
 #[test]
@@ -67,6 +69,16 @@ fn content_swhid_hello_world() {
     );
 }
 
+#[test]
+fn content_swhid_hex() {
+    let content = Content::from_bytes(b"Hello, World!");
+    assert_eq!(
+        content.swhid_hex(),
+        "b45ef6fec89518d314f546fd6c3025367b721684"
+    );
+    assert_eq!(content.swhid().short(7), "swh:1:cnt:b45ef6f");
+}
+
 #[test]
 fn content_unicode() {
     let unicode_data = "Hello, 世界! 🌍";
@@ -184,3 +196,27 @@ fn content_swhid_hash_consistency() {
     let swhid2 = content.swhid();
     assert_eq!(swhid1, swhid2);
 }
+
+#[test]
+fn content_swhid_with_header() {
+    let content = Content::from_bytes(b"Hello, World!");
+    assert_eq!(content.swhid_with_header("blob"), content.swhid());
+
+    let swhid = content.swhid_with_header("file");
+    assert_ne!(swhid, content.swhid());
+    assert_eq!(swhid.object_type(), ObjectType::Content);
+}
+
+#[test]
+fn content_verify() {
+    let content = Content::from_bytes(b"Hello, World!");
+    let swhid: Swhid = "swh:1:cnt:b45ef6fec89518d314f546fd6c3025367b721684"
+        .parse()
+        .unwrap();
+    assert!(content.verify(&swhid));
+
+    assert!(!Content::from_bytes(b"Hello, World?").verify(&swhid));
+
+    let dir_swhid = Swhid::new(ObjectType::Directory, *swhid.digest_bytes());
+    assert!(!content.verify(&dir_swhid));
+}
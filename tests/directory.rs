@@ -1,3 +1,6 @@
+use std::collections::HashSet;
+use std::path::PathBuf;
+
 use assert_fs::prelude::*;
 
 use swhid::directory::*;
@@ -28,11 +31,85 @@ fn simple_dir_hash() {
 
     // Checked against the implementation in https://archive.softwareheritage.org/swh:1:dir:60e683f48069373ee85227f2d7ab2eb1a8873ddb;origin=https://gitlab.softwareheritage.org/swh/devel/swh-model.git;visit=swh:1:snp:291aefbdccd43abac57629431201c2fd55284df7;anchor=swh:1:rev:9e54500902fc00ab1e6400431e2803b9bb41cc0a
     assert_eq!(
-        dir.swhid().unwrap().to_string(),
+        dir.swhid().to_string(),
         "swh:1:dir:8863dfedee16d4f5eae8c796f57b90b165e5bd8d"
     );
 }
 
+#[test]
+fn manifest_matches_dir_manifest() {
+    // unsorted, with a directory sorted as if its name ended with `/`
+    let entries = vec![
+        Entry::new(name("b.txt"), 0o100755, [2; 20]),
+        Entry::new(name("a.txt"), 0o100644, [1; 20]),
+        Entry::new(name("a"), 0o040000, [3; 20]),
+        Entry::new(name("a-b"), 0o120000, [4; 20]),
+    ];
+    let dir = Directory::new(entries.clone()).unwrap();
+
+    let manifest = dir.manifest();
+    assert_eq!(manifest, dir_manifest(entries).unwrap());
+    assert_eq!(
+        dir.swhid().digest_bytes(),
+        &swhid::hash::hash_swhid_object("tree", &manifest)
+    );
+}
+
+#[test]
+fn long_entry_names() {
+    // Git trees have no limit on entry names, unlike most filesystems
+    let file_name = [&[b'a'; 4095][..], b"b"].concat();
+    let dir_name = [&[b'a'; 4095][..], b"c"].concat();
+    let entries = vec![
+        Entry::new(
+            dir_name.clone().into(),
+            0o040000,
+            hex::decode("4b825dc642cb6eb9a060e54bf8d69288fbee4904")
+                .unwrap()
+                .try_into()
+                .unwrap(),
+        ),
+        Entry::new(file_name.clone().into(), 0o100644, hash_content(b"")),
+    ];
+    let dir = Directory::new(entries.clone()).unwrap();
+    assert_eq!(dir.entries()[0].name(), &file_name[..]);
+
+    let manifest = dir.manifest();
+    assert_eq!(manifest.len(), (7 + 4096 + 1 + 20) + (6 + 4096 + 1 + 20));
+    assert!(manifest.starts_with(&[&b"100644 "[..], &file_name, b"\0"].concat()));
+    // Checked against `git mktree`
+    assert_eq!(
+        dir.swhid().to_string(),
+        "swh:1:dir:b163f438fbbd793d5edec03f3cf609ec4ccdd3a2"
+    );
+
+    let mut duplicate = entries;
+    duplicate.push(Entry::new(file_name.into(), 0o100755, [1; 20]));
+    assert!(Directory::new(duplicate).is_err());
+}
+
+#[test]
+fn entry_order() {
+    let dir = Entry::new(name("foo"), 0o040000, [1; 20]);
+    let file = Entry::new(name("foo.txt"), 0o100644, [2; 20]);
+    let submodule = Entry::new(name("foo"), 0o160000, [3; 20]);
+
+    // names alone would put the directory first
+    assert!(dir.name() < file.name());
+    assert_eq!(dir.swhid_cmp(&file), std::cmp::Ordering::Greater);
+    // submodules sort as files
+    assert_eq!(submodule.swhid_cmp(&file), std::cmp::Ordering::Less);
+
+    let mut entries = vec![dir.clone(), file.clone(), submodule.clone()];
+    entries.sort();
+    assert_eq!(entries, [submodule, file.clone(), dir.clone()]);
+
+    // sorting gives the order of the manifest
+    let mut entries = vec![dir, file];
+    entries.sort();
+    assert_eq!(Directory::new(entries.clone()).unwrap().entries(), entries);
+}
+
 #[test]
 fn dir_order() {
     let dir = Directory::new(vec![
@@ -53,11 +130,214 @@ fn dir_order() {
 
     // Checked against the implementation in https://archive.softwareheritage.org/swh:1:dir:60e683f48069373ee85227f2d7ab2eb1a8873ddb;origin=https://gitlab.softwareheritage.org/swh/devel/swh-model.git;visit=swh:1:snp:291aefbdccd43abac57629431201c2fd55284df7;anchor=swh:1:rev:9e54500902fc00ab1e6400431e2803b9bb41cc0a
     assert_eq!(
-        dir.swhid().unwrap().to_string(),
+        dir.swhid().to_string(),
         "swh:1:dir:8863dfedee16d4f5eae8c796f57b90b165e5bd8d"
     );
 }
 
+#[test]
+fn sort_entries_matches_dir_order() {
+    let mut entries = vec![
+        Entry::new(name("a.txt"), 0o100644, [1; 20]),
+        Entry::new(name("c.txt"), 0o100644, [0; 20]),
+        Entry::new(name("b.txt"), 0o100755, [2; 20]),
+    ];
+    let dir = Directory::new(entries.clone()).unwrap();
+
+    sort_entries(&mut entries).unwrap();
+    assert_eq!(entries, dir.entries());
+}
+
+#[test]
+fn sort_entries_many_entries_order() {
+    // Same entries as dir_order, with many more files and directories around them
+    let mut entries = vec![
+        Entry::new(name("a.txt"), 0o100644, [1; 20]),
+        Entry::new(name("c.txt"), 0o100644, [0; 20]),
+        Entry::new(name("b.txt"), 0o100755, [2; 20]),
+    ];
+    for i in (0..1000).rev() {
+        let mode = if i % 2 == 0 { 0o040000 } else { 0o100644 };
+        entries.push(Entry::new(
+            format!("b{i}").into_bytes().into(),
+            mode,
+            [3; 20],
+        ));
+    }
+    let dir = Directory::new(entries.clone()).unwrap();
+    assert_eq!(dir.manifest(), dir_manifest(entries.clone()).unwrap());
+    sort_entries(&mut entries).unwrap();
+    assert_eq!(entries, dir.entries());
+
+    let position = |n: &str| entries.iter().position(|e| e.name() == n.as_bytes());
+    assert!(position("a.txt") < position("b.txt"));
+    assert!(position("b.txt") < position("c.txt"));
+}
+
+#[test]
+fn invalid_entry_names() {
+    for invalid_name in ["", ".", ".."] {
+        let entries = vec![
+            Entry::new(name("a.txt"), 0o100644, [1; 20]),
+            Entry::new(name(invalid_name), 0o040000, [2; 20]),
+        ];
+        assert!(
+            matches!(
+                Directory::new(entries),
+                Err(swhid::error::DirectoryError::InvalidEntryName(ref n)) if **n == *invalid_name.as_bytes()
+            ),
+            "{invalid_name:?}"
+        );
+    }
+
+    // Only the exact special names are invalid
+    Directory::new(vec![
+        Entry::new(name("..."), 0o100644, [1; 20]),
+        Entry::new(name(".a"), 0o100644, [1; 20]),
+    ])
+    .unwrap();
+}
+
+#[test]
+fn sort_entries_directories_trailing_slash() {
+    // "a" is a directory so it sorts as "a/", which is after "a.txt"
+    let mut entries = vec![
+        Entry::new(name("a"), 0o040000, [1; 20]),
+        Entry::new(name("a.txt"), 0o100644, [2; 20]),
+        Entry::new(name("a-"), 0o100644, [3; 20]),
+    ];
+    sort_entries(&mut entries).unwrap();
+    assert_eq!(
+        entries,
+        vec![
+            Entry::new(name("a-"), 0o100644, [3; 20]),
+            Entry::new(name("a.txt"), 0o100644, [2; 20]),
+            Entry::new(name("a"), 0o040000, [1; 20]),
+        ]
+    );
+}
+
+#[test]
+fn sort_entries_submodules_no_trailing_slash() {
+    // 0o160000 has the directory bit set, but submodules sort as files like in Git
+    let mut entries = vec![
+        Entry::new(name("foo.c"), 0o100644, hash_content(b"a")),
+        Entry::new(name("foo"), 0o160000, [1; 20]),
+    ];
+    sort_entries(&mut entries).unwrap();
+    assert_eq!(entries[0].name(), b"foo");
+    assert_eq!(entries[1].name(), b"foo.c");
+
+    // `git mktree` of the same entries
+    assert_eq!(
+        Directory::new(entries).unwrap().swhid().to_string(),
+        "swh:1:dir:c082d3abf8a794e29edf166468d5b9b3fdacdeff"
+    );
+}
+
+#[test]
+fn sort_entries_file_and_directory_same_name() {
+    // Git forbids a tree with both a `foo` blob and a `foo` tree
+    let mut entries = vec![
+        Entry::new(name("foo"), 0o100644, [1; 20]),
+        Entry::new(name("foo"), 0o040000, [2; 20]),
+    ];
+    assert!(matches!(
+        sort_entries(&mut entries),
+        Err(swhid::error::DirectoryError::DuplicateEntryName(name)) if &*name == b"foo"
+    ));
+
+    // Names sorting between `foo` and `foo/` do not hide the duplicate
+    let mut entries = vec![
+        Entry::new(name("foo"), 0o040000, [2; 20]),
+        Entry::new(name("foo.c"), 0o100644, [3; 20]),
+        Entry::new(name("foo-bar"), 0o040000, [4; 20]),
+        Entry::new(name("foo"), 0o100755, [1; 20]),
+        Entry::new(name("fo"), 0o100644, [5; 20]),
+    ];
+    assert!(matches!(
+        sort_entries(&mut entries),
+        Err(swhid::error::DirectoryError::DuplicateEntryName(name)) if &*name == b"foo"
+    ));
+
+    // Submodules sort as files
+    let mut entries = vec![
+        Entry::new(name("foo"), 0o160000, [1; 20]),
+        Entry::new(name("foo.c"), 0o100644, [3; 20]),
+        Entry::new(name("foo"), 0o040000, [2; 20]),
+    ];
+    assert!(sort_entries(&mut entries).is_err());
+
+    // Prefixes are fine
+    let mut entries = vec![
+        Entry::new(name("foo"), 0o100644, [1; 20]),
+        Entry::new(name("foo.c"), 0o100644, [3; 20]),
+        Entry::new(name("foobar"), 0o040000, [2; 20]),
+        Entry::new(name("fo"), 0o040000, [2; 20]),
+    ];
+    sort_entries(&mut entries).unwrap();
+}
+
+#[test]
+fn entry_modes() {
+    for mode in [0o100644, 0o100755, 0o040000, 0o120000, 0o160000] {
+        let entry = Entry::try_new(name("a"), mode, [1; 20]).unwrap();
+        assert_eq!(entry, Entry::new(name("a"), mode, [1; 20]));
+    }
+    assert!(matches!(
+        Entry::try_new(name("a"), 0o777, [1; 20]),
+        Err(swhid::error::DirectoryError::InvalidMode { mode: 0o777, .. })
+    ));
+    assert!(Entry::try_new(name("a"), 0o100664, [1; 20]).is_err());
+
+    // non-canonical modes of old Git trees are still hashed verbatim
+    let dir = Directory::new(vec![Entry::new(name("a"), 0o100664, [1; 20])]).unwrap();
+    assert!(dir.manifest().starts_with(b"100664 a\0"));
+}
+
+#[test]
+fn sort_entries_errors() {
+    let mut entries = vec![
+        Entry::new(name("a.txt"), 0o100644, [1; 20]),
+        Entry::new(name("a.txt"), 0o100755, [2; 20]),
+    ];
+    assert!(matches!(
+        sort_entries(&mut entries),
+        Err(swhid::error::DirectoryError::DuplicateEntryName(_))
+    ));
+
+    let mut entries = vec![Entry::new(name("a/b.txt"), 0o100644, [1; 20])];
+    assert!(matches!(
+        sort_entries(&mut entries),
+        Err(swhid::error::DirectoryError::InvalidByteInName { byte: b'/', .. })
+    ));
+    assert_eq!(
+        sort_entries(&mut entries).unwrap_err().to_string(),
+        "Invalid byte '/' in name: a/b.txt"
+    );
+}
+
+#[test]
+fn same_contents_ignores_order() {
+    let a = vec![
+        Entry::new(name("a.txt"), 0o100644, [1; 20]),
+        Entry::new(name("b"), 0o040000, [2; 20]),
+        Entry::new(name("c.txt"), 0o100755, [0; 20]),
+    ];
+    let mut b = a.clone();
+    b.reverse();
+    assert!(Directory::same_contents(&a, &b));
+    assert!(Directory::same_contents(&[], &[]));
+
+    // Different mode
+    let mut c = a.clone();
+    c[2] = Entry::new(name("c.txt"), 0o100644, [0; 20]);
+    assert!(!Directory::same_contents(&a, &c));
+
+    // Missing entry
+    assert!(!Directory::same_contents(&a, &b[1..]));
+}
+
 #[test]
 fn empty_dir_hash() {
     let dir = Directory::new(vec![]).unwrap();
@@ -66,7 +346,7 @@ fn empty_dir_hash() {
 
     // Checked against the implementation in https://archive.softwareheritage.org/swh:1:dir:60e683f48069373ee85227f2d7ab2eb1a8873ddb;origin=https://gitlab.softwareheritage.org/swh/devel/swh-model.git;visit=swh:1:snp:291aefbdccd43abac57629431201c2fd55284df7;anchor=swh:1:rev:9e54500902fc00ab1e6400431e2803b9bb41cc0a
     assert_eq!(
-        dir.swhid().unwrap().to_string(),
+        dir.swhid().to_string(),
         "swh:1:dir:4b825dc642cb6eb9a060e54bf8d69288fbee4904"
     );
 }
@@ -89,7 +369,7 @@ fn dir_with_symlinks() {
 
     // Checked against the implementation in https://archive.softwareheritage.org/swh:1:dir:60e683f48069373ee85227f2d7ab2eb1a8873ddb;origin=https://gitlab.softwareheritage.org/swh/devel/swh-model.git;visit=swh:1:snp:291aefbdccd43abac57629431201c2fd55284df7;anchor=swh:1:rev:9e54500902fc00ab1e6400431e2803b9bb41cc0a
     assert_eq!(
-        dir.swhid().unwrap().to_string(),
+        dir.swhid().to_string(),
         "swh:1:dir:277f7807173d7053469ccbab70958b3bc9d5c9f6"
     );
 }
@@ -113,7 +393,7 @@ fn dir_with_subdir() {
 
     // ditto
     assert_eq!(
-        dir.swhid().unwrap().to_string(),
+        dir.swhid().to_string(),
         "swh:1:dir:c890b32febf94c3163b67778ae8b26bb631610a3",
     );
 }
@@ -183,6 +463,45 @@ fn read_nested_dir_structure() {
     assert_eq!(id.object_type(), ObjectType::Directory);
 }
 
+#[test]
+fn dir_stats() {
+    let tmp = assert_fs::TempDir::new().unwrap();
+    tmp.child("file1.txt").write_str("content1").unwrap();
+    tmp.child("subdir/file2.txt").write_str("content2").unwrap();
+    tmp.child("subdir/file3.txt").write_str("content3").unwrap();
+    tmp.child("subdir/nested").create_dir_all().unwrap();
+    tmp.child("subdir/nested/link.txt")
+        .symlink_to_file("../file2.txt")
+        .unwrap();
+    tmp.child("empty").create_dir_all().unwrap();
+
+    let builder = DiskDirectoryBuilder::new(tmp.path());
+    let (swhid, stats) = builder.swhid_with_stats().unwrap();
+    assert_eq!(swhid, builder.swhid().unwrap());
+    assert_eq!(
+        stats,
+        DirStats {
+            files: 3,
+            bytes: 24,
+            symlinks: 1,
+            directories: 2,
+            submodules: 0,
+        }
+    );
+    assert_eq!(builder.stats().unwrap(), stats);
+
+    // excluded files are not counted
+    let opts = WalkOptions {
+        exclude_suffixes: vec!["3.txt".to_owned()],
+        ..Default::default()
+    };
+    let stats = DiskDirectoryBuilder::new(tmp.path())
+        .with_options(opts)
+        .stats()
+        .unwrap();
+    assert_eq!((stats.files, stats.bytes), (2, 16));
+}
+
 #[test]
 fn read_dir_with_symlinks() {
     let tmp = assert_fs::TempDir::new().unwrap();
@@ -204,7 +523,42 @@ fn read_dir_with_symlinks() {
     );
 }
 
+#[cfg(unix)]
+#[test]
+fn read_dir_with_unusual_symlink_targets() {
+    use std::ffi::OsStr;
+    use std::os::unix::ffi::OsStrExt;
+
+    let tmp = assert_fs::TempDir::new().unwrap();
+    // dangling targets with spaces, non-ASCII characters and bytes which are not
+    // UTF-8, which are hashed as is
+    let targets: [&[u8]; 3] = [
+        b"my file.txt",
+        "caf\u{e9}/\u{1f980}".as_bytes(),
+        b"\xff\xfe x",
+    ];
+    for (i, target) in targets.iter().enumerate() {
+        std::os::unix::fs::symlink(OsStr::from_bytes(target), tmp.path().join(format!("{i}")))
+            .unwrap();
+    }
+
+    let dir = DiskDirectoryBuilder::new(tmp.path()).build().unwrap();
+    let expected: Vec<_> = targets
+        .iter()
+        .enumerate()
+        .map(|(i, target)| {
+            Entry::new(
+                format!("{i}").into_bytes().into(),
+                0o120000,
+                hash_content(target),
+            )
+        })
+        .collect();
+    assert_eq!(dir.entries(), expected);
+}
+
 #[test]
+#[allow(clippy::field_reassign_with_default)]
 fn read_dir_with_followed_symlinks() {
     let tmp = assert_fs::TempDir::new().unwrap();
     tmp.child("target.txt").write_str("target content").unwrap();
@@ -231,6 +585,112 @@ fn read_dir_with_followed_symlinks() {
     );
 }
 
+#[test]
+fn followed_symlinks_to_dirs() {
+    let tmp = assert_fs::TempDir::new().unwrap();
+    tmp.child("sub/file.txt").write_str("content").unwrap();
+    tmp.child("linked").symlink_to_dir("sub").unwrap();
+
+    // walked like a copy of the directory
+    let opts = WalkOptions {
+        follow_symlinks: true,
+        ..Default::default()
+    };
+    assert!(!opts.reject_symlinked_dirs);
+    let dir = DiskDirectoryBuilder::new(tmp.path())
+        .with_options(opts.clone())
+        .build()
+        .unwrap();
+    let sub = DiskDirectoryBuilder::new(tmp.child("sub").path())
+        .swhid()
+        .unwrap();
+    assert_eq!(
+        dir.entries(),
+        vec![
+            Entry::new(name("linked"), 0o040000, *sub.digest_bytes()),
+            Entry::new(name("sub"), 0o040000, *sub.digest_bytes()),
+        ]
+    );
+
+    let opts = WalkOptions {
+        reject_symlinked_dirs: true,
+        ..opts
+    };
+    let builder = DiskDirectoryBuilder::new(tmp.path()).with_options(opts.clone());
+    assert!(builder.swhid().is_err());
+    assert!(builder.build().is_err());
+
+    // links to files are still followed
+    std::fs::remove_file(tmp.child("linked").path()).unwrap();
+    tmp.child("linked.txt")
+        .symlink_to_file("sub/file.txt")
+        .unwrap();
+    let dir = DiskDirectoryBuilder::new(tmp.path())
+        .with_options(opts)
+        .build()
+        .unwrap();
+    assert_eq!(
+        dir.entries()[0],
+        Entry::new(name("linked.txt"), 0o100644, hash_content(b"content"))
+    );
+}
+
+#[test]
+fn followed_symlinks_confined_to_root() {
+    let outside = assert_fs::TempDir::new().unwrap();
+    outside.child("secret.txt").write_str("secret").unwrap();
+
+    let tmp = assert_fs::TempDir::new().unwrap();
+    tmp.child("sub/target.txt")
+        .write_str("target content")
+        .unwrap();
+    // relative link staying under the root
+    tmp.child("sub/nested").create_dir_all().unwrap();
+    tmp.child("sub/nested/link.txt")
+        .symlink_to_file("../target.txt")
+        .unwrap();
+
+    let opts = WalkOptions {
+        follow_symlinks: true,
+        ..Default::default()
+    };
+    assert!(opts.confine_symlinks_to_root);
+    let in_root = DiskDirectoryBuilder::new(tmp.path())
+        .with_options(opts.clone())
+        .build()
+        .unwrap();
+    assert_eq!(in_root.entries().len(), 1);
+
+    tmp.child("escaping.txt")
+        .symlink_to_file(outside.child("secret.txt").path())
+        .unwrap();
+    let builder = DiskDirectoryBuilder::new(tmp.path()).with_options(opts.clone());
+    assert!(builder.swhid().is_err());
+    assert!(builder.build().is_err());
+    let hardlinks = WalkOptions {
+        detect_hardlinks: true,
+        ..opts.clone()
+    };
+    assert!(DiskDirectoryBuilder::new(tmp.path())
+        .with_options(hardlinks)
+        .build_with_hardlinks()
+        .is_err());
+
+    let opts = WalkOptions {
+        confine_symlinks_to_root: false,
+        ..opts
+    };
+    let dir = DiskDirectoryBuilder::new(tmp.path())
+        .with_options(opts)
+        .build()
+        .unwrap();
+    assert!(dir.entries().contains(&Entry::new(
+        name("escaping.txt"),
+        0o100644,
+        hash_content(b"secret")
+    )));
+}
+
 #[test]
 fn read_dir_with_exclude_patterns() {
     let tmp = assert_fs::TempDir::new().unwrap();
@@ -260,10 +720,12 @@ fn read_dir_with_exclude_patterns() {
 fn dir_walk_options_default() {
     let opts = WalkOptions::default();
     assert!(!opts.follow_symlinks);
+    assert!(opts.confine_symlinks_to_root);
     assert!(opts.exclude_suffixes.is_empty());
 }
 
 #[test]
+#[allow(clippy::field_reassign_with_default)]
 fn dir_walk_options_custom() {
     let mut opts = WalkOptions::default();
     opts.follow_symlinks = true;
@@ -298,8 +760,8 @@ fn executable_bit_changes_directory_id() {
     .unwrap();
 
     // Directory IDs must differ
-    let swhid1 = dir1.swhid().unwrap();
-    let swhid2 = dir2.swhid().unwrap();
+    let swhid1 = dir1.swhid();
+    let swhid2 = dir2.swhid();
     assert_ne!(swhid1, swhid2, "Executable bit should change directory ID");
 }
 
@@ -426,3 +888,757 @@ executable = false
         swhid::permissions::EntryExec::Unknown
     );
 }
+
+#[test]
+fn permission_manifest_walk() {
+    let tmp = assert_fs::TempDir::new().unwrap();
+    tmp.child("bin/tool").write_str("#!/bin/sh\n").unwrap();
+    tmp.child("README").write_str("readme").unwrap();
+    let manifest = assert_fs::NamedTempFile::new("perms.toml").unwrap();
+    manifest
+        .write_str("[[file]]\npath = \"bin/tool\"\nexecutable = true\n")
+        .unwrap();
+
+    let opts = DirectoryBuildOptions {
+        permissions_source: swhid::PermissionsSourceKind::Manifest,
+        permissions_manifest_path: Some(manifest.path().to_path_buf()),
+        ..Default::default()
+    };
+    let swhid = DiskDirectoryBuilder::new(tmp.path())
+        .with_build_options(opts.clone())
+        .swhid()
+        .unwrap();
+
+    let mut expected = DirectorySink::new();
+    expected.add_file("README", b"readme").unwrap();
+    expected
+        .add_entry(
+            "bin/tool",
+            &swhid::Content::from_bytes(b"#!/bin/sh\n".to_vec()).swhid(),
+            swhid::EntryPerms::File { executable: true },
+        )
+        .unwrap();
+    assert_eq!(swhid, expected.finalize().unwrap());
+
+    let bin = DiskDirectoryBuilder::new(&tmp.path().join("bin"))
+        .with_build_options(DirectoryBuildOptions {
+            permissions_manifest_path: Some(manifest.path().to_path_buf()),
+            ..opts
+        })
+        .build()
+        .unwrap();
+    // paths are relative to the walked directory
+    assert_eq!(bin.entries()[0].mode(), 0o100644);
+}
+
+#[test]
+fn directory_cache_update() {
+    let tmp = assert_fs::TempDir::new().unwrap();
+    tmp.child("README").write_str("readme").unwrap();
+    tmp.child("src/lib.rs").write_str("lib").unwrap();
+    tmp.child("src/a/b/deep.rs").write_str("deep").unwrap();
+    tmp.child("src/a/other.rs").write_str("other").unwrap();
+    tmp.child("docs/index.md").write_str("index").unwrap();
+
+    let fresh_swhid = || DiskDirectoryBuilder::new(tmp.path()).swhid().unwrap();
+
+    let mut cache = DirectoryCache::new(tmp.path(), DirectoryBuildOptions::default()).unwrap();
+    assert_eq!(cache.swhid(), fresh_swhid());
+
+    // Modified file, absolute path
+    tmp.child("src/a/b/deep.rs").write_str("deeper").unwrap();
+    let swhid = cache.update(&tmp.path().join("src/a/b/deep.rs")).unwrap();
+    assert_eq!(swhid, fresh_swhid());
+    assert_eq!(cache.swhid(), swhid);
+
+    // New file in new directories, relative path
+    tmp.child("src/c/d/new.rs").write_str("new").unwrap();
+    let swhid = cache
+        .update(std::path::Path::new("src/c/d/new.rs"))
+        .unwrap();
+    assert_eq!(swhid, fresh_swhid());
+
+    // Removed directory
+    std::fs::remove_dir_all(tmp.path().join("src/a")).unwrap();
+    let swhid = cache.update(std::path::Path::new("src/a")).unwrap();
+    assert_eq!(swhid, fresh_swhid());
+
+    // Modified file in a directory whose sibling was removed
+    tmp.child("src/c/d/new.rs").write_str("newer").unwrap();
+    let swhid = cache
+        .update(std::path::Path::new("src/c/d/new.rs"))
+        .unwrap();
+    assert_eq!(swhid, fresh_swhid());
+
+    assert!(cache.update(std::path::Path::new("/elsewhere")).is_err());
+}
+
+#[test]
+fn directory_cache_update_empty_dirs() {
+    let tmp = assert_fs::TempDir::new().unwrap();
+    tmp.child("README").write_str("readme").unwrap();
+    tmp.child("a/b").create_dir_all().unwrap();
+
+    for prune_empty_dirs in [true, false] {
+        let opts = DirectoryBuildOptions {
+            walk_options: WalkOptions {
+                prune_empty_dirs,
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        let fresh_swhid = || {
+            DiskDirectoryBuilder::new(tmp.path())
+                .with_build_options(opts.clone())
+                .swhid()
+                .unwrap()
+        };
+        let mut cache = DirectoryCache::new(tmp.path(), opts.clone()).unwrap();
+        assert_eq!(cache.swhid(), fresh_swhid());
+
+        // A pruned directory is no longer empty
+        tmp.child("a/b/new.rs").write_str("new").unwrap();
+        let swhid = cache.update(std::path::Path::new("a/b/new.rs")).unwrap();
+        assert_eq!(swhid, fresh_swhid());
+
+        // ... and becomes empty again
+        std::fs::remove_file(tmp.path().join("a/b/new.rs")).unwrap();
+        let swhid = cache.update(std::path::Path::new("a/b/new.rs")).unwrap();
+        assert_eq!(swhid, fresh_swhid());
+    }
+}
+
+#[test]
+fn entry_from_swhid() {
+    use swhid::permissions::EntryPerms;
+
+    let subdir = Directory::new(vec![Entry::new(name("a.txt"), 0o100644, [1; 20])]).unwrap();
+    let entry = Entry::from_swhid(name("subdir"), &subdir.swhid(), EntryPerms::Directory).unwrap();
+    assert_eq!(
+        entry,
+        Entry::new(name("subdir"), 0o040000, *subdir.swhid().digest_bytes())
+    );
+
+    let content = swhid::Content::from_bytes(b"content").swhid();
+    let entry = Entry::from_swhid(
+        name("script.sh"),
+        &content,
+        EntryPerms::File { executable: true },
+    )
+    .unwrap();
+    assert_eq!(
+        entry,
+        Entry::new(name("script.sh"), 0o100755, *content.digest_bytes())
+    );
+}
+
+#[test]
+fn entry_from_swhid_mismatched_type() {
+    use swhid::permissions::EntryPerms;
+
+    let content = swhid::Content::from_bytes(b"content").swhid();
+    assert!(matches!(
+        Entry::from_swhid(name("subdir"), &content, EntryPerms::Directory),
+        Err(swhid::error::DirectoryError::MismatchedObjectType {
+            object_type: ObjectType::Content,
+            perms: EntryPerms::Directory,
+            ..
+        })
+    ));
+}
+
+#[test]
+fn directory_dump() {
+    let dir = Directory::new(vec![
+        Entry::new(name("a.txt"), 0o100644, [1; 20]),
+        Entry::new(name("b.txt"), 0o100755, [2; 20]),
+        Entry::new(name("c.txt"), 0o100644, [0; 20]),
+    ])
+    .unwrap();
+
+    let dump = swhid::debug::directory_dump(&dir);
+    assert!(dump.starts_with(
+        "# object header: tree 99\n\
+         00000000  74 72 65 65 20 39 39 00                          |tree 99.|\n\
+         # entry: 100644 a.txt 0101010101010101010101010101010101010101\n\
+         00000008  31 30 30 36 34 34 20 61 2e 74 78 74 00 01 01 01  |100644 a.txt....|\n"
+    ));
+    assert!(dump.contains(
+        "# entry: 100755 b.txt 0202020202020202020202020202020202020202\n\
+         00000029  31 30 30 37 35 35 20 62 2e 74 78 74 00 02 02 02  |100755 b.txt....|\n"
+    ));
+}
+
+#[cfg(feature = "sort-external")]
+#[test]
+fn sort_large_dirs_externally_same_swhid() {
+    let tmp = assert_fs::TempDir::new().unwrap();
+    for i in 0..50 {
+        tmp.child(format!("file{i}")).write_str("content").unwrap();
+        tmp.child(format!("dir{i}/file"))
+            .write_str("nested")
+            .unwrap();
+    }
+    tmp.child("a").write_str("a").unwrap();
+    tmp.child("a.b/c").write_str("c").unwrap();
+
+    let opts = WalkOptions {
+        sort_large_dirs_externally: true,
+        ..Default::default()
+    };
+    assert_eq!(
+        DiskDirectoryBuilder::new(tmp.path())
+            .with_options(opts)
+            .swhid()
+            .unwrap(),
+        DiskDirectoryBuilder::new(tmp.path()).swhid().unwrap()
+    );
+}
+
+#[cfg(not(feature = "sort-external"))]
+#[test]
+fn sort_large_dirs_externally_requires_feature() {
+    let tmp = assert_fs::TempDir::new().unwrap();
+    tmp.child("file").write_str("content").unwrap();
+
+    let opts = WalkOptions {
+        sort_large_dirs_externally: true,
+        ..Default::default()
+    };
+    assert!(DiskDirectoryBuilder::new(tmp.path())
+        .with_options(opts)
+        .swhid()
+        .is_err());
+}
+
+#[test]
+fn git_tree_object() {
+    let dir = Directory::new(vec![
+        Entry::new(name("b.txt"), 0o100755, [2; 20]),
+        Entry::new(name("a"), 0o040000, [1; 20]),
+        Entry::new(name("a.txt"), 0o100644, [0; 20]),
+    ])
+    .unwrap();
+
+    let tree = dir.git_tree_object();
+    assert!(tree.starts_with(b"100644 a.txt\0"));
+    assert_eq!(
+        &swhid::hash::hash_swhid_object("tree", &tree),
+        dir.swhid().digest_bytes()
+    );
+}
+
+#[test]
+fn iterate_directory() {
+    let tmp = assert_fs::TempDir::new().unwrap();
+    tmp.child("a.txt").write_str("a").unwrap();
+    tmp.child("b.txt").write_str("b").unwrap();
+    tmp.child("sub/c.txt").write_str("c").unwrap();
+
+    let dir = DiskDirectoryBuilder::new(tmp.path()).build().unwrap();
+    let mut count = 0;
+    for entry in &dir {
+        assert!(dir.entries().contains(entry));
+        count += 1;
+    }
+    assert_eq!(count, 3);
+    let names: Vec<_> = (&dir).into_iter().map(|entry| entry.name()).collect();
+    assert_eq!(names, [&b"a.txt"[..], b"b.txt", b"sub"]);
+}
+
+#[test]
+fn path_filter() {
+    let tmp = assert_fs::TempDir::new().unwrap();
+    tmp.child("a.txt").write_str("a").unwrap();
+    tmp.child("b.txt").write_str("b").unwrap();
+    tmp.child("sub/c.txt").write_str("c").unwrap();
+    tmp.child("sub/d.txt").write_str("d").unwrap();
+    tmp.child("other/e.txt").write_str("e").unwrap();
+
+    let sparse = assert_fs::TempDir::new().unwrap();
+    sparse.child("a.txt").write_str("a").unwrap();
+    sparse.child("sub/c.txt").write_str("c").unwrap();
+
+    let filter: HashSet<PathBuf> = ["a.txt", "sub/c.txt"].map(PathBuf::from).into();
+    let builder = DiskDirectoryBuilder::new(tmp.path()).with_path_filter(filter);
+    let expected = DiskDirectoryBuilder::new(sparse.path()).swhid().unwrap();
+    assert_eq!(builder.swhid().unwrap(), expected);
+    assert_eq!(builder.build().unwrap().swhid(), expected);
+
+    // directories in the filter are included with their contents
+    let filter: HashSet<PathBuf> = ["sub"].map(PathBuf::from).into();
+    let dir = DiskDirectoryBuilder::new(tmp.path())
+        .with_path_filter(filter)
+        .build()
+        .unwrap();
+    assert_eq!(dir.entries().len(), 1);
+    assert_eq!(dir.entries()[0].name(), b"sub");
+    assert_eq!(
+        dir.entries()[0].id(),
+        DiskDirectoryBuilder::new(&tmp.path().join("sub"))
+            .swhid()
+            .unwrap()
+            .digest_bytes()
+    );
+}
+
+#[test]
+fn intern_names() {
+    let tmp = assert_fs::TempDir::new().unwrap();
+    for dir in ["", "src/", "src/src/", "lib/src/"] {
+        tmp.child(format!("{dir}mod.rs")).write_str(dir).unwrap();
+    }
+
+    let opts = DirectoryBuildOptions {
+        walk_options: WalkOptions {
+            intern_names: true,
+            ..Default::default()
+        },
+        ..Default::default()
+    };
+    let expected = DiskDirectoryBuilder::new(tmp.path()).swhid().unwrap();
+    let builder = DiskDirectoryBuilder::new(tmp.path()).with_build_options(opts.clone());
+    assert_eq!(builder.swhid().unwrap(), expected);
+    assert_eq!(builder.build().unwrap().swhid(), expected);
+
+    let mut cache = DirectoryCache::new(tmp.path(), opts).unwrap();
+    assert_eq!(cache.swhid(), expected);
+    tmp.child("lib/src/mod.rs").write_str("changed").unwrap();
+    assert_eq!(
+        cache.update(&tmp.path().join("lib/src/mod.rs")).unwrap(),
+        DiskDirectoryBuilder::new(tmp.path()).swhid().unwrap()
+    );
+}
+
+#[test]
+fn max_file_size() {
+    let tmp = assert_fs::TempDir::new().unwrap();
+    tmp.child("small.txt").write_str("small").unwrap();
+    tmp.child("sub/large.bin").write_binary(&[0; 100]).unwrap();
+
+    let opts = WalkOptions {
+        max_file_size: Some(99),
+        ..Default::default()
+    };
+    let builder = DiskDirectoryBuilder::new(tmp.path()).with_options(opts.clone());
+    assert!(builder.swhid().is_err());
+    assert!(builder.build().is_err());
+
+    // The limit is inclusive
+    let opts = WalkOptions {
+        max_file_size: Some(100),
+        ..opts
+    };
+    let full = DiskDirectoryBuilder::new(tmp.path())
+        .with_options(opts.clone())
+        .swhid()
+        .unwrap();
+    assert_eq!(full, DiskDirectoryBuilder::new(tmp.path()).swhid().unwrap());
+
+    // Skipping the only file of `sub` also prunes it
+    let opts = WalkOptions {
+        max_file_size: Some(99),
+        skip_oversized_files: true,
+        ..opts
+    };
+    let dir = DiskDirectoryBuilder::new(tmp.path())
+        .with_options(opts.clone())
+        .build()
+        .unwrap();
+    assert_eq!(
+        dir.entries(),
+        vec![Entry::new(
+            name("small.txt"),
+            0o100644,
+            hash_content(b"small")
+        )]
+    );
+    assert_eq!(
+        DiskDirectoryBuilder::new(tmp.path())
+            .with_options(opts)
+            .swhid()
+            .unwrap(),
+        dir.swhid()
+    );
+}
+
+#[test]
+#[cfg(unix)]
+fn detect_hardlinks() {
+    use std::path::PathBuf;
+
+    let tmp = assert_fs::TempDir::new().unwrap();
+    tmp.child("a.txt").write_str("shared").unwrap();
+    tmp.child("sub").create_dir_all().unwrap();
+    std::fs::hard_link(tmp.child("a.txt").path(), tmp.child("b.txt").path()).unwrap();
+    std::fs::hard_link(tmp.child("a.txt").path(), tmp.child("sub/c.txt").path()).unwrap();
+    tmp.child("d.txt").write_str("shared").unwrap();
+    tmp.child("e.txt").write_str("other").unwrap();
+    std::fs::hard_link(tmp.child("e.txt").path(), tmp.child("f.txt").path()).unwrap();
+
+    let opts = WalkOptions {
+        detect_hardlinks: true,
+        ..Default::default()
+    };
+    let (dir, hardlinks) = DiskDirectoryBuilder::new(tmp.path())
+        .with_options(opts)
+        .build_with_hardlinks()
+        .unwrap();
+    assert_eq!(
+        hardlinks,
+        vec![
+            vec![
+                PathBuf::from("a.txt"),
+                PathBuf::from("b.txt"),
+                PathBuf::from("sub/c.txt")
+            ],
+            vec![PathBuf::from("e.txt"), PathBuf::from("f.txt")],
+        ]
+    );
+    // Identifiers are unchanged
+    assert_eq!(dir, DiskDirectoryBuilder::new(tmp.path()).build().unwrap());
+
+    let (_, hardlinks) = DiskDirectoryBuilder::new(tmp.path())
+        .build_with_hardlinks()
+        .unwrap();
+    assert!(hardlinks.is_empty());
+
+    // Only files part of the directory are reported
+    tmp.child("bin").write_binary(b"\0binary").unwrap();
+    std::fs::hard_link(tmp.child("bin").path(), tmp.child("bin2").path()).unwrap();
+    tmp.child("big.txt").write_str(&"x".repeat(100)).unwrap();
+    std::fs::hard_link(tmp.child("big.txt").path(), tmp.child("big2.txt").path()).unwrap();
+    let opts = WalkOptions {
+        detect_hardlinks: true,
+        skip_binary: true,
+        max_file_size: Some(50),
+        skip_oversized_files: true,
+        exclude_suffixes: vec!["f.txt".to_owned()],
+        ..Default::default()
+    };
+    let (dir, hardlinks) = DiskDirectoryBuilder::new(tmp.path())
+        .with_options(opts)
+        .build_with_hardlinks()
+        .unwrap();
+    assert_eq!(
+        hardlinks,
+        vec![vec![
+            PathBuf::from("a.txt"),
+            PathBuf::from("b.txt"),
+            PathBuf::from("sub/c.txt")
+        ]]
+    );
+    assert!(dir.entries().iter().all(|entry| entry.name() != b"bin"));
+}
+
+#[test]
+fn owned_builder() {
+    struct Project {
+        builder: DiskDirectoryBuilder<'static>,
+    }
+
+    fn project(root: &std::path::Path) -> Project {
+        Project {
+            builder: DiskDirectoryBuilder::new_owned(root.to_path_buf()).with_options(
+                WalkOptions {
+                    exclude_suffixes: vec![".log".to_owned()],
+                    ..Default::default()
+                },
+            ),
+        }
+    }
+
+    let tmp = assert_fs::TempDir::new().unwrap();
+    tmp.child("a.txt").write_str("a").unwrap();
+    tmp.child("debug.log").write_str("log").unwrap();
+
+    let project = project(tmp.path());
+    let expected = DiskDirectoryBuilder::new(tmp.path())
+        .with_options(WalkOptions {
+            exclude_suffixes: vec![".log".to_owned()],
+            ..Default::default()
+        })
+        .swhid()
+        .unwrap();
+    assert_eq!(project.builder.swhid().unwrap(), expected);
+    assert_eq!(
+        project.builder.swhid().unwrap().to_string(),
+        "swh:1:dir:1a602d9bd07ce5272ddaa64e21da12dbca2b8c9f"
+    );
+}
+
+#[test]
+fn swhid_checked() {
+    let tmp = assert_fs::TempDir::new().unwrap();
+    tmp.child("a.txt").write_str("a").unwrap();
+    tmp.child("sub/b.txt").write_str("b").unwrap();
+
+    let builder = DiskDirectoryBuilder::new(tmp.path());
+    assert_eq!(builder.swhid_checked().unwrap(), builder.swhid().unwrap());
+}
+
+#[test]
+fn directory_sink() {
+    let tmp = assert_fs::TempDir::new().unwrap();
+    tmp.child("a.txt").write_str("a").unwrap();
+    tmp.child("sub/b.txt").write_str("b").unwrap();
+    tmp.child("sub/c.txt").write_str("c").unwrap();
+
+    let mut sink = DirectorySink::new();
+    sink.add_file("sub/c.txt", b"c").unwrap();
+    sink.add_file("a.txt", b"a").unwrap();
+    sink.add_file("./sub//b.txt", b"b").unwrap();
+    assert_eq!(
+        sink.finalize().unwrap(),
+        DiskDirectoryBuilder::new(tmp.path()).swhid().unwrap()
+    );
+
+    let mut sink = DirectorySink::new();
+    sink.add_file("sub/b.txt", b"b").unwrap();
+    assert!(sink.add_file("sub/b.txt", b"other").is_err());
+    assert!(sink.add_file("sub", b"").is_err());
+    assert!(sink.add_file("sub/b.txt/d.txt", b"").is_err());
+    assert!(sink.add_file("../d.txt", b"").is_err());
+    assert!(sink
+        .add_entry(
+            "sub/link",
+            &"swh:1:dir:4b825dc642cb6eb9a060e54bf8d69288fbee4904"
+                .parse()
+                .unwrap(),
+            swhid::EntryPerms::Symlink
+        )
+        .is_err());
+}
+
+#[test]
+fn skip_binary() {
+    let tmp = assert_fs::TempDir::new().unwrap();
+    tmp.child("main.c").write_str("int main() {}\n").unwrap();
+    tmp.child("logo.png")
+        .write_binary(b"\x89PNG\r\n\x1a\n\0\0")
+        .unwrap();
+    // only the first 8000 bytes of files are checked
+    let mut nul = vec![b'a'; 7999];
+    nul.push(0);
+    tmp.child("nul.txt").write_binary(&nul).unwrap();
+    let mut late_nul = vec![b'a'; 8000];
+    late_nul.push(0);
+    tmp.child("late_nul.txt").write_binary(&late_nul).unwrap();
+    tmp.child("assets/blob.bin").write_binary(&[0; 10]).unwrap();
+
+    let opts = WalkOptions {
+        skip_binary: true,
+        ..Default::default()
+    };
+    let swhid = DiskDirectoryBuilder::new(tmp.path())
+        .with_options(opts)
+        .swhid()
+        .unwrap();
+
+    let mut expected = DirectorySink::new();
+    expected.add_file("main.c", b"int main() {}\n").unwrap();
+    expected.add_file("late_nul.txt", &late_nul).unwrap();
+    assert_eq!(swhid, expected.finalize().unwrap());
+    assert_ne!(
+        swhid,
+        DiskDirectoryBuilder::new(tmp.path()).swhid().unwrap()
+    );
+}
+
+#[test]
+fn skip_hidden() {
+    let tmp = assert_fs::TempDir::new().unwrap();
+    tmp.child("README").write_str("readme").unwrap();
+    tmp.child(".hidden").write_str("hidden").unwrap();
+    tmp.child(".config/settings").write_str("settings").unwrap();
+    tmp.child("src/.DS_Store").write_str("junk").unwrap();
+    tmp.child("src/lib.rs").write_str("").unwrap();
+
+    let mut all = DirectorySink::new();
+    all.add_file("README", b"readme").unwrap();
+    all.add_file(".hidden", b"hidden").unwrap();
+    all.add_file(".config/settings", b"settings").unwrap();
+    all.add_file("src/.DS_Store", b"junk").unwrap();
+    all.add_file("src/lib.rs", b"").unwrap();
+    assert_eq!(
+        DiskDirectoryBuilder::new(tmp.path()).swhid().unwrap(),
+        all.finalize().unwrap()
+    );
+
+    let opts = WalkOptions {
+        skip_hidden: true,
+        ..Default::default()
+    };
+    let builder = DiskDirectoryBuilder::new(tmp.path()).with_options(opts);
+    let mut visible = DirectorySink::new();
+    visible.add_file("README", b"readme").unwrap();
+    visible.add_file("src/lib.rs", b"").unwrap();
+    assert_eq!(builder.swhid().unwrap(), visible.finalize().unwrap());
+    let names: Vec<_> = builder
+        .build()
+        .unwrap()
+        .entries()
+        .iter()
+        .map(|entry| entry.name().to_vec())
+        .collect();
+    assert_eq!(names, [b"README".to_vec(), b"src".to_vec()]);
+}
+
+#[test]
+fn skip_vcs_dirs() {
+    let tmp = assert_fs::TempDir::new().unwrap();
+    tmp.child("README").write_str("readme").unwrap();
+    tmp.child(".git/HEAD")
+        .write_str("ref: refs/heads/main\n")
+        .unwrap();
+    tmp.child(".hg/requires").write_str("store\n").unwrap();
+    // a submodule, where `.git` is a file
+    tmp.child("sub/.git")
+        .write_str("gitdir: ../.git/modules/sub\n")
+        .unwrap();
+    tmp.child("sub/lib.rs").write_str("").unwrap();
+    tmp.child(".gitignore").write_str("target\n").unwrap();
+
+    let mut project = DirectorySink::new();
+    project.add_file("README", b"readme").unwrap();
+    project.add_file("sub/lib.rs", b"").unwrap();
+    project.add_file(".gitignore", b"target\n").unwrap();
+    let project = project.finalize().unwrap();
+    assert_eq!(
+        DiskDirectoryBuilder::new(tmp.path()).swhid().unwrap(),
+        project
+    );
+
+    let opts = WalkOptions {
+        skip_vcs_dirs: false,
+        ..Default::default()
+    };
+    let everything = DiskDirectoryBuilder::new(tmp.path())
+        .with_options(opts)
+        .build()
+        .unwrap();
+    assert_ne!(everything.swhid(), project);
+    assert_eq!(everything.entries().len(), 5);
+}
+
+#[test]
+fn overlay() {
+    use std::collections::HashMap;
+
+    fn file(name: &'static str, contents: &[u8]) -> Entry {
+        Entry::new(self::name(name), 0o100644, hash_content(contents))
+    }
+    let mut store = HashMap::new();
+    let mut dir = |entries: Vec<Entry>| {
+        let dir = Directory::new(entries).unwrap();
+        store.insert(*dir.swhid().digest_bytes(), dir.clone());
+        dir
+    };
+    let subdir = |name: &'static str, dir: &Directory| {
+        Entry::new(self::name(name), 0o040000, *dir.swhid().digest_bytes())
+    };
+
+    let base_deep = dir(vec![file("x", b"x")]);
+    let base_sub = dir(vec![
+        file("b.txt", b"b"),
+        file("c.txt", b"c"),
+        subdir("deep", &base_deep),
+    ]);
+    let base_replaced = dir(vec![file("y", b"y")]);
+    let base = dir(vec![
+        file("a.txt", b"a"),
+        subdir("sub", &base_sub),
+        subdir("replaced", &base_replaced),
+    ]);
+    let upper_deep = dir(vec![file("z", b"z")]);
+    let upper_sub = dir(vec![
+        file("c.txt", b"new c"),
+        file("d.txt", b"d"),
+        subdir("deep", &upper_deep),
+    ]);
+    let upper = dir(vec![
+        file("a.txt", b"new a"),
+        file("e.txt", b"e"),
+        subdir("sub", &upper_sub),
+        // a file replaces a directory
+        file("replaced", b"file"),
+    ]);
+
+    let mut expected = DirectorySink::new();
+    expected.add_file("a.txt", b"new a").unwrap();
+    expected.add_file("e.txt", b"e").unwrap();
+    expected.add_file("replaced", b"file").unwrap();
+    expected.add_file("sub/b.txt", b"b").unwrap();
+    expected.add_file("sub/c.txt", b"new c").unwrap();
+    expected.add_file("sub/d.txt", b"d").unwrap();
+    expected.add_file("sub/deep/x", b"x").unwrap();
+    expected.add_file("sub/deep/z", b"z").unwrap();
+
+    let merged = base.overlay(&upper, |id| store.get(id).cloned()).unwrap();
+    assert_eq!(merged.swhid(), expected.finalize().unwrap());
+
+    // overlaying a directory over itself or an empty one changes nothing
+    assert_eq!(base.overlay(&base, |_| None).unwrap(), base);
+    assert_eq!(base.overlay(&Directory::default(), |_| None).unwrap(), base);
+    assert_eq!(Directory::default().overlay(&base, |_| None).unwrap(), base);
+
+    // subdirectories must be known to be merged
+    assert!(matches!(
+        base.overlay(&upper, |_| None),
+        Err(swhid::error::DirectoryError::UnknownSubdirectory(_))
+    ));
+}
+
+#[test]
+fn walk_checkpoint() {
+    let tmp = assert_fs::TempDir::new().unwrap();
+    tmp.child("a/x/1.txt").write_str("1").unwrap();
+    tmp.child("a/2.txt").write_str("2").unwrap();
+    tmp.child("b/3.txt").write_str("3").unwrap();
+    tmp.child("b/large.bin").write_binary(&[0; 100]).unwrap();
+    tmp.child("4.txt").write_str("4").unwrap();
+    let builder = DiskDirectoryBuilder::new(tmp.path());
+    let full = builder.swhid().unwrap();
+
+    let mut checkpoint = WalkCheckpoint::new();
+    assert_eq!(builder.swhid_resuming(&mut checkpoint).unwrap(), full);
+    // the root, a, a/x and b
+    assert_eq!(checkpoint.len(), 4);
+    assert!(checkpoint.get(&PathBuf::from("a/x")).is_some());
+    let mut saved = Vec::new();
+    checkpoint.save(&mut saved).unwrap();
+    assert_eq!(WalkCheckpoint::load(saved.as_slice()).unwrap(), checkpoint);
+
+    // Resuming after any number of directories gives the same identifier. The
+    // root is hashed last, and its record (the empty path) is saved first.
+    let records: Vec<&[u8]> = saved.split_inclusive(|b| *b == 0).skip(1).collect();
+    for n in 0..=records.len() {
+        let mut checkpoint = WalkCheckpoint::load(records[..n].concat().as_slice()).unwrap();
+        assert_eq!(checkpoint.len(), n);
+        assert_eq!(builder.swhid_resuming(&mut checkpoint).unwrap(), full);
+        assert_eq!(checkpoint.get(&PathBuf::new()), Some(full.digest_bytes()));
+    }
+
+    // Directories in the checkpoint are not read again
+    let mut stale = WalkCheckpoint::load(records[..2].concat().as_slice()).unwrap();
+    tmp.child("a/x/1.txt").write_str("changed").unwrap();
+    tmp.child("b/3.txt").write_str("changed").unwrap();
+    let resumed = builder.swhid_resuming(&mut stale).unwrap();
+    assert_ne!(resumed, full);
+    assert_ne!(resumed, builder.swhid().unwrap());
+    tmp.child("a/x/1.txt").write_str("1").unwrap();
+    tmp.child("b/3.txt").write_str("3").unwrap();
+
+    // A failed walk keeps the directories hashed before the failure
+    let limited = DiskDirectoryBuilder::new(tmp.path()).with_options(WalkOptions {
+        max_file_size: Some(99),
+        ..Default::default()
+    });
+    let mut checkpoint = WalkCheckpoint::new();
+    assert!(limited.swhid_resuming(&mut checkpoint).is_err());
+    assert!(checkpoint.get(&PathBuf::from("b")).is_none());
+    assert!(checkpoint.get(&PathBuf::new()).is_none());
+    assert_eq!(builder.swhid_resuming(&mut checkpoint).unwrap(), full);
+
+    assert!(WalkCheckpoint::load(&b"not a checkpoint\0"[..]).is_err());
+}
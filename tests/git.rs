@@ -5,7 +5,7 @@ use git2::{Repository, Signature, Time};
 
 use swhid::git::*;
 use swhid::release::{Release, ReleaseTargetType};
-use swhid::revision::Revision;
+use swhid::revision::{rev_manifest, Revision};
 use swhid::snapshot::{Branch, BranchTarget, Snapshot};
 
 fn bs(s: &'static str) -> Box<[u8]> {
@@ -67,6 +67,7 @@ fn test_revision_swhid() {
             committer_timestamp_offset: bs("+0100"),
             extra_headers: Vec::new(),
             message: Some(bs("Test commit")),
+            raw_manifest: None,
         }
     );
 
@@ -86,6 +87,29 @@ fn test_revision_swhid() {
     );
 }
 
+#[test]
+fn test_verify_revision() {
+    let tmp = assert_fs::TempDir::new().unwrap();
+    let repo = Repository::init(tmp.path()).unwrap();
+
+    let tree_oid = repo.treebuilder(None).unwrap().write().unwrap();
+    let tree = repo.find_tree(tree_oid).unwrap();
+    let sig = Signature::new("Test User", "test@example.com", &Time::new(1763027354, 60)).unwrap();
+    let commit_oid = repo
+        .commit(None, &sig, &sig, "Test commit", &tree, &[])
+        .unwrap();
+
+    let matching = swhid::Swhid::new(swhid::ObjectType::Revision, oid_to_array(commit_oid));
+    assert!(verify_revision(&repo, &commit_oid, &matching).unwrap());
+
+    let other = swhid::Swhid::new(swhid::ObjectType::Revision, [0; 20]);
+    assert!(!verify_revision(&repo, &commit_oid, &other).unwrap());
+
+    // Same digest, but not a revision
+    let wrong_type = swhid::Swhid::new(swhid::ObjectType::Release, oid_to_array(commit_oid));
+    assert!(!verify_revision(&repo, &commit_oid, &wrong_type).unwrap());
+}
+
 #[test]
 fn test_signed_revision_swhid() {
     let tmp = assert_fs::TempDir::new().unwrap();
@@ -136,6 +160,7 @@ fn test_signed_revision_swhid() {
             committer_timestamp_offset: bs("+0100"),
             extra_headers: vec![(bs("gpgsig"), bs(gpgsig))],
             message: Some(bs("Test commit")),
+            raw_manifest: None,
         }
     );
 
@@ -201,6 +226,7 @@ fn test_release_swhid() {
             author_timestamp_offset: Some(bs("+0100")),
             extra_headers: Vec::new(),
             message: Some(bs("Test tag")),
+            raw_manifest: None,
         }
     );
 
@@ -220,6 +246,92 @@ fn test_release_swhid() {
     );
 }
 
+#[test]
+fn test_lightweight_and_annotated_tag_swhid() {
+    let tmp = assert_fs::TempDir::new().unwrap();
+    let repo = Repository::init(tmp.path()).unwrap();
+
+    let tree_oid = repo.treebuilder(None).unwrap().write().unwrap();
+    let tree = repo.find_tree(tree_oid).unwrap();
+    let sig = Signature::new("Test User", "test@example.com", &Time::new(1763027354, 60)).unwrap();
+    let commit_oid = repo
+        .commit(None, &sig, &sig, "Test commit", &tree, &[])
+        .unwrap();
+    let commit = repo.find_object(commit_oid, None).unwrap();
+
+    let tag_oid = repo
+        .tag("v1.0", &commit, &sig, "Test tag", /* force: */ false)
+        .unwrap();
+    repo.tag_lightweight("v1.0-light", &commit, /* force: */ false)
+        .unwrap();
+    repo.tag_lightweight("tree-light", tree.as_object(), /* force: */ false)
+        .unwrap();
+
+    // Annotated tags are releases, whatever the policy
+    let release = release_swhid(&repo, &tag_oid).unwrap();
+    for policy in [LightweightTagPolicy::Error, LightweightTagPolicy::Target] {
+        assert_eq!(tag_swhid(&repo, "v1.0", policy).unwrap(), release);
+    }
+
+    // Lightweight tags have no release object
+    let err = release_swhid(&repo, &commit_oid).unwrap_err();
+    assert!(err
+        .to_string()
+        .contains("lightweight tag has no release object"));
+    let err = tag_swhid(&repo, "v1.0-light", LightweightTagPolicy::Error).unwrap_err();
+    assert!(err.to_string().contains("lightweight tag"));
+
+    // ... but can be resolved to the tagged object
+    assert_eq!(
+        tag_swhid(&repo, "v1.0-light", LightweightTagPolicy::Target).unwrap(),
+        revision_swhid(&repo, &commit_oid).unwrap()
+    );
+    assert_eq!(
+        tag_swhid(&repo, "tree-light", LightweightTagPolicy::Target)
+            .unwrap()
+            .to_string(),
+        "swh:1:dir:4b825dc642cb6eb9a060e54bf8d69288fbee4904"
+    );
+
+    assert!(tag_swhid(&repo, "does-not-exist", LightweightTagPolicy::Target).is_err());
+}
+
+#[test]
+fn test_get_tags_named() {
+    let tmp = assert_fs::TempDir::new().unwrap();
+    let repo = Repository::init(tmp.path()).unwrap();
+
+    let tree_oid = repo.treebuilder(None).unwrap().write().unwrap();
+    let tree = repo.find_tree(tree_oid).unwrap();
+    let sig = Signature::new("Test User", "test@example.com", &Time::new(1763027354, 60)).unwrap();
+    let commit_oid = repo
+        .commit(None, &sig, &sig, "Test commit", &tree, &[])
+        .unwrap();
+    let commit = repo.find_object(commit_oid, None).unwrap();
+
+    let tag_oid = repo
+        .tag("v1.0", &commit, &sig, "Test tag", /* force: */ false)
+        .unwrap();
+    repo.tag_lightweight("v1.0-light", &commit, /* force: */ false)
+        .unwrap();
+
+    let mut tags = get_tags_named(&repo).unwrap();
+    tags.sort();
+    assert_eq!(
+        tags,
+        vec![
+            ("v1.0".to_owned(), tag_oid),
+            ("v1.0-light".to_owned(), commit_oid),
+        ]
+    );
+
+    let mut tag_oids = get_tags(&repo).unwrap();
+    tag_oids.sort();
+    let mut expected_oids = vec![tag_oid, commit_oid];
+    expected_oids.sort();
+    assert_eq!(tag_oids, expected_oids);
+}
+
 #[test]
 fn test_snapshot_swhid() {
     let tmp = assert_fs::TempDir::new().unwrap();
@@ -324,3 +436,745 @@ fn test_snapshot_swhid() {
         "swh:1:snp:a0bfd8450daaf74c55c2375f21e40745bc5f95b7"
     );
 }
+
+/// Creates a repository with a single commit on `refs/heads/main`, which HEAD points to
+fn repo_with_main_branch(tmp: &assert_fs::TempDir) -> (Repository, [u8; 20]) {
+    let repo = Repository::init(tmp.path()).unwrap();
+
+    let mut index = repo.index().unwrap();
+    let file_path = tmp.child("test.txt");
+    file_path.write_str("test content").unwrap();
+    index
+        .add_path(file_path.path().strip_prefix(tmp.path()).unwrap())
+        .unwrap();
+    let tree_oid = index.write_tree().unwrap();
+
+    let commit_oid = {
+        let tree = repo.find_tree(tree_oid).unwrap();
+        let sig =
+            Signature::new("Test User", "test@example.com", &Time::new(1763027354, 60)).unwrap();
+        repo.commit(
+            Some("refs/heads/main"),
+            &sig,
+            &sig,
+            "Test commit",
+            &tree,
+            &[],
+        )
+        .unwrap()
+    };
+    repo.set_head("refs/heads/main").unwrap();
+
+    (repo, oid_to_array(commit_oid))
+}
+
+#[test]
+fn test_snapshot_dangling_direct_ref() {
+    let tmp = assert_fs::TempDir::new().unwrap();
+    let (repo, commit_hash) = repo_with_main_branch(&tmp);
+
+    // libgit2 refuses to create references to missing objects, so write it by hand
+    let missing_hash = [0xab; 20];
+    tmp.child(".git/refs/heads/dangling")
+        .write_str(&format!("{}\n", hex::encode(missing_hash)))
+        .unwrap();
+
+    let snapshot_with_dangling = |dangling_target| {
+        Snapshot::new(vec![
+            Branch {
+                name: bs("HEAD"),
+                target: BranchTarget::Alias(Some(bs("refs/heads/main"))),
+            },
+            Branch {
+                name: bs("refs/heads/main"),
+                target: BranchTarget::Revision(Some(commit_hash)),
+            },
+            Branch {
+                name: bs("refs/heads/dangling"),
+                target: dangling_target,
+            },
+        ])
+        .unwrap()
+    };
+
+    // Default: assume it is a commit
+    assert_eq!(
+        snapshot_from_git(&repo).unwrap(),
+        snapshot_with_dangling(BranchTarget::Revision(Some(missing_hash)))
+    );
+
+    let opts = SnapshotOptions {
+        dangling_target: DanglingTarget::Directory,
+        ..Default::default()
+    };
+    let snp = snapshot_from_git_with_options(&repo, &opts).unwrap();
+    assert_eq!(
+        snp,
+        snapshot_with_dangling(BranchTarget::Directory(Some(missing_hash)))
+    );
+    assert_eq!(
+        snapshot_swhid_with_options(&repo, &opts).unwrap(),
+        snp.swhid()
+    );
+    assert_ne!(snp.swhid(), snapshot_swhid(&repo).unwrap());
+}
+
+#[test]
+fn test_snapshot_dangling_symbolic_ref() {
+    let tmp = assert_fs::TempDir::new().unwrap();
+    let (repo, commit_hash) = repo_with_main_branch(&tmp);
+
+    repo.reference_symbolic(
+        "refs/heads/sym",
+        "refs/heads/does-not-exist",
+        /* force: */ false,
+        "log message",
+    )
+    .unwrap();
+
+    let expected = Snapshot::new(vec![
+        Branch {
+            name: bs("HEAD"),
+            target: BranchTarget::Alias(Some(bs("refs/heads/main"))),
+        },
+        Branch {
+            name: bs("refs/heads/main"),
+            target: BranchTarget::Revision(Some(commit_hash)),
+        },
+        Branch {
+            name: bs("refs/heads/sym"),
+            target: BranchTarget::Alias(Some(bs("refs/heads/does-not-exist"))),
+        },
+    ])
+    .unwrap();
+
+    // Symbolic references are always aliases, whatever the dangling target type
+    assert_eq!(snapshot_from_git(&repo).unwrap(), expected);
+    let opts = SnapshotOptions {
+        dangling_target: DanglingTarget::Directory,
+        ..Default::default()
+    };
+    assert_eq!(
+        snapshot_from_git_with_options(&repo, &opts).unwrap(),
+        expected
+    );
+}
+
+#[test]
+fn test_octopus_merge_revision_swhid() {
+    let tmp = assert_fs::TempDir::new().unwrap();
+    let repo = Repository::init(tmp.path()).unwrap();
+
+    let tree_oid = repo.treebuilder(None).unwrap().write().unwrap();
+    let tree = repo.find_tree(tree_oid).unwrap();
+    let sig = Signature::new("Test User", "test@example.com", &Time::new(1763027354, 60)).unwrap();
+
+    let mut parent_oids: Vec<_> = ["Parent 1", "Parent 2", "Parent 3"]
+        .into_iter()
+        .map(|message| repo.commit(None, &sig, &sig, message, &tree, &[]).unwrap())
+        .collect();
+    // Sort in reverse order, so the parent order cannot be recovered by sorting ids
+    parent_oids.sort_by(|a, b| b.cmp(a));
+    let parents: Vec<_> = parent_oids
+        .iter()
+        .map(|oid| repo.find_commit(*oid).unwrap())
+        .collect();
+
+    let merge_oid = repo
+        .commit(
+            None,
+            &sig,
+            &sig,
+            "Octopus merge",
+            &tree,
+            &parents.iter().collect::<Vec<_>>(),
+        )
+        .unwrap();
+
+    let rev = revision_from_git(&repo, &merge_oid).unwrap();
+    assert_eq!(
+        rev.parents,
+        parent_oids
+            .iter()
+            .copied()
+            .map(oid_to_array)
+            .collect::<Vec<_>>()
+    );
+
+    let parent_lines: String = parent_oids
+        .iter()
+        .map(|oid| format!("parent {oid}\n"))
+        .collect();
+    let manifest = rev_manifest(&rev);
+    assert!(manifest
+        .windows(parent_lines.len())
+        .any(|window| window == parent_lines.as_bytes()));
+
+    // Software Heritage hashes revisions the same way Git hashes commits, so the
+    // revision SWHID must be the commit id.
+    let swhid = revision_swhid(&repo, &merge_oid).unwrap();
+    assert_eq!(swhid.digest_bytes(), &oid_to_array(merge_oid));
+
+    // Reordering the parents changes the SWHID
+    let mut reordered_rev = rev.clone();
+    reordered_rev.parents.reverse();
+    assert_ne!(reordered_rev.swhid(), swhid);
+}
+
+#[test]
+fn test_revision_with_encoding_header() {
+    let tmp = assert_fs::TempDir::new().unwrap();
+    let repo = Repository::init(tmp.path()).unwrap();
+
+    let tree_oid = repo.treebuilder(None).unwrap().write().unwrap();
+
+    // libgit2 cannot create commits with an encoding header, so write the raw object
+    let mut raw_commit = Vec::new();
+    raw_commit.extend_from_slice(format!("tree {tree_oid}\n").as_bytes());
+    raw_commit.extend_from_slice(b"author Test User <test@example.com> 1763027354 +0100\n");
+    raw_commit.extend_from_slice(b"committer Test User <test@example.com> 1763027354 +0100\n");
+    raw_commit.extend_from_slice(b"encoding ISO-8859-1\n");
+    raw_commit.extend_from_slice(b"\n");
+    raw_commit.extend_from_slice(b"Caf\xe9 cr\xe8me\n"); // not valid UTF-8
+    let commit_oid = repo
+        .odb()
+        .unwrap()
+        .write(git2::ObjectType::Commit, &raw_commit)
+        .unwrap();
+
+    let rev = revision_from_git(&repo, &commit_oid).unwrap();
+    let mut expected = Revision {
+        directory: oid_to_array(tree_oid),
+        parents: Vec::new(),
+        author: bs("Test User <test@example.com>"),
+        author_timestamp: 1763027354,
+        author_timestamp_offset: bs("+0100"),
+        committer: bs("Test User <test@example.com>"),
+        committer_timestamp: 1763027354,
+        committer_timestamp_offset: bs("+0100"),
+        extra_headers: vec![(bs("encoding"), bs("ISO-8859-1"))],
+        message: Some(b"Caf\xe9 cr\xe8me\n".as_slice().into()),
+        raw_manifest: None,
+    };
+    assert_eq!(rev, expected);
+    assert_eq!(rev_manifest(&rev), raw_commit);
+
+    // The encoding header is hashed, and the message is hashed as raw bytes
+    let swhid = revision_swhid(&repo, &commit_oid).unwrap();
+    assert_eq!(swhid.digest_bytes(), &oid_to_array(commit_oid));
+    expected.extra_headers.clear();
+    assert_ne!(expected.swhid(), swhid);
+}
+
+#[test]
+fn test_revision_with_mergetag_header() {
+    let tmp = assert_fs::TempDir::new().unwrap();
+    let repo = Repository::init(tmp.path()).unwrap();
+
+    let tree_oid = repo.treebuilder(None).unwrap().write().unwrap();
+    let tree = repo.find_tree(tree_oid).unwrap();
+    let sig = Signature::new("Test User", "test@example.com", &Time::new(1763027354, 60)).unwrap();
+    let parent1 = repo
+        .commit(None, &sig, &sig, "Parent 1", &tree, &[])
+        .unwrap();
+    let parent2 = repo
+        .commit(None, &sig, &sig, "Parent 2", &tree, &[])
+        .unwrap();
+
+    let mergetag = format!(
+        "object {parent2}\n\
+        type commit\n\
+        tag v1.0\n\
+        tagger Test User <test@example.com> 1763027354 +0100\n\
+        \n\
+        Tag message\n\
+        -----BEGIN PGP SIGNATURE-----\n\
+        \n\
+        blah blah blah\n\
+        -----END PGP SIGNATURE-----"
+    );
+
+    // libgit2 cannot create commits with a mergetag header, so write the raw object
+    let mut raw_commit = Vec::new();
+    raw_commit.extend_from_slice(format!("tree {tree_oid}\n").as_bytes());
+    raw_commit.extend_from_slice(format!("parent {parent1}\n").as_bytes());
+    raw_commit.extend_from_slice(format!("parent {parent2}\n").as_bytes());
+    raw_commit.extend_from_slice(b"author Test User <test@example.com> 1763027354 +0100\n");
+    raw_commit.extend_from_slice(b"committer Test User <test@example.com> 1763027354 +0100\n");
+    raw_commit.extend_from_slice(b"mergetag ");
+    raw_commit.extend_from_slice(mergetag.replace('\n', "\n ").as_bytes());
+    raw_commit.extend_from_slice(b"\n\nMerge tag 'v1.0'\n");
+    let commit_oid = repo
+        .odb()
+        .unwrap()
+        .write(git2::ObjectType::Commit, &raw_commit)
+        .unwrap();
+
+    let rev = revision_from_git(&repo, &commit_oid).unwrap();
+    assert_eq!(
+        rev.extra_headers,
+        vec![(bs("mergetag"), mergetag.into_bytes().into_boxed_slice())]
+    );
+    assert_eq!(rev.parents, [oid_to_array(parent1), oid_to_array(parent2)]);
+
+    // Each line of the mergetag, including the empty one, is continued with a space
+    let manifest = rev_manifest(&rev);
+    assert_eq!(manifest, raw_commit);
+    let continued = b"\n -----BEGIN PGP SIGNATURE-----\n \n blah blah blah\n";
+    assert!(manifest
+        .windows(continued.len())
+        .any(|window| window == continued));
+    let swhid = revision_swhid(&repo, &commit_oid).unwrap();
+    assert_eq!(swhid.digest_bytes(), &oid_to_array(commit_oid));
+}
+
+#[test]
+fn test_revision_extra_headers_order() {
+    let tmp = assert_fs::TempDir::new().unwrap();
+    let repo = Repository::init(tmp.path()).unwrap();
+
+    let tree_oid = repo.treebuilder(None).unwrap().write().unwrap();
+    let gpgsig = "-----BEGIN PGP SIGNATURE-----\n\nblah blah blah\n-----END PGP SIGNATURE-----";
+
+    let mut swhids = Vec::new();
+    for (first, second) in [("x-custom", "gpgsig"), ("gpgsig", "x-custom")] {
+        let value = |key| {
+            if key == "gpgsig" {
+                gpgsig
+            } else {
+                "custom value"
+            }
+        };
+        let mut raw_commit = Vec::new();
+        raw_commit.extend_from_slice(format!("tree {tree_oid}\n").as_bytes());
+        raw_commit.extend_from_slice(b"author Test User <test@example.com> 1763027354 +0100\n");
+        raw_commit.extend_from_slice(b"committer Test User <test@example.com> 1763027354 +0100\n");
+        for key in [first, second] {
+            raw_commit.extend_from_slice(
+                format!("{key} {}\n", value(key).replace('\n', "\n ")).as_bytes(),
+            );
+        }
+        raw_commit.extend_from_slice(b"\nSigned commit\n");
+        let commit_oid = repo
+            .odb()
+            .unwrap()
+            .write(git2::ObjectType::Commit, &raw_commit)
+            .unwrap();
+
+        // Extra headers are kept in the order of the commit object
+        let rev = revision_from_git(&repo, &commit_oid).unwrap();
+        assert_eq!(
+            rev.extra_headers,
+            vec![
+                (first.as_bytes().into(), value(first).as_bytes().into()),
+                (second.as_bytes().into(), value(second).as_bytes().into()),
+            ]
+        );
+        assert_eq!(rev_manifest(&rev), raw_commit);
+        let swhid = revision_swhid(&repo, &commit_oid).unwrap();
+        assert_eq!(swhid.digest_bytes(), &oid_to_array(commit_oid));
+        swhids.push(swhid);
+    }
+    // so swapping them changes the SWHID
+    assert_ne!(swhids[0], swhids[1]);
+}
+
+#[test]
+fn test_revision_raw_manifest() {
+    let tmp = assert_fs::TempDir::new().unwrap();
+    let repo = Repository::init(tmp.path()).unwrap();
+    let tree_oid = repo.treebuilder(None).unwrap().write().unwrap();
+    let write_commit = |raw_commit: &str| {
+        repo.odb()
+            .unwrap()
+            .write(git2::ObjectType::Commit, raw_commit.as_bytes())
+            .unwrap()
+    };
+    let author = "author Test User <test@example.com> 1763027354 +0100\n";
+    let committer = "committer Test User <test@example.com> 1763027354 +0100\n";
+
+    // `rev_manifest` writes extra headers after `committer`, so the commit object is
+    // kept as the manifest of the revision
+    let raw_commit = format!("tree {tree_oid}\n{author}x-custom value\n{committer}\nTest commit\n");
+    let commit_oid = write_commit(&raw_commit);
+    let mut rev = revision_from_git(&repo, &commit_oid).unwrap();
+    assert_eq!(rev.author, bs("Test User <test@example.com>"));
+    assert_eq!(rev.committer, bs("Test User <test@example.com>"));
+    assert_eq!(rev.extra_headers, vec![(bs("x-custom"), bs("value"))]);
+    assert_eq!(rev.raw_manifest.as_deref(), Some(raw_commit.as_bytes()));
+    // Software Heritage hashes revisions the same way Git hashes commits, so the
+    // revision SWHID must be the commit id.
+    assert_eq!(
+        rev.swhid(),
+        swhid_from_oid(&commit_oid, swhid::ObjectType::Revision).unwrap()
+    );
+    // the commit object is no longer hashed once the fields are changed
+    rev.message = Some(bs("Other commit\n"));
+    let fields = Revision {
+        raw_manifest: None,
+        ..rev.clone()
+    };
+    assert_eq!(rev_manifest(&rev), rev_manifest(&fields));
+
+    // Commits which libgit2 would normalize, but which are written back exactly
+    for raw_commit in [
+        // leading newlines, stripped from the message by libgit2
+        format!("tree {tree_oid}\n{author}{committer}\n\n\nTest commit\n"),
+        // spaces around the name, trimmed by libgit2
+        format!(
+            "tree {tree_oid}\nauthor  Test User  <test@example.com> 1763027354 +0100\n\
+             {committer}\nTest commit\n"
+        ),
+        // no message
+        format!("tree {tree_oid}\n{author}{committer}"),
+    ] {
+        let commit_oid = write_commit(&raw_commit);
+        let rev = revision_from_git(&repo, &commit_oid).unwrap();
+        assert_eq!(rev.raw_manifest, None);
+        assert_eq!(rev_manifest(&rev), raw_commit.as_bytes());
+
+        let swhid = revision_swhid(&repo, &commit_oid).unwrap();
+        assert_eq!(swhid.digest_bytes(), &oid_to_array(commit_oid));
+    }
+}
+
+#[test]
+fn test_release_extra_headers() {
+    let tmp = assert_fs::TempDir::new().unwrap();
+    let repo = Repository::init(tmp.path()).unwrap();
+    let tree_oid = repo.treebuilder(None).unwrap().write().unwrap();
+    let write_tag = |raw_tag: &str| {
+        repo.odb()
+            .unwrap()
+            .write(git2::ObjectType::Tag, raw_tag.as_bytes())
+            .unwrap()
+    };
+    let header = format!("object {tree_oid}\ntype tree\ntag v1.0\n");
+    let tagger = "tagger Test User <test@example.com> 1763027354 +0100\n";
+
+    // extra header after the tagger, where `rel_manifest` writes them
+    let tag_oid = write_tag(&format!("{header}{tagger}x-custom value\n\nTest tag\n"));
+    let rel = release_from_git(&repo, &tag_oid).unwrap();
+    assert_eq!(rel.extra_headers, vec![(bs("x-custom"), bs("value"))]);
+    assert_eq!(rel.raw_manifest, None);
+    assert_eq!(
+        release_swhid(&repo, &tag_oid).unwrap().digest_bytes(),
+        &oid_to_array(tag_oid)
+    );
+
+    // extra header before the tagger, so the tag object is kept as the manifest
+    let raw_tag = format!("{header}x-custom value\n{tagger}\nTest tag\n");
+    let tag_oid = write_tag(&raw_tag);
+    let rel = release_from_git(&repo, &tag_oid).unwrap();
+    assert_eq!(rel.author, Some(bs("Test User <test@example.com>")));
+    assert_eq!(rel.extra_headers, vec![(bs("x-custom"), bs("value"))]);
+    assert_eq!(rel.raw_manifest.as_deref(), Some(raw_tag.as_bytes()));
+    assert_eq!(
+        rel.swhid(),
+        swhid_from_oid(&tag_oid, swhid::ObjectType::Release).unwrap()
+    );
+
+    // spaces around the name, which libgit2 would trim
+    let raw_tag =
+        format!("{header}tagger  Test User  <test@example.com> 1763027354 +0100\n\nTest tag\n");
+    let tag_oid = write_tag(&raw_tag);
+    let rel = release_from_git(&repo, &tag_oid).unwrap();
+    assert_eq!(rel.raw_manifest, None);
+    assert_eq!(
+        release_swhid(&repo, &tag_oid).unwrap().digest_bytes(),
+        &oid_to_array(tag_oid)
+    );
+}
+
+#[test]
+fn test_snapshot_include_head() {
+    let tmp = assert_fs::TempDir::new().unwrap();
+    let (repo, commit_hash) = repo_with_main_branch(&tmp);
+
+    let main_branch = Branch {
+        name: bs("refs/heads/main"),
+        target: BranchTarget::Revision(Some(commit_hash)),
+    };
+    let head_branch = Branch {
+        name: bs("HEAD"),
+        target: BranchTarget::Alias(Some(bs("refs/heads/main"))),
+    };
+
+    // HEAD is included by default
+    let with_head = snapshot_from_git(&repo).unwrap();
+    assert_eq!(
+        with_head,
+        Snapshot::new(vec![head_branch, main_branch.clone()]).unwrap()
+    );
+    assert_eq!(
+        snapshot_from_git_with_options(&repo, &SnapshotOptions::default()).unwrap(),
+        with_head
+    );
+
+    let opts = SnapshotOptions {
+        include_head: false,
+        ..Default::default()
+    };
+    let without_head = snapshot_from_git_with_options(&repo, &opts).unwrap();
+    assert_eq!(without_head, Snapshot::new(vec![main_branch]).unwrap());
+
+    assert_ne!(
+        snapshot_swhid(&repo).unwrap(),
+        snapshot_swhid_with_options(&repo, &opts).unwrap()
+    );
+}
+
+#[test]
+#[cfg(unix)]
+fn test_directory_swhid_matches_checkout() {
+    use std::os::unix::fs::PermissionsExt;
+
+    let tmp = assert_fs::TempDir::new().unwrap();
+    let repo = Repository::init(tmp.path()).unwrap();
+
+    tmp.child("README").write_str("readme").unwrap();
+    tmp.child("bin/tool").write_str("#!/bin/sh\n").unwrap();
+    std::fs::set_permissions(
+        tmp.child("bin/tool").path(),
+        std::fs::Permissions::from_mode(0o755),
+    )
+    .unwrap();
+    tmp.child("src/lib/mod.rs").write_str("mod").unwrap();
+    tmp.child("link").symlink_to_file("README").unwrap();
+
+    let mut index = repo.index().unwrap();
+    index
+        .add_all(["*"], git2::IndexAddOption::DEFAULT, None)
+        .unwrap();
+    index.write().unwrap();
+    let tree_oid = index.write_tree().unwrap();
+
+    let swhid = directory_swhid(&repo, &tree_oid).unwrap();
+    assert_eq!(swhid.digest_bytes(), &oid_to_array(tree_oid));
+
+    let disk_swhid = swhid::DiskDirectoryBuilder::new(tmp.path())
+        .with_options(swhid::WalkOptions {
+            exclude_suffixes: vec![".git".to_owned()],
+            ..Default::default()
+        })
+        .swhid()
+        .unwrap();
+    assert_eq!(swhid, disk_swhid);
+
+    let tree = repo.find_tree(tree_oid).unwrap();
+    let subtree_oid = |path: &str| {
+        tree.get_path(std::path::Path::new(path))
+            .unwrap()
+            .id()
+            .to_string()
+    };
+    let swhids: Vec<_> = directory_swhids_recursive(&repo, &tree_oid)
+        .unwrap()
+        .into_iter()
+        .map(|(path, swhid)| (String::from_utf8(path.into()).unwrap(), swhid.digest_hex()))
+        .collect();
+    assert_eq!(
+        swhids,
+        vec![
+            (String::new(), tree_oid.to_string()),
+            ("bin".to_owned(), subtree_oid("bin")),
+            ("src".to_owned(), subtree_oid("src")),
+            ("src/lib".to_owned(), subtree_oid("src/lib")),
+        ]
+    );
+}
+
+#[test]
+fn test_revision_swhid_negative_offset() {
+    let tmp = assert_fs::TempDir::new().unwrap();
+    let repo = Repository::init(tmp.path()).unwrap();
+
+    let tree_oid = repo.treebuilder(None).unwrap().write().unwrap();
+    let tree = repo.find_tree(tree_oid).unwrap();
+    let sig = Signature::new(
+        "Test User",
+        "test@example.com",
+        &Time::new(1763027354, -330),
+    )
+    .unwrap();
+    let commit_oid = repo
+        .commit(None, &sig, &sig, "Test commit", &tree, &[])
+        .unwrap();
+
+    let rev = revision_from_git(&repo, &commit_oid).unwrap();
+    assert_eq!(rev.author_timestamp_offset, bs("-0530"));
+    assert_eq!(
+        revision_swhid(&repo, &commit_oid).unwrap(),
+        swhid::Swhid::new(swhid::ObjectType::Revision, oid_to_array(commit_oid))
+    );
+}
+
+#[test]
+fn test_revision_swhid_negative_timestamp() {
+    let tmp = assert_fs::TempDir::new().unwrap();
+    let repo = Repository::init(tmp.path()).unwrap();
+
+    // libgit2 writes negative times as unsigned 32-bit numbers, so the commit is
+    // written as Git would write it.
+    let tree_oid = repo.treebuilder(None).unwrap().write().unwrap();
+    let commit = format!(
+        "tree {tree_oid}\n\
+         author Test User <test@example.com> -14182940 -0500\n\
+         committer Test User <test@example.com> 1763027354 +0100\n\
+         \n\
+         Apollo 11\n"
+    );
+    let commit_oid = repo
+        .odb()
+        .unwrap()
+        .write(git2::ObjectType::Commit, commit.as_bytes())
+        .unwrap();
+
+    let rev = revision_from_git(&repo, &commit_oid).unwrap();
+    assert_eq!(rev.author_timestamp, -14182940);
+    assert_eq!(rev.author_timestamp_offset, bs("-0500"));
+    // parsed exactly, not hashed from the raw commit object
+    assert_eq!(rev.raw_manifest, None);
+    assert_eq!(rev_manifest(&rev), commit.as_bytes());
+    assert_eq!(
+        revision_swhid(&repo, &commit_oid).unwrap(),
+        swhid::Swhid::new(swhid::ObjectType::Revision, oid_to_array(commit_oid))
+    );
+}
+
+#[test]
+fn test_disk_directory_prunes_empty_dirs_like_git() {
+    let tmp = assert_fs::TempDir::new().unwrap();
+    let repo = Repository::init(tmp.path()).unwrap();
+    tmp.child("file.txt").write_str("content").unwrap();
+    tmp.child("empty").create_dir_all().unwrap();
+    tmp.child("sub/only_empty/nested").create_dir_all().unwrap();
+    tmp.child("sub/file.txt").write_str("content").unwrap();
+
+    let mut index = repo.index().unwrap();
+    index
+        .add_all(["*"], git2::IndexAddOption::DEFAULT, None)
+        .unwrap();
+    // Same as `git write-tree`
+    let tree_oid = index.write_tree().unwrap();
+
+    let opts = swhid::WalkOptions {
+        exclude_suffixes: vec![".git".to_owned()],
+        ..Default::default()
+    };
+    assert!(opts.prune_empty_dirs);
+    let disk_swhid = swhid::DiskDirectoryBuilder::new(tmp.path())
+        .with_options(opts.clone())
+        .swhid()
+        .unwrap();
+    assert_eq!(disk_swhid.digest_bytes(), &oid_to_array(tree_oid));
+
+    let dir = swhid::DiskDirectoryBuilder::new(tmp.path())
+        .with_options(swhid::WalkOptions {
+            prune_empty_dirs: false,
+            ..opts
+        })
+        .build()
+        .unwrap();
+    let names: Vec<_> = dir.entries().iter().map(|entry| entry.name()).collect();
+    assert_eq!(names, [&b"empty"[..], b"file.txt", b"sub"]);
+    assert_ne!(dir.swhid(), disk_swhid);
+}
+
+#[test]
+fn test_swhid_from_oid() {
+    let tmp = assert_fs::TempDir::new().unwrap();
+    let repo = Repository::init(tmp.path()).unwrap();
+    let tree_oid = repo.treebuilder(None).unwrap().write().unwrap();
+
+    let swhid = swhid_from_oid(&tree_oid, swhid::ObjectType::Directory).unwrap();
+    assert_eq!(
+        swhid.to_string(),
+        "swh:1:dir:4b825dc642cb6eb9a060e54bf8d69288fbee4904"
+    );
+    assert_eq!(swhid, directory_swhid(&repo, &tree_oid).unwrap());
+
+    assert!(swhid_from_oid(&tree_oid, swhid::ObjectType::Snapshot).is_err());
+}
+
+#[test]
+fn test_swhid_for_oid() {
+    let tmp = assert_fs::TempDir::new().unwrap();
+    let (repo, _) = repo_with_main_branch(&tmp);
+    let commit = repo.head().unwrap().peel_to_commit().unwrap();
+    let tree = commit.tree().unwrap();
+    let blob_oid = tree.get_name("test.txt").unwrap().id();
+    let sig = Signature::new("Test User", "test@example.com", &Time::new(1763027354, 60)).unwrap();
+    let tag_oid = repo
+        .tag(
+            "v1.0",
+            commit.as_object(),
+            &sig,
+            "Test tag",
+            /* force: */ false,
+        )
+        .unwrap();
+
+    assert_eq!(
+        swhid_for_oid(&repo, &blob_oid).unwrap(),
+        swhid::Content::from_bytes(b"test content".to_vec()).swhid()
+    );
+    assert_eq!(
+        swhid_for_oid(&repo, &tree.id()).unwrap().to_string(),
+        "swh:1:dir:0efb37b28c53c7e4fbd253bb04a4df14008f63fe"
+    );
+    assert_eq!(
+        swhid_for_oid(&repo, &commit.id()).unwrap(),
+        revision_swhid(&repo, &commit.id()).unwrap()
+    );
+    assert_eq!(
+        swhid_for_oid(&repo, &tag_oid).unwrap(),
+        release_swhid(&repo, &tag_oid).unwrap()
+    );
+
+    let missing = git2::Oid::from_bytes(&[0x42; 20]).unwrap();
+    assert!(swhid_for_oid(&repo, &missing).is_err());
+}
+
+#[test]
+fn test_empty_messages() {
+    let tmp = assert_fs::TempDir::new().unwrap();
+    let repo = Repository::init(tmp.path()).unwrap();
+    let tree_oid = repo.treebuilder(None).unwrap().write().unwrap();
+    let tree = repo.find_tree(tree_oid).unwrap();
+    let sig = Signature::new("Test User", "test@example.com", &Time::new(1763027354, 60)).unwrap();
+    let commit_oid = repo.commit(None, &sig, &sig, "", &tree, &[]).unwrap();
+    let tag_oid = repo
+        .tag(
+            "v1.0",
+            &repo.find_object(commit_oid, None).unwrap(),
+            &sig,
+            "",
+            /* force: */ false,
+        )
+        .unwrap();
+
+    let rev = revision_from_git(&repo, &commit_oid).unwrap();
+    assert_eq!(rev.message.as_deref(), Some(&b""[..]));
+    assert_eq!(rev.raw_manifest, None);
+    assert_eq!(rev.swhid().digest_bytes(), &oid_to_array(commit_oid));
+
+    let rel = release_from_git(&repo, &tag_oid).unwrap();
+    assert_eq!(rel.message.as_deref(), Some(&b""[..]));
+    assert_eq!(rel.raw_manifest, None);
+    assert_eq!(rel.swhid().digest_bytes(), &oid_to_array(tag_oid));
+}
+
+#[test]
+fn test_git_tree_object_hashes_like_git() {
+    let dir = swhid::Directory::new(vec![
+        swhid::Entry::new(bs("a"), 0o040000, [1; 20]),
+        swhid::Entry::new(bs("a.txt"), 0o100644, [0; 20]),
+        swhid::Entry::new(bs("link"), 0o120000, [2; 20]),
+    ])
+    .unwrap();
+
+    // Same as `git hash-object -t tree --stdin`
+    let oid = git2::Oid::hash_object(git2::ObjectType::Tree, &dir.git_tree_object()).unwrap();
+    assert_eq!(&oid_to_array(oid), dir.swhid().digest_bytes());
+}
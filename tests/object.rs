@@ -0,0 +1,78 @@
+use swhid::*;
+
+fn bs(s: &'static str) -> Box<[u8]> {
+    s.as_bytes().into()
+}
+
+#[test]
+fn heterogeneous_swhid_objects() {
+    let content = Content::from_bytes(b"Hello, World!");
+    let directory = Directory::new(vec![Entry::new(
+        bs("hello.txt"),
+        0o100644,
+        *content.swhid().digest_bytes(),
+    )])
+    .unwrap();
+    let revision = Revision {
+        directory: *directory.swhid().digest_bytes(),
+        parents: Vec::new(),
+        author: bs("Test User <test@example.com>"),
+        author_timestamp: 1763027354,
+        author_timestamp_offset: bs("+0100"),
+        committer: bs("Test User <test@example.com>"),
+        committer_timestamp: 1763027354,
+        committer_timestamp_offset: bs("+0100"),
+        extra_headers: Vec::new(),
+        message: Some(bs("Test commit")),
+        raw_manifest: None,
+    };
+    let release = Release {
+        object: *revision.swhid().digest_bytes(),
+        object_type: ReleaseTargetType::Revision,
+        name: bs("v1.0"),
+        author: None,
+        author_timestamp: None,
+        author_timestamp_offset: None,
+        extra_headers: Vec::new(),
+        message: None,
+        raw_manifest: None,
+    };
+    let snapshot = Snapshot::new(vec![Branch::new(
+        bs("refs/heads/main"),
+        BranchTarget::Revision(Some(*revision.swhid().digest_bytes())),
+    )])
+    .unwrap();
+
+    let expected = vec![
+        content.swhid(),
+        directory.swhid(),
+        revision.swhid(),
+        release.swhid(),
+        snapshot.swhid(),
+    ];
+
+    let objects: Vec<Box<dyn SwhidObject>> = vec![
+        Box::new(content),
+        Box::new(directory),
+        Box::new(revision),
+        Box::new(release),
+        Box::new(snapshot),
+    ];
+    let swhids: Vec<Swhid> = objects.iter().map(|object| object.swhid()).collect();
+    assert_eq!(swhids, expected);
+
+    let object_types: Vec<ObjectType> = objects.iter().map(|object| object.object_type()).collect();
+    assert_eq!(
+        object_types,
+        vec![
+            ObjectType::Content,
+            ObjectType::Directory,
+            ObjectType::Revision,
+            ObjectType::Release,
+            ObjectType::Snapshot,
+        ]
+    );
+    for (object, swhid) in objects.iter().zip(&swhids) {
+        assert_eq!(object.object_type(), swhid.object_type());
+    }
+}
@@ -0,0 +1,29 @@
+use swhid::prelude::*;
+
+#[test]
+fn prelude_names() {
+    let content = Content::from_bytes(b"Hello, World!\n".to_vec());
+    let swhid: Swhid = content.swhid();
+    assert_eq!(swhid.object_type(), ObjectType::Content);
+    let qualified: QualifiedSwhid = format!("{swhid};path=/hello.txt").parse().unwrap();
+    assert_eq!(qualified.core(), &swhid);
+
+    let tmp = assert_fs::TempDir::new().unwrap();
+    let builder = DiskDirectoryBuilder::new(tmp.path()).with_options(WalkOptions::default());
+    let dir: Directory = builder.build().unwrap();
+    assert_eq!(dir.swhid(), Directory::default().swhid());
+    let _: Vec<Entry> = dir.entries().to_vec();
+
+    let snp = Snapshot::new(vec![Branch::new(
+        b"HEAD".as_slice().into(),
+        BranchTarget::Revision(None),
+    )])
+    .unwrap();
+    assert_eq!(snp.swhid().object_type(), ObjectType::Snapshot);
+
+    let _: fn(&[u8]) -> Result<Revision, SwhidError> = Revision::from_manifest;
+    let _: fn(&[u8]) -> Result<Release, SwhidError> = Release::from_manifest;
+    let _: Option<DirectoryError> = None;
+    let _: Option<SnapshotError> = None;
+    let _: Option<HeaderError> = None;
+}
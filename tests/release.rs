@@ -20,6 +20,7 @@ fn simple_rel_hash() {
         author_timestamp_offset: Some(bs("+0100")),
         extra_headers: Vec::new(),
         message: Some(bs("Test tag")),
+        raw_manifest: None,
     };
 
     // Checked against the implementation in https://archive.softwareheritage.org/swh:1:dir:60e683f48069373ee85227f2d7ab2eb1a8873ddb;origin=https://gitlab.softwareheritage.org/swh/devel/swh-model.git;visit=swh:1:snp:291aefbdccd43abac57629431201c2fd55284df7;anchor=swh:1:rev:9e54500902fc00ab1e6400431e2803b9bb41cc0a
@@ -48,3 +49,147 @@ fn simple_rel_hash() {
         "swh:1:rel:46d326edb8bfc49b757ccd09930365595806bfc0",
     );
 }
+
+#[test]
+fn release_target_type_display() {
+    assert_eq!(ReleaseTargetType::Revision.to_string(), "commit");
+    assert_eq!(ReleaseTargetType::Directory.to_string(), "tree");
+    assert_eq!(ReleaseTargetType::Release.to_string(), "tag");
+    assert_eq!(ReleaseTargetType::Content.to_string(), "blob");
+}
+
+#[test]
+fn partial_authorship() {
+    let manifest_headers =
+        |author: Option<&'static str>, timestamp: Option<i64>, offset: Option<&'static str>| {
+            let rel = Release {
+                object: [0; 20],
+                object_type: ReleaseTargetType::Revision,
+                name: bs("v1.0"),
+                author: author.map(bs),
+                author_timestamp: timestamp,
+                author_timestamp_offset: offset.map(bs),
+                extra_headers: Vec::new(),
+                message: None,
+                raw_manifest: None,
+            };
+            String::from_utf8(rel_manifest(&rel)).unwrap()
+        };
+    let prefix = "object 0000000000000000000000000000000000000000\ntype commit\ntag v1.0\n";
+    let author = Some("Test User <test@example.com>");
+
+    // Like swh.model, the tagger line is written whenever there is an author...
+    assert_eq!(
+        manifest_headers(author, Some(1763027354), Some("+0100")),
+        format!("{prefix}tagger Test User <test@example.com> 1763027354 +0100\n")
+    );
+    // ... without a date unless both its timestamp and offset are known
+    for (timestamp, offset) in [
+        (None, None),
+        (Some(1763027354), None),
+        (None, Some("+0100")),
+    ] {
+        assert_eq!(
+            manifest_headers(author, timestamp, offset),
+            format!("{prefix}tagger Test User <test@example.com>\n"),
+            "{timestamp:?} {offset:?}"
+        );
+    }
+
+    // A date without an author is not represented
+    for (timestamp, offset) in [
+        (None, None),
+        (Some(1763027354), Some("+0100")),
+        (Some(1763027354), None),
+        (None, Some("+0100")),
+    ] {
+        assert_eq!(
+            manifest_headers(None, timestamp, offset),
+            prefix,
+            "{timestamp:?} {offset:?}"
+        );
+    }
+}
+
+#[test]
+fn invalid_extra_header_key() {
+    let mut rel = Release {
+        object: [0; 20],
+        object_type: ReleaseTargetType::Revision,
+        name: bs("v1.0"),
+        author: None,
+        author_timestamp: None,
+        author_timestamp_offset: None,
+        extra_headers: vec![(bs("x-key"), bs("value"))],
+        message: None,
+        raw_manifest: None,
+    };
+    assert!(rel.check_headers().is_ok());
+
+    rel.extra_headers = vec![(
+        bs("tagger Mallory <mallory@example.com> 0 +0000\nx-key"),
+        bs("value"),
+    )];
+    assert!(matches!(
+        rel.check_headers(),
+        Err(swhid::error::HeaderError::InvalidKey(_))
+    ));
+}
+
+#[test]
+fn release_target_type_object_type() {
+    use swhid::ObjectType;
+
+    for (target_type, object_type) in [
+        (ReleaseTargetType::Revision, ObjectType::Revision),
+        (ReleaseTargetType::Directory, ObjectType::Directory),
+        (ReleaseTargetType::Release, ObjectType::Release),
+        (ReleaseTargetType::Content, ObjectType::Content),
+    ] {
+        assert_eq!(target_type.object_type(), object_type);
+        assert_eq!(ObjectType::from(target_type), object_type);
+        assert_eq!(
+            ReleaseTargetType::from_object_type(object_type),
+            Some(target_type)
+        );
+    }
+    assert_eq!(
+        ReleaseTargetType::from_object_type(ObjectType::Snapshot),
+        None
+    );
+}
+
+#[test]
+fn from_manifest() {
+    let rel = Release {
+        object: [1; 20],
+        object_type: ReleaseTargetType::Revision,
+        name: bs("v1.0"),
+        author: Some(bs("Test User <test@example.com>")),
+        author_timestamp: Some(1763027354),
+        author_timestamp_offset: Some(bs("+0100")),
+        extra_headers: vec![(bs("gpgsig"), bs("sig\nnature"))],
+        message: Some(bs("Release\n")),
+        raw_manifest: None,
+    };
+    assert_eq!(Release::from_manifest(&rel_manifest(&rel)).unwrap(), rel);
+
+    // tagger without date, and no tagger
+    for author in [Some(bs("Test User <test@example.com>")), None] {
+        let rel = Release {
+            author,
+            author_timestamp: None,
+            author_timestamp_offset: None,
+            extra_headers: Vec::new(),
+            message: None,
+            ..rel.clone()
+        };
+        assert_eq!(Release::from_manifest(&rel_manifest(&rel)).unwrap(), rel);
+    }
+
+    assert!(Release::from_manifest(b"object 0101\ntype commit\ntag v1.0\n").is_err());
+    assert!(Release::from_manifest(
+        b"object 0101010101010101010101010101010101010101\ntype snapshot\ntag v1.0\n"
+    )
+    .is_err());
+}
@@ -22,6 +22,7 @@ fn simple_rev_hash() {
         committer_timestamp_offset: bs("+0100"),
         extra_headers: Vec::new(),
         message: Some(bs("Test commit")),
+        raw_manifest: None,
     };
 
     // Checked against the implementation in https://archive.softwareheritage.org/swh:1:dir:60e683f48069373ee85227f2d7ab2eb1a8873ddb;origin=https://gitlab.softwareheritage.org/swh/devel/swh-model.git;visit=swh:1:snp:291aefbdccd43abac57629431201c2fd55284df7;anchor=swh:1:rev:9e54500902fc00ab1e6400431e2803b9bb41cc0a
@@ -49,3 +50,162 @@ fn simple_rev_hash() {
         "swh:1:rev:07cde6575fb633ef9b5ecbe730e6eb97475a2fd9"
     );
 }
+
+#[test]
+fn extra_headers() {
+    let rev = |key: &'static str| Revision {
+        directory: [0; 20],
+        parents: Vec::new(),
+        author: bs("Test User <test@example.com>"),
+        author_timestamp: 1763027354,
+        author_timestamp_offset: bs("+0100"),
+        committer: bs("Test User <test@example.com>"),
+        committer_timestamp: 1763027354,
+        committer_timestamp_offset: bs("+0100"),
+        extra_headers: vec![(bs(key), bs("line 1\nline 2"))],
+        message: None,
+        raw_manifest: None,
+    };
+
+    // Newlines in values are escaped as continuation lines
+    let valid = rev("gpgsig");
+    assert!(valid.check_headers().is_ok());
+    assert!(rev_manifest(&valid).ends_with(b"\ngpgsig line 1\n line 2\n"));
+
+    // A key smuggling in a parent would otherwise be hashed as such
+    for key in [
+        "parent 0000000000000000000000000000000000000000\nx-key",
+        "x key",
+        "x-key\n",
+        "",
+    ] {
+        assert!(
+            matches!(
+                rev(key).check_headers(),
+                Err(swhid::error::HeaderError::InvalidKey(invalid)) if &*invalid == key.as_bytes()
+            ),
+            "{key:?}"
+        );
+    }
+}
+
+#[test]
+fn empty_message() {
+    let rev = Revision {
+        directory: hex::decode("0efb37b28c53c7e4fbd253bb04a4df14008f63fe")
+            .unwrap()
+            .try_into()
+            .unwrap(),
+        parents: Vec::new(),
+        author: bs("Test User <test@example.com>"),
+        author_timestamp: 1763027354,
+        author_timestamp_offset: bs("+0100"),
+        committer: bs("Test User <test@example.com>"),
+        committer_timestamp: 1763027354,
+        committer_timestamp_offset: bs("+0100"),
+        extra_headers: Vec::new(),
+        message: Some(bs("")),
+        raw_manifest: None,
+    };
+
+    // Git always separates the message from the headers, even when it is empty
+    // (`git commit --allow-empty-message -m ""`), so an empty message is `Some`.
+    // Checked with `git hash-object -t commit`
+    let manifest = rev_manifest(&rev);
+    assert!(manifest.ends_with(b"+0100\n\n"));
+    assert_eq!(
+        rev.swhid().to_string(),
+        "swh:1:rev:9fb96515deb8f2bd7493278ef768b1afc385a5d7"
+    );
+    assert_eq!(Revision::from_manifest(&manifest).unwrap(), rev);
+
+    // `None` is a revision without the separator, as in swh-model, which Git does
+    // not write but still hashes the same way (`git hash-object --literally`)
+    let rev = Revision {
+        message: None,
+        ..rev
+    };
+    let manifest = rev_manifest(&rev);
+    assert!(manifest.ends_with(b"+0100\n"));
+    assert_eq!(
+        rev.swhid().to_string(),
+        "swh:1:rev:3a0a9f1470436b0b823b95229f1d070cc82c9a7f"
+    );
+    assert_eq!(Revision::from_manifest(&manifest).unwrap(), rev);
+}
+
+#[test]
+fn negative_timestamp() {
+    // Dates before 1970 are valid in Git, and written with a minus sign
+    let rev = Revision {
+        directory: hex::decode("0efb37b28c53c7e4fbd253bb04a4df14008f63fe")
+            .unwrap()
+            .try_into()
+            .unwrap(),
+        parents: Vec::new(),
+        author: bs("Test User <test@example.com>"),
+        author_timestamp: -14182940,
+        author_timestamp_offset: bs("-0500"),
+        committer: bs("Test User <test@example.com>"),
+        committer_timestamp: 1763027354,
+        committer_timestamp_offset: bs("+0100"),
+        extra_headers: Vec::new(),
+        message: Some(bs("Apollo 11\n")),
+        raw_manifest: None,
+    };
+    let manifest = rev_manifest(&rev);
+    assert_eq!(
+        manifest,
+        b"\
+        tree 0efb37b28c53c7e4fbd253bb04a4df14008f63fe\n\
+        author Test User <test@example.com> -14182940 -0500\n\
+        committer Test User <test@example.com> 1763027354 +0100\n\
+        \n\
+        Apollo 11\n\
+        "
+    );
+    // Checked against `git hash-object -t commit`
+    assert_eq!(
+        rev.swhid().to_string(),
+        "swh:1:rev:234e22715d8f2f26298e19ed911502ee645cf129"
+    );
+    assert_eq!(Revision::from_manifest(&manifest).unwrap(), rev);
+}
+
+#[test]
+fn from_manifest() {
+    let rev = Revision {
+        directory: [0xab; 20],
+        parents: vec![[2; 20], [3; 20]],
+        author: bs("Test User <test@example.com>"),
+        author_timestamp: -1,
+        author_timestamp_offset: bs("-0000"),
+        committer: bs("Other User <other@example.com>"),
+        committer_timestamp: 1763027354,
+        committer_timestamp_offset: bs("+0100"),
+        extra_headers: vec![(bs("gpgsig"), bs("-----BEGIN\n\nsig\n-----END"))],
+        message: Some(bs("Subject\n\nBody\n")),
+        raw_manifest: None,
+    };
+    let manifest = rev_manifest(&rev);
+    assert_eq!(Revision::from_manifest(&manifest).unwrap(), rev);
+
+    let rev = Revision {
+        parents: Vec::new(),
+        extra_headers: Vec::new(),
+        message: None,
+        ..rev
+    };
+    assert_eq!(Revision::from_manifest(&rev_manifest(&rev)).unwrap(), rev);
+
+    assert!(Revision::from_manifest(b"").is_err());
+    assert!(Revision::from_manifest(&manifest[1..]).is_err());
+    // uppercase hexadecimal is not written back, so the manifest is kept to be hashed
+    let uppercase = String::from_utf8(manifest.clone())
+        .unwrap()
+        .replace(&hex::encode([0xab; 20]), &hex::encode_upper([0xab; 20]));
+    let parsed = Revision::from_manifest(uppercase.as_bytes()).unwrap();
+    assert_eq!(parsed, Revision::from_manifest(&manifest).unwrap());
+    assert_eq!(parsed.raw_manifest, Some(uppercase.as_bytes().into()));
+    assert_eq!(rev_manifest(&parsed), uppercase.as_bytes());
+}
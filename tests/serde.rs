@@ -0,0 +1,122 @@
+#![cfg(feature = "serde")]
+
+use swhid::{
+    Branch, BranchTarget, Directory, Entry, Release, ReleaseTargetType, Revision, Snapshot,
+};
+
+fn bs(s: &'static str) -> Box<[u8]> {
+    s.as_bytes().into()
+}
+
+fn roundtrip<T>(value: &T) -> String
+where
+    T: serde::Serialize + serde::de::DeserializeOwned + PartialEq + std::fmt::Debug,
+{
+    let json = serde_json::to_string(value).unwrap();
+    assert_eq!(&serde_json::from_str::<T>(&json).unwrap(), value);
+    json
+}
+
+#[test]
+fn directory() {
+    let dir = Directory::new(vec![
+        Entry::new(bs("b"), 0o100644, [0x11; 20]),
+        Entry::new(bs("a"), 0o040000, [0xab; 20]),
+    ])
+    .unwrap();
+    assert_eq!(
+        roundtrip(&dir),
+        format!(
+            r#"[{{"name":"61","mode":16384,"id":"{}"}},{{"name":"62","mode":33188,"id":"{}"}}]"#,
+            "ab".repeat(20),
+            "11".repeat(20)
+        )
+    );
+
+    // Entries are sorted and checked again
+    let unsorted = format!(
+        r#"[{{"name":"62","mode":33188,"id":"{0}"}},{{"name":"61","mode":16384,"id":"{0}"}}]"#,
+        "00".repeat(20)
+    );
+    let parsed: Directory = serde_json::from_str(&unsorted).unwrap();
+    assert_eq!(parsed.entries()[0].name(), b"a");
+    let duplicate = format!(
+        r#"[{{"name":"61","mode":33188,"id":"{0}"}},{{"name":"61","mode":16384,"id":"{0}"}}]"#,
+        "00".repeat(20)
+    );
+    assert!(serde_json::from_str::<Directory>(&duplicate).is_err());
+
+    // Any mode is accepted, as for entries of old Git trees
+    let dir = Directory::new(vec![Entry::new(bs("a"), 0o100664, [0x11; 20])]).unwrap();
+    roundtrip(&dir);
+    assert!(Entry::try_new(bs("a"), 0o100664, [0x11; 20]).is_err());
+
+    // Ids must be 20 bytes
+    let short_id = r#"[{"name":"61","mode":33188,"id":"00"}]"#;
+    assert!(serde_json::from_str::<Directory>(short_id).is_err());
+    let not_hex = r#"[{"name":"zz","mode":33188,"id":"00"}]"#;
+    assert!(serde_json::from_str::<Directory>(not_hex).is_err());
+}
+
+#[test]
+fn snapshot() {
+    let snp = Snapshot::new(vec![
+        Branch::new(bs("refs/heads/main"), BranchTarget::Revision(Some([1; 20]))),
+        Branch::new(bs("HEAD"), BranchTarget::Alias(Some(bs("refs/heads/main")))),
+        Branch::new(bs("dangling"), BranchTarget::Release(None)),
+    ])
+    .unwrap();
+    let json = roundtrip(&snp);
+    assert!(json.starts_with(
+        r#"[{"name":"48454144","target":{"Alias":"726566732f68656164732f6d61696e"}}"#
+    ));
+    assert!(json.contains(r#""target":{"Release":null}"#));
+    assert_eq!(
+        serde_json::from_str::<Snapshot>(&json).unwrap().swhid(),
+        snp.swhid()
+    );
+}
+
+#[test]
+fn revision() {
+    let rev = Revision {
+        directory: [2; 20],
+        parents: vec![[3; 20], [4; 20]],
+        author: bs("Test User <test@example.com>"),
+        author_timestamp: 1763027354,
+        author_timestamp_offset: bs("+0100"),
+        committer: bs("Test User <test@example.com>"),
+        committer_timestamp: -1,
+        committer_timestamp_offset: bs("-0000"),
+        extra_headers: vec![(bs("encoding"), bs("latin-1"))],
+        message: Some(bs("Test commit\n")),
+        raw_manifest: None,
+    };
+    let json = roundtrip(&rev);
+    assert!(json.contains(r#""extra_headers":[["656e636f64696e67","6c6174696e2d31"]]"#));
+    assert!(!json.contains("raw_manifest"));
+}
+
+#[test]
+fn release() {
+    let rel = Release {
+        object: [5; 20],
+        object_type: ReleaseTargetType::Revision,
+        name: bs("v1.0"),
+        author: Some(bs("Test User <test@example.com>")),
+        author_timestamp: Some(1763027354),
+        author_timestamp_offset: Some(bs("+0100")),
+        extra_headers: Vec::new(),
+        message: None,
+        raw_manifest: Some(bs("object ...")),
+    };
+    let json = roundtrip(&rel);
+    assert!(json.contains(r#""object_type":"Revision""#));
+    assert!(json.contains(r#""message":null"#));
+    // the raw manifest is not serialized, as it could not be checked on deserialization
+    assert!(!json.contains("raw_manifest"));
+    assert_eq!(
+        serde_json::from_str::<Release>(&json).unwrap().raw_manifest,
+        None
+    );
+}
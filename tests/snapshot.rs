@@ -5,6 +5,7 @@ fn name(s: &'static str) -> Box<[u8]> {
 }
 
 #[test]
+#[allow(clippy::octal_escapes)]
 fn simple_snp_hash() {
     let snp = Snapshot::new(vec![
         Branch::new(
@@ -35,6 +36,7 @@ fn simple_snp_hash() {
 }
 
 #[test]
+#[allow(clippy::octal_escapes)]
 fn snp_order() {
     let snp = Snapshot::new(vec![
         Branch::new(
@@ -64,6 +66,55 @@ fn snp_order() {
     );
 }
 
+#[test]
+fn sort_branches_matches_snp_order() {
+    let mut branches = vec![
+        Branch::new(
+            name("refs/heads/main"),
+            BranchTarget::Revision(Some([1; 20])),
+        ),
+        Branch::new(
+            name("refs/heads/develop"),
+            BranchTarget::Revision(Some([2; 20])),
+        ),
+    ];
+    let snp = Snapshot::new(branches.clone()).unwrap();
+
+    sort_branches(&mut branches).unwrap();
+    assert_eq!(branches, snp.branches());
+}
+
+#[test]
+fn sort_branches_errors() {
+    let mut branches = vec![
+        Branch::new(
+            name("refs/heads/main"),
+            BranchTarget::Revision(Some([1; 20])),
+        ),
+        Branch::new(
+            name("refs/heads/main"),
+            BranchTarget::Revision(Some([2; 20])),
+        ),
+    ];
+    assert!(matches!(
+        sort_branches(&mut branches),
+        Err(swhid::error::SnapshotError::DuplicateBranchName(_))
+    ));
+
+    let mut branches = vec![Branch::new(
+        name("refs/heads/ma\0in"),
+        BranchTarget::Revision(Some([1; 20])),
+    )];
+    assert!(matches!(
+        sort_branches(&mut branches),
+        Err(swhid::error::SnapshotError::InvalidByteInName { byte: b'\0', .. })
+    ));
+    assert!(sort_branches(&mut branches)
+        .unwrap_err()
+        .to_string()
+        .starts_with(r"Invalid byte '\0' in name"));
+}
+
 #[test]
 fn empty_snp_hash() {
     let snp = Snapshot::new(vec![]).unwrap();
@@ -110,3 +161,236 @@ fn snp_with_alias() {
         "swh:1:snp:9ecd7950d10ed3d02bfcf9c4a534f173697ab9f3"
     );
 }
+
+#[test]
+fn snp_from_refs() {
+    let snp = Snapshot::from_refs([
+        (
+            name("refs/heads/develop"),
+            BranchTarget::Revision(Some([2; 20])),
+        ),
+        (
+            name("HEAD"),
+            BranchTarget::Alias(Some(name("refs/heads/main"))),
+        ),
+        (
+            name("refs/heads/main"),
+            BranchTarget::Revision(Some([1; 20])),
+        ),
+    ])
+    .unwrap();
+
+    // same as `snp_with_alias`
+    assert_eq!(
+        snp.swhid().to_string(),
+        "swh:1:snp:9ecd7950d10ed3d02bfcf9c4a534f173697ab9f3"
+    );
+
+    assert!(Snapshot::from_refs([
+        (name("HEAD"), BranchTarget::Revision(None)),
+        (name("HEAD"), BranchTarget::Release(None)),
+    ])
+    .is_err());
+}
+
+#[test]
+fn branch_target_from_raw() {
+    assert_eq!(
+        BranchTarget::from_raw("revision", Some(&[1; 20])).unwrap(),
+        BranchTarget::Revision(Some([1; 20]))
+    );
+    assert_eq!(
+        BranchTarget::from_raw("release", None).unwrap(),
+        BranchTarget::Release(None)
+    );
+    assert_eq!(
+        BranchTarget::from_raw("alias", Some(b"refs/heads/main")).unwrap(),
+        BranchTarget::Alias(Some(name("refs/heads/main")))
+    );
+
+    assert!(matches!(
+        BranchTarget::from_raw("revision", Some(b"")),
+        Err(swhid::error::SnapshotError::InvalidTargetLength {
+            target_type: "revision",
+            len: 0
+        })
+    ));
+    assert!(matches!(
+        BranchTarget::from_raw("directory", Some(&[1; 21])),
+        Err(swhid::error::SnapshotError::InvalidTargetLength { len: 21, .. })
+    ));
+    assert!(matches!(
+        BranchTarget::from_raw("alias", Some(b"")),
+        Err(swhid::error::SnapshotError::EmptyAliasTarget)
+    ));
+    assert_eq!(
+        BranchTarget::from_raw("alias", None).unwrap(),
+        BranchTarget::Alias(None)
+    );
+    assert!(matches!(
+        BranchTarget::from_raw("tag", Some(&[1; 20])),
+        Err(swhid::error::SnapshotError::InvalidTargetType(_))
+    ));
+}
+
+#[test]
+fn branch_target_object_type() {
+    use swhid::ObjectType;
+
+    for object_type in [
+        ObjectType::Content,
+        ObjectType::Directory,
+        ObjectType::Revision,
+        ObjectType::Release,
+        ObjectType::Snapshot,
+    ] {
+        for id in [Some([1; 20]), None] {
+            let target = BranchTarget::from_object_type(object_type, id);
+            assert_eq!(target.object_type(), Some(object_type));
+            // agrees with the type names of snapshot manifests
+            assert_eq!(
+                BranchTarget::from_raw(target.type_name(), id.as_ref().map(|id| &id[..])).unwrap(),
+                target
+            );
+        }
+    }
+    assert_eq!(
+        BranchTarget::Revision(Some([1; 20])).object_type(),
+        Some(ObjectType::Revision)
+    );
+    // aliases point to branches, not objects
+    assert_eq!(
+        BranchTarget::Alias(Some(b"refs/heads/main".to_vec().into())).object_type(),
+        None
+    );
+    assert_eq!(BranchTarget::Alias(None).object_type(), None);
+}
+
+#[test]
+fn snp_from_manifest() {
+    let snp = Snapshot::new(vec![
+        Branch::new(
+            name("refs/heads/main"),
+            BranchTarget::Revision(Some([1; 20])),
+        ),
+        Branch::new(
+            name("refs/heads/develop"),
+            BranchTarget::Revision(Some([2; 20])),
+        ),
+        Branch::new(
+            name("HEAD"),
+            BranchTarget::Alias(Some(name("refs/heads/main"))),
+        ),
+        Branch::new(name("refs/tags/gone"), BranchTarget::Release(None)),
+    ])
+    .unwrap();
+    let manifest = snp.manifest();
+    assert_eq!(Snapshot::from_manifest(&manifest).unwrap(), snp);
+
+    // same as `snp_with_alias`
+    let manifest = b"\
+        alias HEAD\x0015:refs/heads/main\
+        revision refs/heads/develop\x0020:\x02\x02\x02\x02\x02\x02\x02\x02\x02\x02\x02\x02\x02\x02\x02\x02\x02\x02\x02\x02\
+        revision refs/heads/main\x0020:\x01\x01\x01\x01\x01\x01\x01\x01\x01\x01\x01\x01\x01\x01\x01\x01\x01\x01\x01\x01\
+        ";
+    let snp = Snapshot::from_manifest(manifest).unwrap();
+    assert_eq!(snp.manifest(), manifest);
+    assert_eq!(
+        snp.swhid().to_string(),
+        "swh:1:snp:9ecd7950d10ed3d02bfcf9c4a534f173697ab9f3"
+    );
+    assert_eq!(Snapshot::from_manifest(b"").unwrap(), Snapshot::default());
+
+    for invalid in [
+        // not sorted
+        &b"alias b\x001:a\
+           alias a\x001:b"[..],
+        b"alias a\x001:b\
+          alias a\x001:b",
+        b"alias HEAD\x0015:refs/heads",
+        b"alias HEAD\x00+1:a",
+        b"alias HEAD\x0001:a",
+        b"alias HEAD",
+        b"revision HEAD\x002:ab",
+        b"branch HEAD\x000:",
+    ] {
+        assert!(
+            Snapshot::from_manifest(invalid).is_err(),
+            "{}",
+            String::from_utf8_lossy(invalid)
+        );
+    }
+}
+
+#[test]
+fn branch_target_from_str() {
+    assert_eq!(
+        "swh:1:rel:0101010101010101010101010101010101010101"
+            .parse::<BranchTarget>()
+            .unwrap(),
+        BranchTarget::Release(Some([1; 20]))
+    );
+    assert!("refs/heads/main".parse::<BranchTarget>().is_err());
+}
+
+#[test]
+fn snp_resolve_alias() {
+    let snp = Snapshot::new(vec![
+        Branch::new(
+            name("HEAD"),
+            BranchTarget::Alias(Some(name("refs/heads/dev"))),
+        ),
+        Branch::new(
+            name("refs/heads/dev"),
+            BranchTarget::Alias(Some(name("refs/heads/main"))),
+        ),
+        Branch::new(
+            name("refs/heads/main"),
+            BranchTarget::Revision(Some([1; 20])),
+        ),
+        Branch::new(name("dangling"), BranchTarget::Alias(None)),
+        Branch::new(name("missing"), BranchTarget::Alias(Some(name("nowhere")))),
+        Branch::new(name("loop/a"), BranchTarget::Alias(Some(name("loop/b")))),
+        Branch::new(name("loop/b"), BranchTarget::Alias(Some(name("loop/a")))),
+        Branch::new(name("self"), BranchTarget::Alias(Some(name("self")))),
+    ])
+    .unwrap();
+
+    let main = Some(&BranchTarget::Revision(Some([1; 20])));
+    assert_eq!(snp.resolve_alias(b"HEAD"), main);
+    assert_eq!(snp.resolve_alias(b"refs/heads/dev"), main);
+    assert_eq!(snp.resolve_alias(b"refs/heads/main"), main);
+
+    assert_eq!(snp.resolve_alias(b"dangling"), None);
+    assert_eq!(snp.resolve_alias(b"missing"), None);
+    assert_eq!(snp.resolve_alias(b"nowhere"), None);
+    assert_eq!(snp.resolve_alias(b"loop/a"), None);
+    assert_eq!(snp.resolve_alias(b"self"), None);
+}
+
+#[test]
+fn snp_snapshot_target() {
+    let inner = Snapshot::new(vec![Branch::new(
+        name("refs/heads/main"),
+        BranchTarget::Revision(Some([1; 20])),
+    )])
+    .unwrap();
+    let target = BranchTarget::snapshot(&inner);
+    assert_eq!(
+        target,
+        BranchTarget::Snapshot(Some(*inner.swhid().digest_bytes()))
+    );
+    assert_eq!(target.type_name(), "snapshot");
+
+    let outer = Snapshot::new(vec![
+        Branch::new(name("repos/a"), BranchTarget::Snapshot(Some([7; 20]))),
+        Branch::new(name("repos/b"), BranchTarget::Snapshot(None)),
+    ])
+    .unwrap();
+    let manifest = outer.manifest();
+    let mut expected = b"snapshot repos/a\x0020:".to_vec();
+    expected.extend([7; 20]);
+    expected.extend(b"snapshot repos/b\x000:");
+    assert_eq!(manifest, expected);
+    assert_eq!(Snapshot::from_manifest(&manifest).unwrap(), outer);
+}
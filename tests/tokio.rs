@@ -0,0 +1,40 @@
+#![cfg(feature = "tokio")]
+
+use assert_fs::prelude::*;
+
+use swhid::{Content, DiskDirectoryBuilder};
+
+#[tokio::test]
+async fn content_from_reader_async() {
+    let tmp = assert_fs::TempDir::new().unwrap();
+    tmp.child("a.txt").write_str("Hello, World!\n").unwrap();
+
+    let file = tokio::fs::File::open(tmp.child("a.txt").path())
+        .await
+        .unwrap();
+    let content = Content::from_reader_async(file).await.unwrap();
+    let expected = Content::from_bytes(&b"Hello, World!\n"[..]).swhid();
+    assert_eq!(content.swhid(), expected);
+    assert_eq!(content.swhid_async().await, expected);
+}
+
+#[tokio::test]
+async fn directory_swhid_async() {
+    let tmp = assert_fs::TempDir::new().unwrap();
+    tmp.child("README").write_str("readme").unwrap();
+    tmp.child("src/lib.rs").write_str("lib").unwrap();
+    tmp.child("src/a/b/deep.rs").write_str("deep").unwrap();
+    tmp.child("link").symlink_to_file("README").unwrap();
+
+    let builder = DiskDirectoryBuilder::new(tmp.path());
+    assert_eq!(
+        builder.swhid_async().await.unwrap(),
+        builder.swhid().unwrap()
+    );
+
+    let missing = tmp.path().join("missing");
+    assert!(DiskDirectoryBuilder::new(&missing)
+        .swhid_async()
+        .await
+        .is_err());
+}
@@ -0,0 +1,69 @@
+#![cfg(feature = "tracing")]
+
+use std::fmt;
+use std::sync::{Arc, Mutex};
+
+use swhid::DiskDirectoryBuilder;
+use tracing::field::{Field, Visit};
+use tracing::span::{Attributes, Id, Record};
+use tracing::{Event, Metadata, Subscriber};
+
+/// Subscriber keeping the message of every event
+#[derive(Clone, Default)]
+struct Messages(Arc<Mutex<Vec<String>>>);
+
+struct MessageVisitor<'a>(&'a mut Option<String>);
+
+impl Visit for MessageVisitor<'_> {
+    fn record_debug(&mut self, field: &Field, value: &dyn fmt::Debug) {
+        if field.name() == "message" {
+            *self.0 = Some(format!("{value:?}"));
+        }
+    }
+}
+
+impl Subscriber for Messages {
+    fn enabled(&self, _metadata: &Metadata<'_>) -> bool {
+        true
+    }
+
+    fn new_span(&self, _span: &Attributes<'_>) -> Id {
+        Id::from_u64(1)
+    }
+
+    fn record(&self, _span: &Id, _values: &Record<'_>) {}
+
+    fn record_follows_from(&self, _span: &Id, _follows: &Id) {}
+
+    fn event(&self, event: &Event<'_>) {
+        let mut message = None;
+        event.record(&mut MessageVisitor(&mut message));
+        if let Some(message) = message {
+            self.0.lock().unwrap().push(message);
+        }
+    }
+
+    fn enter(&self, _span: &Id) {}
+
+    fn exit(&self, _span: &Id) {}
+}
+
+#[test]
+fn file_hash_events() {
+    let tmp = assert_fs::TempDir::new().unwrap();
+    std::fs::write(tmp.path().join("a.txt"), "a").unwrap();
+    std::fs::create_dir(tmp.path().join("sub")).unwrap();
+    std::fs::write(tmp.path().join("sub/b.txt"), "b").unwrap();
+    std::fs::write(tmp.path().join("sub/c.txt"), "c").unwrap();
+
+    let messages = Messages::default();
+    tracing::subscriber::with_default(messages.clone(), || {
+        DiskDirectoryBuilder::new(tmp.path()).swhid().unwrap();
+    });
+
+    let messages = messages.0.lock().unwrap();
+    let count = |message: &str| messages.iter().filter(|m| *m == message).count();
+    assert_eq!(count("hashed file"), 3);
+    assert_eq!(count("sorting entries"), 2);
+    assert_eq!(count("duplicate entry name"), 0);
+}